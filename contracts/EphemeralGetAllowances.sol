@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "./TokenUtils.sol";
+
+/// @notice A lens that batches fetching of ERC20 allowances without deployment
+/// @author Aperture Finance
+/// @dev The return data can be accessed externally by `eth_call` without a `to` address or internally by catching the
+/// revert data, and decoded by `abi.decode(data, (uint256[][]))`
+contract EphemeralGetAllowances is TokenUtils {
+    constructor(address owner, address[] memory tokens, address[] memory spenders) payable {
+        uint256[][] memory allowances = getAllowances(owner, tokens, spenders);
+        bytes memory returnData = abi.encode(allowances);
+        assembly ("memory-safe") {
+            revert(add(returnData, 0x20), mload(returnData))
+        }
+    }
+
+    /// @notice Get the allowances granted by an owner to spenders in tokens
+    /// @dev Public function to expose the abi for easier decoding using TypeChain
+    /// @param owner The address of the owner
+    /// @param tokens The addresses of the tokens, where an address without code, that reverts or that does not return a
+    /// word has zero allowances
+    /// @param spenders The addresses of the spenders
+    /// @return allowances The allowance of `spenders[j]` in `tokens[i]` at `allowances[i][j]`
+    function getAllowances(
+        address owner,
+        address[] memory tokens,
+        address[] memory spenders
+    ) public payable returns (uint256[][] memory allowances) {
+        unchecked {
+            uint256 length = tokens.length;
+            uint256 numSpenders = spenders.length;
+            allowances = new uint256[][](length);
+            for (uint256 i; i < length; ++i) {
+                uint256[] memory row = new uint256[](numSpenders);
+                address token = tokens[i];
+                for (uint256 j; j < numSpenders; ++j) {
+                    (, row[j]) = tryUint(token, abi.encodeWithSelector(IERC20.allowance.selector, owner, spenders[j]));
+                }
+                allowances[i] = row;
+            }
+        }
+    }
+}
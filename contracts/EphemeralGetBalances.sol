@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "./TokenUtils.sol";
+
+/// @notice A lens that batches fetching of ERC20 and native token balances without deployment
+/// @author Aperture Finance
+/// @dev The return data can be accessed externally by `eth_call` without a `to` address or internally by catching the
+/// revert data, and decoded by `abi.decode(data, (uint256[][]))`
+contract EphemeralGetBalances is TokenUtils {
+    constructor(address[] memory owners, address[] memory tokens) payable {
+        uint256[][] memory balances = getBalances(owners, tokens);
+        bytes memory returnData = abi.encode(balances);
+        assembly ("memory-safe") {
+            revert(add(returnData, 0x20), mload(returnData))
+        }
+    }
+
+    /// @notice Get the balances of owners in tokens
+    /// @dev Public function to expose the abi for easier decoding using TypeChain
+    /// @param owners The addresses of the owners
+    /// @param tokens The addresses of the tokens, where the zero address represents the native token and an address
+    /// without code, that reverts or that does not return a word has zero balances
+    /// @return balances The balance of `owners[i]` in `tokens[j]` at `balances[i][j]`
+    function getBalances(
+        address[] memory owners,
+        address[] memory tokens
+    ) public payable returns (uint256[][] memory balances) {
+        unchecked {
+            uint256 length = owners.length;
+            uint256 numTokens = tokens.length;
+            balances = new uint256[][](length);
+            for (uint256 i; i < length; ++i) {
+                address owner = owners[i];
+                uint256[] memory row = new uint256[](numTokens);
+                for (uint256 j; j < numTokens; ++j) {
+                    address token = tokens[j];
+                    if (token == address(0)) {
+                        row[j] = owner.balance;
+                    } else {
+                        (, row[j]) = tryUint(token, abi.encodeWithSelector(IERC20.balanceOf.selector, owner));
+                    }
+                }
+                balances[i] = row;
+            }
+        }
+    }
+}
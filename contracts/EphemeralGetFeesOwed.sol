@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "./PoolUtils.sol";
+
+/// @notice A lens that batches fetching of the fees owed to positions in a Uniswap v3 pool without deployment
+/// @author Aperture Finance
+/// @dev The return data can be accessed externally by `eth_call` without a `to` address or internally by catching the
+/// revert data, and decoded by `abi.decode(data, (PositionFees[]))`
+contract EphemeralGetFeesOwed is PoolUtils {
+    constructor(V3PoolCallee pool, PositionKey[] memory keys) payable {
+        PositionFees[] memory fees = getFeesOwed(pool, keys);
+        bytes memory returnData = abi.encode(fees);
+        assembly ("memory-safe") {
+            revert(add(returnData, 0x20), mload(returnData))
+        }
+    }
+
+    /// @notice Get the fees owed to positions in a pool
+    /// @dev Public function to expose the abi for easier decoding using TypeChain
+    /// @param pool The address of the pool
+    /// @param keys The position keys
+    /// @return fees The fees owed to each position
+    function getFeesOwed(
+        V3PoolCallee pool,
+        PositionKey[] memory keys
+    ) public payable returns (PositionFees[] memory fees) {
+        unchecked {
+            uint256 length = keys.length;
+            fees = new PositionFees[](length);
+            for (uint256 i; i < length; ++i) {
+                PositionKey memory key = keys[i];
+                (fees[i].tokensOwed0, fees[i].tokensOwed1) = feesOwed(
+                    pool,
+                    key.owner,
+                    key.tickLower,
+                    key.tickUpper
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "./TokenUtils.sol";
+
+/// @notice A lens that batches fetching of ERC20 token metadata without deployment
+/// @author Aperture Finance
+/// @dev The return data can be accessed externally by `eth_call` without a `to` address or internally by catching the
+/// revert data, and decoded by `abi.decode(data, (TokenMetadata[]))`
+contract EphemeralGetTokenMetadata is TokenUtils {
+    constructor(address[] memory tokens) payable {
+        TokenMetadata[] memory metadata = getTokenMetadata(tokens);
+        bytes memory returnData = abi.encode(metadata);
+        assembly ("memory-safe") {
+            revert(add(returnData, 0x20), mload(returnData))
+        }
+    }
+
+    /// @notice Get the metadata of tokens
+    /// @dev Public function to expose the abi for easier decoding using TypeChain
+    /// @param tokens The addresses of the tokens
+    /// @return metadata The name, symbol, decimals and total supply of each token
+    function getTokenMetadata(address[] memory tokens) public payable returns (TokenMetadata[] memory metadata) {
+        unchecked {
+            uint256 length = tokens.length;
+            metadata = new TokenMetadata[](length);
+            for (uint256 i; i < length; ++i) {
+                tokenMetadataInPlace(tokens[i], metadata[i]);
+            }
+        }
+    }
+}
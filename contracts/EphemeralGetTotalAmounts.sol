@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "./PoolUtils.sol";
+
+/// @notice A lens that batches fetching of the total amounts held in positions in a Uniswap v3 pool without deployment
+/// @author Aperture Finance
+/// @dev The return data can be accessed externally by `eth_call` without a `to` address or internally by catching the
+/// revert data, and decoded by `abi.decode(data, (PositionAmounts[]))`
+contract EphemeralGetTotalAmounts is PoolUtils {
+    constructor(V3PoolCallee pool, PositionKey[] memory keys) payable {
+        PositionAmounts[] memory amounts = getTotalAmounts(pool, keys);
+        bytes memory returnData = abi.encode(amounts);
+        assembly ("memory-safe") {
+            revert(add(returnData, 0x20), mload(returnData))
+        }
+    }
+
+    /// @notice Get the total amounts of token0 and token1 held in positions in a pool including fees
+    /// @dev Public function to expose the abi for easier decoding using TypeChain
+    /// @param pool The address of the pool
+    /// @param keys The position keys
+    /// @return amounts The total amounts held in each position
+    function getTotalAmounts(
+        V3PoolCallee pool,
+        PositionKey[] memory keys
+    ) public payable returns (PositionAmounts[] memory amounts) {
+        unchecked {
+            uint256 length = keys.length;
+            amounts = new PositionAmounts[](length);
+            for (uint256 i; i < length; ++i) {
+                PositionKey memory key = keys[i];
+                (amounts[i].amount0, amounts[i].amount1) = totalAmounts(
+                    pool,
+                    key.owner,
+                    key.tickLower,
+                    key.tickUpper
+                );
+            }
+        }
+    }
+}
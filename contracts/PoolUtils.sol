@@ -2,8 +2,10 @@
 pragma solidity ^0.8.0;
 
 import {FullMath} from "@aperture_finance/uni-v3-lib/src/FullMath.sol";
+import {LiquidityAmounts} from "@aperture_finance/uni-v3-lib/src/LiquidityAmounts.sol";
 import {IUniswapV3Pool, PoolCaller, V3PoolCallee} from "@aperture_finance/uni-v3-lib/src/PoolCaller.sol";
 import {TickBitmap} from "@aperture_finance/uni-v3-lib/src/TickBitmap.sol";
+import {TickMath} from "@aperture_finance/uni-v3-lib/src/TickMath.sol";
 import {LibBit} from "solady/src/utils/LibBit.sol";
 
 /// @title Pool utility contract
@@ -11,6 +13,7 @@ import {LibBit} from "solady/src/utils/LibBit.sol";
 /// @notice Provides utility functions for Uniswap v3 pools
 abstract contract PoolUtils {
     using FullMath for uint128;
+    using TickMath for int24;
 
     uint256 internal constant Q128 = 1 << 128;
 
@@ -36,6 +39,18 @@ abstract contract PoolUtils {
         uint256 feeGrowthOutside1X128;
     }
 
+    /// @notice Fees owed to a position
+    struct PositionFees {
+        uint128 tokensOwed0;
+        uint128 tokensOwed1;
+    }
+
+    /// @notice Total amounts of token0 and token1 held in a position
+    struct PositionAmounts {
+        uint256 amount0;
+        uint256 amount1;
+    }
+
     /// @notice Returns the position key of the given owner and tick range
     /// @param key owner The address of the position owner
     /// tickLower The lower tick boundary of the position
@@ -139,4 +154,68 @@ abstract contract PoolUtils {
             count += LibBit.popCount(bitmap);
         }
     }
+
+    /// @notice Returns the fees owed to a position
+    /// @param pool Uniswap v3 pool
+    /// @param owner The address of the position owner
+    /// @param tickLower The lower tick boundary of the position
+    /// @param tickUpper The upper tick boundary of the position
+    /// @return tokensOwed0 The amount of token0 owed to the position
+    /// @return tokensOwed1 The amount of token1 owed to the position
+    function feesOwed(
+        V3PoolCallee pool,
+        address owner,
+        int24 tickLower,
+        int24 tickUpper
+    ) internal view returns (uint128 tokensOwed0, uint128 tokensOwed1) {
+        bytes32 key = getPositionKey(PositionKey(owner, tickLower, tickUpper));
+        PoolCaller.PositionInfo memory info = pool.positions(key);
+        (, int24 tickCurrent) = pool.sqrtPriceX96AndTick();
+
+        if (info.liquidity != 0) {
+            (uint256 feeGrowthInside0X128, uint256 feeGrowthInside1X128) = getFeeGrowthInside(
+                pool,
+                tickLower,
+                tickUpper,
+                tickCurrent
+            );
+            (tokensOwed0, tokensOwed1) = calculateFeesGrowth(
+                info.liquidity,
+                feeGrowthInside0X128,
+                feeGrowthInside1X128,
+                info.feeGrowthInside0LastX128,
+                info.feeGrowthInside1LastX128
+            );
+        }
+    }
+
+    /// @notice Returns the total amounts of token0 and token1 held in a position including fees
+    /// @param pool Uniswap v3 pool
+    /// @param owner The address of the position owner
+    /// @param tickLower The lower tick boundary of the position
+    /// @param tickUpper The upper tick boundary of the position
+    /// @return amount0 The total amount of token0 held in the position
+    /// @return amount1 The total amount of token1 held in the position
+    function totalAmounts(
+        V3PoolCallee pool,
+        address owner,
+        int24 tickLower,
+        int24 tickUpper
+    ) internal view returns (uint256 amount0, uint256 amount1) {
+        bytes32 key = getPositionKey(PositionKey(owner, tickLower, tickUpper));
+        PoolCaller.PositionInfo memory info = pool.positions(key);
+        (uint160 sqrtPriceX96, ) = pool.sqrtPriceX96AndTick();
+
+        if (info.liquidity != 0) {
+            (amount0, amount1) = LiquidityAmounts.getAmountsForLiquidity(
+                sqrtPriceX96,
+                tickLower.getSqrtRatioAtTick(),
+                tickUpper.getSqrtRatioAtTick(),
+                info.liquidity
+            );
+            (uint256 fees0, uint256 fees1) = feesOwed(pool, owner, tickLower, tickUpper);
+            amount0 += fees0;
+            amount1 += fees1;
+        }
+    }
 }
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity ^0.8.0;
+
+import "@openzeppelin/contracts/token/ERC20/extensions/IERC20Metadata.sol";
+
+/// @title Token utility contract
+/// @author Aperture Finance
+/// @notice Provides utility functions to query ERC20 tokens that may not conform to the standard
+abstract contract TokenUtils {
+    /// @notice Token metadata and whether each field was returned by the token
+    struct TokenMetadata {
+        address token;
+        string name;
+        string symbol;
+        // 18 if not returned by the token
+        uint8 decimals;
+        uint256 totalSupply;
+        bool hasName;
+        bool hasSymbol;
+        bool hasDecimals;
+        bool hasTotalSupply;
+    }
+
+    /// @dev Call a function on a token, returning false if the token has no code or the call reverts
+    /// @param token ERC20 token
+    /// @param data The abi-encoded calldata
+    /// @return success Whether the call succeeded
+    /// @return returnData The return data of the call
+    function tryCall(address token, bytes memory data) internal view returns (bool success, bytes memory returnData) {
+        if (token.code.length != 0) {
+            (success, returnData) = token.staticcall(data);
+        }
+    }
+
+    /// @dev Call a function without arguments on a token, returning false if the token has no code or the call reverts
+    /// @param token ERC20 token
+    /// @param selector The function selector
+    /// @return success Whether the call succeeded
+    /// @return returnData The return data of the call
+    function tryCall(address token, bytes4 selector) internal view returns (bool success, bytes memory returnData) {
+        return tryCall(token, abi.encodeWithSelector(selector));
+    }
+
+    /// @dev Call a function returning a uint256 on a token
+    /// @param token ERC20 token
+    /// @param data The abi-encoded calldata
+    /// @return success Whether the call succeeded and returned a word
+    /// @return value The returned value
+    function tryUint(address token, bytes memory data) internal view returns (bool success, uint256 value) {
+        bytes memory returnData;
+        (success, returnData) = tryCall(token, data);
+        success = success && returnData.length >= 0x20;
+        if (success) {
+            value = abi.decode(returnData, (uint256));
+        }
+    }
+
+    /// @dev Call a function without arguments returning a uint256 on a token
+    /// @param token ERC20 token
+    /// @param selector The function selector
+    /// @return success Whether the call succeeded and returned a word
+    /// @return value The returned value
+    function tryUint(address token, bytes4 selector) internal view returns (bool success, uint256 value) {
+        return tryUint(token, abi.encodeWithSelector(selector));
+    }
+
+    /// @dev Call a function returning a string or a bytes32 (e.g. MKR) on a token
+    /// @param token ERC20 token
+    /// @param selector The function selector
+    /// @return success Whether the call succeeded and returned a well-formed string or bytes32
+    /// @return value The returned string with trailing zero bytes removed if it was a bytes32
+    function tryString(address token, bytes4 selector) internal view returns (bool success, string memory value) {
+        bytes memory returnData;
+        (success, returnData) = tryCall(token, selector);
+        if (!success) return (false, value);
+        uint256 length = returnData.length;
+        if (length == 0x20) {
+            bytes32 word = bytes32(returnData);
+            uint256 n;
+            while (n < 0x20 && word[n] != bytes1(0)) ++n;
+            bytes memory str = new bytes(n);
+            for (uint256 i; i < n; ++i) {
+                str[i] = word[i];
+            }
+            return (true, string(str));
+        }
+        if (length >= 0x40) {
+            uint256 offset;
+            uint256 strLength;
+            assembly ("memory-safe") {
+                offset := mload(add(returnData, 0x20))
+                strLength := mload(add(returnData, 0x40))
+            }
+            if (offset == 0x20 && strLength <= length - 0x40) {
+                return (true, abi.decode(returnData, (string)));
+            }
+        }
+        return (false, value);
+    }
+
+    /// @dev Get the metadata of a token, falling back to defaults for fields the token does not return
+    /// @param token ERC20 token
+    /// @param metadata Token metadata pointer to be updated in place
+    function tokenMetadataInPlace(address token, TokenMetadata memory metadata) internal view {
+        metadata.token = token;
+        (metadata.hasName, metadata.name) = tryString(token, IERC20Metadata.name.selector);
+        (metadata.hasSymbol, metadata.symbol) = tryString(token, IERC20Metadata.symbol.selector);
+        (bool success, uint256 value) = tryUint(token, IERC20Metadata.decimals.selector);
+        metadata.hasDecimals = success && value <= type(uint8).max;
+        metadata.decimals = metadata.hasDecimals ? uint8(value) : 18;
+        (metadata.hasTotalSupply, metadata.totalSupply) = tryUint(token, IERC20.totalSupply.selector);
+    }
+}
@@ -0,0 +1,306 @@
+//! ## Analytics
+//!
+//! The analytics module turns the populated ticks returned by
+//! [`get_populated_ticks_in_range`](crate::pool_lens::get_populated_ticks_in_range) into the
+//! liquidity distribution of a pool, i.e. the active liquidity and the token amounts between each
+//! pair of adjacent populated ticks, and the market depth around the current price.
+
+use crate::{
+    bindings::ephemeralgetpopulatedticksinrange::PoolUtils::PopulatedTick,
+    error::Error,
+    math::{
+        add_delta, get_amount0_delta, get_amount1_delta, get_amounts_for_liquidity,
+        get_sqrt_ratio_at_tick, mul_div, Q96,
+    },
+};
+use alloc::vec::Vec;
+use alloy::primitives::{
+    aliases::{I24, U160},
+    U256,
+};
+
+/// The liquidity in a tick range between two adjacent populated ticks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct LiquidityBucket {
+    /// The lower tick of the range
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_lower: I24,
+    /// The upper tick of the range
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_upper: I24,
+    /// The active liquidity when the current tick is in the range
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The amount of token0 held in the range at the current price
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub amount0: U256,
+    /// The amount of token1 held in the range at the current price
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub amount1: U256,
+}
+
+/// Subtract a signed liquidity delta from liquidity.
+fn sub_delta(x: u128, y: i128) -> Result<u128, Error> {
+    if y < 0 {
+        x.checked_add(y.unsigned_abs())
+    } else {
+        x.checked_sub(y as u128)
+    }
+    .ok_or(Error::Overflow)
+}
+
+/// Get the active liquidity curve from the populated ticks.
+///
+/// ## Arguments
+///
+/// * `ticks`: The populated ticks sorted in ascending order
+/// * `tick_current`: The current tick of the pool
+/// * `liquidity`: The current in-range liquidity of the pool
+///
+/// ## Returns
+///
+/// A vector of `(tick, liquidity)` where `liquidity` is the active liquidity when the current tick
+/// is between `tick` and the next populated tick
+#[inline]
+pub fn active_liquidity(
+    ticks: &[PopulatedTick],
+    tick_current: I24,
+    liquidity: u128,
+) -> Result<Vec<(I24, u128)>, Error> {
+    // the number of ticks at or below the current tick
+    let k = ticks.partition_point(|PopulatedTick { tick, .. }| *tick <= tick_current);
+    let mut curve: Vec<(I24, u128)> = ticks.iter().map(|t| (t.tick, 0)).collect();
+    // crossing a tick from left to right adds its liquidityNet
+    let mut active = liquidity;
+    for i in k..ticks.len() {
+        active = add_delta(active, ticks[i].liquidityNet)?;
+        curve[i].1 = active;
+    }
+    // crossing a tick from right to left subtracts its liquidityNet
+    if k != 0 {
+        active = liquidity;
+        curve[k - 1].1 = active;
+        for i in (0..k - 1).rev() {
+            active = sub_delta(active, ticks[i + 1].liquidityNet)?;
+            curve[i].1 = active;
+        }
+    }
+    Ok(curve)
+}
+
+/// Get the liquidity distribution of a pool from the populated ticks.
+///
+/// ## Arguments
+///
+/// * `ticks`: The populated ticks sorted in ascending order
+/// * `tick_current`: The current tick of the pool
+/// * `sqrt_price_x96`: The current sqrt price of the pool
+/// * `liquidity`: The current in-range liquidity of the pool
+///
+/// ## Returns
+///
+/// A vector of liquidity buckets between each pair of adjacent populated ticks
+#[inline]
+pub fn liquidity_distribution(
+    ticks: &[PopulatedTick],
+    tick_current: I24,
+    sqrt_price_x96: U160,
+    liquidity: u128,
+) -> Result<Vec<LiquidityBucket>, Error> {
+    let curve = active_liquidity(ticks, tick_current, liquidity)?;
+    curve
+        .windows(2)
+        .map(|window| {
+            let [(tick_lower, liquidity), (tick_upper, _)] = *window else {
+                unreachable!()
+            };
+            let (amount0, amount1) = get_amounts_for_liquidity(
+                sqrt_price_x96,
+                get_sqrt_ratio_at_tick(tick_lower)?,
+                get_sqrt_ratio_at_tick(tick_upper)?,
+                liquidity,
+            )?;
+            Ok(LiquidityBucket {
+                tick_lower,
+                tick_upper,
+                liquidity,
+                amount0,
+                amount1,
+            })
+        })
+        .collect()
+}
+
+/// Returns floor(sqrt(x)).
+fn sqrt(x: U256) -> U256 {
+    if x.is_zero() {
+        return x;
+    }
+    let mut y = x;
+    let mut z = (x >> 1) + U256::ONE;
+    while z < y {
+        y = z;
+        z = (x / z + z) >> 1;
+    }
+    y
+}
+
+/// Get the market depth within a percentage of the current price.
+///
+/// ## Arguments
+///
+/// * `buckets`: The liquidity distribution returned by [`liquidity_distribution`]
+/// * `sqrt_price_x96`: The current sqrt price of the pool
+/// * `bps`: The price deviation from the current price in basis points
+///
+/// ## Returns
+///
+/// The amount of token0 available between the current price and `bps` above it, and the amount of
+/// token1 available between `bps` below the current price and the current price
+#[inline]
+pub fn depth(
+    buckets: &[LiquidityBucket],
+    sqrt_price_x96: U160,
+    bps: u32,
+) -> Result<(U256, U256), Error> {
+    const BPS: u64 = 10_000;
+    let sqrt_price = U256::from(sqrt_price_x96);
+    // sqrt(1 ± bps / 10000) as a Q96 number
+    let sqrt_ratio_x96 = |numerator: u64| sqrt((U256::from(numerator) << 192) / U256::from(BPS));
+    let sqrt_price_upper = mul_div(sqrt_price, sqrt_ratio_x96(BPS + bps as u64), Q96)?;
+    let sqrt_price_lower = mul_div(
+        sqrt_price,
+        sqrt_ratio_x96(BPS.saturating_sub(bps as u64)),
+        Q96,
+    )?;
+
+    let mut amount0 = U256::ZERO;
+    let mut amount1 = U256::ZERO;
+    for bucket in buckets {
+        let sqrt_ratio_lower = U256::from(get_sqrt_ratio_at_tick(bucket.tick_lower)?);
+        let sqrt_ratio_upper = U256::from(get_sqrt_ratio_at_tick(bucket.tick_upper)?);
+        // token0 is sold as the price moves up
+        let lower = sqrt_ratio_lower.max(sqrt_price);
+        let upper = sqrt_ratio_upper.min(sqrt_price_upper);
+        if lower < upper {
+            amount0 += get_amount0_delta(lower.to(), upper.to(), bucket.liquidity, false)?;
+        }
+        // token1 is sold as the price moves down
+        let lower = sqrt_ratio_lower.max(sqrt_price_lower);
+        let upper = sqrt_ratio_upper.min(sqrt_price);
+        if lower < upper {
+            amount1 += get_amount1_delta(lower.to(), upper.to(), bucket.liquidity, false)?;
+        }
+    }
+    Ok((amount0, amount1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(tick: i32) -> I24 {
+        I24::try_from(tick).unwrap()
+    }
+
+    fn populated_tick(t: i32, liquidity_net: i128) -> PopulatedTick {
+        PopulatedTick {
+            tick: tick(t),
+            liquidityNet: liquidity_net,
+            liquidityGross: liquidity_net.unsigned_abs(),
+            feeGrowthOutside0X128: U256::ZERO,
+            feeGrowthOutside1X128: U256::ZERO,
+        }
+    }
+
+    /// Two positions [-600, 600) with 1000 liquidity and [0, 1200) with 500 liquidity.
+    fn mock_ticks() -> Vec<PopulatedTick> {
+        vec![
+            populated_tick(-600, 1000),
+            populated_tick(0, 500),
+            populated_tick(600, -1000),
+            populated_tick(1200, -500),
+        ]
+    }
+
+    #[test]
+    fn test_active_liquidity() {
+        let ticks = mock_ticks();
+        let expected = vec![
+            (tick(-600), 1000),
+            (tick(0), 1500),
+            (tick(600), 500),
+            (tick(1200), 0),
+        ];
+        // current tick in each of the ranges
+        assert_eq!(active_liquidity(&ticks, tick(-601), 0).unwrap(), expected);
+        assert_eq!(active_liquidity(&ticks, tick(-1), 1000).unwrap(), expected);
+        assert_eq!(active_liquidity(&ticks, tick(0), 1500).unwrap(), expected);
+        assert_eq!(active_liquidity(&ticks, tick(700), 500).unwrap(), expected);
+        assert_eq!(active_liquidity(&ticks, tick(1200), 0).unwrap(), expected);
+        // the liquidity is inconsistent with the ticks
+        assert!(matches!(
+            active_liquidity(&ticks, tick(700), 0),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_liquidity_distribution() {
+        let ticks = mock_ticks();
+        let tick_current = tick(300);
+        let sqrt_price_x96 = get_sqrt_ratio_at_tick(tick_current).unwrap();
+        let buckets = liquidity_distribution(&ticks, tick_current, sqrt_price_x96, 1500).unwrap();
+        assert_eq!(buckets.len(), 3);
+        // below the current price, only token1
+        assert!(buckets[0].amount0.is_zero() && !buckets[0].amount1.is_zero());
+        // in range, both tokens
+        assert!(!buckets[1].amount0.is_zero() && !buckets[1].amount1.is_zero());
+        // above the current price, only token0
+        assert!(!buckets[2].amount0.is_zero() && buckets[2].amount1.is_zero());
+        // the sum of the buckets equals the sum of the positions up to rounding
+        let sum0: U256 = buckets.iter().map(|b| b.amount0).sum();
+        let sum1: U256 = buckets.iter().map(|b| b.amount1).sum();
+        let (a0, a1) = get_amounts_for_liquidity(
+            sqrt_price_x96,
+            get_sqrt_ratio_at_tick(tick(-600)).unwrap(),
+            get_sqrt_ratio_at_tick(tick(600)).unwrap(),
+            1000,
+        )
+        .unwrap();
+        let (b0, b1) = get_amounts_for_liquidity(
+            sqrt_price_x96,
+            get_sqrt_ratio_at_tick(tick(0)).unwrap(),
+            get_sqrt_ratio_at_tick(tick(1200)).unwrap(),
+            500,
+        )
+        .unwrap();
+        assert!((a0 + b0).abs_diff(sum0) <= U256::from(2));
+        assert!((a1 + b1).abs_diff(sum1) <= U256::from(2));
+    }
+
+    #[test]
+    fn test_depth() {
+        let ticks = mock_ticks();
+        let tick_current = tick(300);
+        let sqrt_price_x96 = get_sqrt_ratio_at_tick(tick_current).unwrap();
+        let buckets = liquidity_distribution(&ticks, tick_current, sqrt_price_x96, 1500).unwrap();
+        assert_eq!(
+            depth(&buckets, sqrt_price_x96, 0).unwrap(),
+            (U256::ZERO, U256::ZERO)
+        );
+        // depth grows with the price deviation
+        let (small0, small1) = depth(&buckets, sqrt_price_x96, 100).unwrap();
+        let (large0, large1) = depth(&buckets, sqrt_price_x96, 1000).unwrap();
+        assert!(!small0.is_zero() && !small1.is_zero());
+        assert!(large0 > small0 && large1 > small1);
+        // the whole distribution is within 100% of the current price
+        let (all0, all1) = depth(&buckets, sqrt_price_x96, 10_000).unwrap();
+        let sum0: U256 = buckets.iter().map(|b| b.amount0).sum();
+        let sum1: U256 = buckets.iter().map(|b| b.amount1).sum();
+        assert!(all0.abs_diff(sum0) <= U256::from(2));
+        assert_eq!(all1, sum1);
+    }
+}
@@ -0,0 +1,390 @@
+//! ## uniswap-lens
+//!
+//! A command-line interface exposing each lens as a subcommand, e.g.
+//!
+//! ```text
+//! uniswap-lens --rpc-url $ETH_RPC_URL --block 17000000 position 4
+//! uniswap-lens --rpc-url $ETH_RPC_URL --format csv ticks 0x88e6...5640 -887220 887220
+//! ```
+
+use alloy::{
+    eips::BlockId,
+    network::Ethereum,
+    primitives::{address, aliases::I24, Address, B256, U256},
+    providers::{Provider, ProviderBuilder},
+    transports::http::reqwest::Url,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Map, Value};
+use std::{
+    error::Error,
+    io::{self, Write},
+    process::ExitCode,
+};
+use uniswap_lens::prelude::*;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Query Uniswap V3 using ephemeral lens contracts")]
+struct Cli {
+    /// The JSON-RPC endpoint of the node
+    #[arg(long, env = "ETH_RPC_URL")]
+    rpc_url: Url,
+    /// The block number, tag or hash to query
+    #[arg(long, default_value = "latest", global = true)]
+    block: BlockId,
+    /// The unix timestamp to query at, resolved to the last block at or before it
+    #[arg(long, conflicts_with = "block", global = true)]
+    timestamp: Option<u64>,
+    /// The gas limit of the call, the node's default cap if unset
+    #[arg(long, global = true)]
+    gas: Option<u64>,
+    /// The sender of the call
+    #[arg(long, global = true)]
+    from: Option<Address>,
+    /// The chain of the deployment, detected from the RPC endpoint by default
+    #[arg(long, value_enum, global = true)]
+    chain: Option<Chain>,
+    /// The output format
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Get the details of a position given the token ID
+    Position { token_id: U256 },
+    /// Get all positions owned by an address
+    Owner { owner: Address },
+    /// Get the populated ticks of a pool within a range
+    #[command(allow_negative_numbers = true)]
+    Ticks {
+        pool: Address,
+        tick_lower: I24,
+        tick_upper: I24,
+    },
+    /// Get the static storage slots of a pool
+    Slots { pool: Address },
+    /// Get the storage of a contract at the given slots
+    Storage {
+        address: Address,
+        #[arg(required = true)]
+        slots: Vec<U256>,
+    },
+}
+
+/// The chains with a canonical Uniswap V3 deployment.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Chain {
+    Mainnet,
+    Optimism,
+    Arbitrum,
+    Polygon,
+    Base,
+}
+
+impl Chain {
+    const fn from_id(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            1 => Some(Self::Mainnet),
+            10 => Some(Self::Optimism),
+            42161 => Some(Self::Arbitrum),
+            137 => Some(Self::Polygon),
+            8453 => Some(Self::Base),
+            _ => None,
+        }
+    }
+
+    /// The address of the non-fungible position manager.
+    const fn npm(self) -> Address {
+        match self {
+            Self::Base => address!("03a520b32C04BF3bEEf7BEb72E919cf822Ed34f1"),
+            _ => address!("C36442b4a4522E871399CD717aBDD847Ab11FE88"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Json,
+    Table,
+    Csv,
+}
+
+impl Format {
+    fn write(self, value: Value, out: &mut impl Write) -> io::Result<()> {
+        if let Self::Json = self {
+            serde_json::to_writer_pretty(&mut *out, &value)?;
+            return writeln!(out);
+        }
+        let rows = to_rows(value);
+        let mut columns: Vec<&String> = Vec::new();
+        for key in rows.iter().flat_map(Map::keys) {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| columns.iter().map(|&key| cell(row.get(key))).collect())
+            .collect();
+        match self {
+            Self::Csv => {
+                let line = |fields: Vec<String>| {
+                    fields
+                        .iter()
+                        .map(|field| escape_csv(field))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                };
+                writeln!(
+                    out,
+                    "{}",
+                    line(columns.iter().map(|&c| c.clone()).collect())
+                )?;
+                for row in cells {
+                    writeln!(out, "{}", line(row))?;
+                }
+            }
+            _ => {
+                let widths: Vec<usize> = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| {
+                        cells
+                            .iter()
+                            .map(|row| row[i].len())
+                            .fold(column.len(), usize::max)
+                    })
+                    .collect();
+                let line = |fields: &[String]| {
+                    fields
+                        .iter()
+                        .zip(&widths)
+                        .map(|(field, &width)| format!("{field:<width$}"))
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                        .trim_end()
+                        .to_owned()
+                };
+                let header: Vec<String> = columns.iter().map(|&c| c.clone()).collect();
+                let separator: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+                writeln!(out, "{}", line(&header))?;
+                writeln!(out, "{}", line(&separator))?;
+                for row in cells {
+                    writeln!(out, "{}", line(&row))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split a result into rows of columns, joining the keys of nested objects with dots.
+fn to_rows(value: Value) -> Vec<Map<String, Value>> {
+    let flatten = |value| {
+        let mut row = Map::new();
+        flatten_into(&mut row, String::new(), value);
+        row
+    };
+    match value {
+        Value::Array(items) => items.into_iter().map(flatten).collect(),
+        value => vec![flatten(value)],
+    }
+}
+
+fn flatten_into(row: &mut Map<String, Value>, prefix: String, value: Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_into(row, key, value);
+            }
+        }
+        value if prefix.is_empty() => {
+            row.insert("value".to_owned(), value);
+        }
+        value => {
+            row.insert(prefix, value);
+        }
+    }
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Get the address of the non-fungible position manager on the chain of the provider.
+async fn get_npm(
+    chain: Option<Chain>,
+    provider: &impl Provider,
+) -> Result<Address, Box<dyn Error>> {
+    let chain = match chain {
+        Some(chain) => chain,
+        None => {
+            let chain_id = provider.get_chain_id().await?;
+            Chain::from_id(chain_id)
+                .ok_or_else(|| format!("unsupported chain id {chain_id}, specify --chain"))?
+        }
+    };
+    Ok(chain.npm())
+}
+
+async fn run(cli: Cli) -> Result<Value, Box<dyn Error>> {
+    let provider = ProviderBuilder::new()
+        .disable_recommended_fillers()
+        .connect_http(cli.rpc_url);
+    let options = CallOptions {
+        block_id: Some(cli.block),
+        timestamp: cli.timestamp,
+        gas: cli.gas,
+        from: cli.from,
+        ..Default::default()
+    };
+    let value = match cli.command {
+        Command::Position { token_id } => {
+            let npm = get_npm(cli.chain, &provider).await?;
+            serde_json::to_value(get_position_details(npm, token_id, &provider, options).await?)?
+        }
+        Command::Owner { owner } => {
+            let npm = get_npm(cli.chain, &provider).await?;
+            serde_json::to_value(get_all_positions_by_owner(npm, owner, &provider, options).await?)?
+        }
+        Command::Ticks {
+            pool,
+            tick_lower,
+            tick_upper,
+        } => {
+            let (ticks, _) =
+                get_populated_ticks_in_range(pool, tick_lower, tick_upper, &provider, options)
+                    .await?;
+            serde_json::to_value(ticks)?
+        }
+        Command::Slots { pool } => {
+            serde_json::to_value(get_static_slots(pool, &provider, options).await?)?
+        }
+        Command::Storage { address, slots } => {
+            let slots: Vec<B256> = slots.into_iter().map(B256::from).collect();
+            let values =
+                get_storage_at::<Ethereum, _>(address, slots.clone(), &provider, options).await?;
+            slots
+                .into_iter()
+                .zip(values)
+                .map(|(slot, value)| json!({ "slot": slot, "value": value }))
+                .collect()
+        }
+    };
+    Ok(value)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = cli.format;
+    let result = match run(cli).await {
+        Ok(value) => format
+            .write(value, &mut io::stdout().lock())
+            .map_err(Into::into),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: Format, value: Value) -> String {
+        let mut out = Vec::new();
+        format.write(value, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_cli() {
+        let cli = Cli::try_parse_from([
+            "uniswap-lens",
+            "--rpc-url",
+            "http://localhost:8545",
+            "ticks",
+            "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640",
+            "-887220",
+            "887220",
+            "--block",
+            "17000000",
+            "--gas",
+            "30000000",
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+        assert_eq!(cli.block, BlockId::number(17000000));
+        assert_eq!(cli.timestamp, None);
+        assert_eq!(cli.gas, Some(30000000));
+        assert_eq!(cli.from, None);
+        assert!(matches!(cli.format, Format::Csv));
+        assert!(matches!(
+            cli.command,
+            Command::Ticks { tick_lower, .. } if tick_lower.as_i32() == -887220
+        ));
+        let args = [
+            "uniswap-lens",
+            "--rpc-url",
+            "http://localhost:8545",
+            "slots",
+            "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640",
+            "--timestamp",
+            "1681000000",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert_eq!(cli.timestamp, Some(1681000000));
+        assert!(Cli::try_parse_from(args.into_iter().chain(["--block", "17000000"])).is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let value = json!([
+            { "tick": -60, "liquidity": { "gross": "10", "net": "-10" } },
+            { "tick": 60, "liquidity": { "gross": "10", "net": "10" }, "note": "a, \"b\"" },
+        ]);
+        assert_eq!(
+            render(Format::Csv, value.clone()),
+            "tick,liquidity.gross,liquidity.net,note\n\
+             -60,10,-10,\n\
+             60,10,10,\"a, \"\"b\"\"\"\n"
+        );
+        assert_eq!(
+            render(Format::Table, value.clone()),
+            "tick  liquidity.gross  liquidity.net  note\n\
+             ----  ---------------  -------------  ------\n\
+             -60   10               -10\n\
+             60    10               10             a, \"b\"\n"
+        );
+        let json = render(Format::Json, value.clone());
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+        assert_eq!(render(Format::Csv, json!("0x01")), "value\n0x01\n");
+    }
+}
@@ -24,24 +24,40 @@ macro_rules! create_sol_binding {
 
 // Use the macro to create all the bindings
 create_sol_binding!(ephemeralallpositionsbyowner, EphemeralAllPositionsByOwner);
+create_sol_binding!(ephemeralgetallowances, EphemeralGetAllowances);
+create_sol_binding!(ephemeralgetbalances, EphemeralGetBalances);
+create_sol_binding!(ephemeralgetfeesowed, EphemeralGetFeesOwed);
 create_sol_binding!(
     ephemeralgetpopulatedticksinrange,
     EphemeralGetPopulatedTicksInRange
 );
 create_sol_binding!(ephemeralgetposition, EphemeralGetPosition);
 create_sol_binding!(ephemeralgetpositions, EphemeralGetPositions);
+create_sol_binding!(ephemeralgettokenmetadata, EphemeralGetTokenMetadata);
+create_sol_binding!(ephemeralgettotalamounts, EphemeralGetTotalAmounts);
 create_sol_binding!(ephemeralpoolpositions, EphemeralPoolPositions);
 create_sol_binding!(ephemeralpoolslots, EphemeralPoolSlots);
 create_sol_binding!(ephemeralpooltickbitmap, EphemeralPoolTickBitmap);
 create_sol_binding!(ephemeralpoolticks, EphemeralPoolTicks);
 create_sol_binding!(ephemeralstoragelens, EphemeralStorageLens);
 
+create_sol_binding!(iuniswapv3factory, IUniswapV3Factory);
 create_sol_binding!(iuniswapv3pool, IUniswapV3Pool);
 create_sol_binding!(
     iuniswapv3nonfungiblepositionmanager,
     IUniswapV3NonfungiblePositionManager
 );
 
+/// The `multicall` inherited by `NonfungiblePositionManager`, which is not part of its interface.
+pub mod imulticall {
+    alloy::sol!(
+        #[sol(rpc)]
+        interface IMulticall {
+            function multicall(bytes[] calldata data) external payable returns (bytes[] memory results);
+        }
+    );
+}
+
 create_sol_binding!(ierc20, IERC20);
 create_sol_binding!(ierc20metadata, IERC20Metadata);
 create_sol_binding!(ierc721enumerable, IERC721Enumerable);
@@ -1,20 +1,438 @@
-/// Call an ephemeral contract and return the decoded data
+//! ## Caller
+//!
+//! The caller module calls ephemeral lens contracts, which run their query in the constructor and
+//! revert with the ABI encoded result instead of deploying.
+
+use crate::{
+    error::Error,
+    history::{BlockOrTimestamp, BlockResolver},
+};
+use alloy::{
+    contract::{CallBuilder, CallDecoder, Error as ContractError, RawCallBuilder},
+    eips::BlockId,
+    network::Network,
+    primitives::{Address, U256},
+    providers::Provider,
+    rpc::types::state::StateOverride,
+    sol_types::SolCall,
+    transports::TransportError,
+};
+
+/// Options forwarded to the `eth_call` of a lens. Every lens function accepts `CallOptions`, or
+/// just an `Option<BlockId>`, a `BlockId` or a [`BlockOrTimestamp`] which convert into it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallOptions {
+    /// The block to query, the latest block if `None`
+    pub block_id: Option<BlockId>,
+    /// The unix timestamp to query at, resolved to the last block at or before it in place of
+    /// `block_id`, by the lens or upfront with [`resolve_with`](Self::resolve_with)
+    pub timestamp: Option<u64>,
+    /// The gas limit of the call, the node's default cap if `None`
+    pub gas: Option<u64>,
+    /// The sender of the call
+    pub from: Option<Address>,
+    /// The value sent with the call
+    pub value: Option<U256>,
+    /// Additional state overrides applied to the call
+    pub state: Option<StateOverride>,
+}
+
+impl CallOptions {
+    /// Set the block to query.
+    #[inline]
+    #[must_use]
+    pub const fn block(mut self, block_id: BlockId) -> Self {
+        self.block_id = Some(block_id);
+        self.timestamp = None;
+        self
+    }
+
+    /// Set the block or the point in time to query at.
+    #[inline]
+    #[must_use]
+    pub const fn at(mut self, at: BlockOrTimestamp) -> Self {
+        match at {
+            BlockOrTimestamp::Block(block_id) => self.block(block_id),
+            BlockOrTimestamp::Timestamp(timestamp) => {
+                self.timestamp = Some(timestamp);
+                self
+            }
+        }
+    }
+
+    /// Set the gas limit of the call.
+    #[inline]
+    #[must_use]
+    pub const fn gas(mut self, gas: u64) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+
+    /// Set the sender of the call.
+    #[inline]
+    #[must_use]
+    pub const fn sender(mut self, from: Address) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Set the value sent with the call.
+    #[inline]
+    #[must_use]
+    pub const fn value(mut self, value: U256) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Set the state overrides applied to the call.
+    #[inline]
+    #[must_use]
+    pub fn state(mut self, state: StateOverride) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Resolve the timestamp to query at, if any, to a block with a new [`BlockResolver`]. Use
+    /// [`resolve_with`](Self::resolve_with) to reuse the anchors of previous lookups.
+    ///
+    /// ## Arguments
+    ///
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The options with the resolved block and without a timestamp
+    #[inline]
+    pub async fn resolve<N, P>(self, provider: P) -> Result<Self, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        self.resolve_with(&mut BlockResolver::new(), provider).await
+    }
+
+    /// Resolve the timestamp to query at, if any, to a block with a shared [`BlockResolver`]. The
+    /// lenses accept the resolved options as is, so resolving the options of a series of calls
+    /// with the same resolver narrows each lookup with the blocks fetched by the previous ones.
+    ///
+    /// ## Arguments
+    ///
+    /// * `resolver`: The resolver whose anchors are used and extended by the lookup
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The options with the resolved block and without a timestamp
+    #[inline]
+    pub async fn resolve_with<N, P>(
+        mut self,
+        resolver: &mut BlockResolver,
+        provider: P,
+    ) -> Result<Self, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        if let Some(timestamp) = self.timestamp.take() {
+            let block_number = resolver.block_at_timestamp(timestamp, provider).await?;
+            self.block_id = Some(BlockId::number(block_number));
+        }
+        Ok(self)
+    }
+
+    /// Apply the options to a call builder. A timestamp must be [`resolve`](Self::resolve)d first.
+    ///
+    /// ## Arguments
+    ///
+    /// * `call_builder`: The call builder of a lens
+    ///
+    /// ## Returns
+    ///
+    /// The call builder with the options set
+    #[inline]
+    pub fn apply<P, D, N>(self, mut call_builder: CallBuilder<P, D, N>) -> CallBuilder<P, D, N>
+    where
+        N: Network,
+        P: Provider<N>,
+        D: CallDecoder,
+    {
+        if let Some(block_id) = self.block_id {
+            call_builder = call_builder.block(block_id);
+        }
+        if let Some(gas) = self.gas {
+            call_builder = call_builder.gas(gas);
+        }
+        if let Some(from) = self.from {
+            call_builder = call_builder.from(from);
+        }
+        if let Some(value) = self.value {
+            call_builder = call_builder.value(value);
+        }
+        if let Some(state) = self.state {
+            call_builder = call_builder.state(state);
+        }
+        call_builder
+    }
+}
+
+impl From<Option<BlockId>> for CallOptions {
+    #[inline]
+    fn from(block_id: Option<BlockId>) -> Self {
+        Self {
+            block_id,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<BlockId> for CallOptions {
+    #[inline]
+    fn from(block_id: BlockId) -> Self {
+        Some(block_id).into()
+    }
+}
+
+impl From<BlockOrTimestamp> for CallOptions {
+    #[inline]
+    fn from(at: BlockOrTimestamp) -> Self {
+        Self::default().at(at)
+    }
+}
+
+/// Call an ephemeral contract and decode the revert data as the return of `C`.
+///
+/// ## Arguments
+///
+/// * `deploy_builder`: The deployment of the ephemeral contract, e.g. from the `deploy_builder`
+///   generated by `sol!`
+/// * `options`: Optional block id, timestamp or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The decoded return of `C`, or [`Error::UnexpectedSuccess`] if the deployment does not revert
+///
+/// ## Example
+///
+/// A lens whose constructor reverts with `abi.encode(block.number)`:
+///
+/// ```no_run
+/// use alloy::{providers::ProviderBuilder, sol};
+/// use uniswap_lens::caller::call_ephemeral;
+///
+/// sol! {
+///     #[sol(rpc, bytecode = "4360005260206000fd")]
+///     contract EphemeralBlockNumber {
+///         function query() external returns (uint256 blockNumber);
+///     }
+/// }
+///
+/// # async fn run() -> Result<(), uniswap_lens::error::Error> {
+/// let provider = ProviderBuilder::new().connect_http("http://localhost:8545".parse().unwrap());
+/// let deploy_builder = EphemeralBlockNumber::deploy_builder(provider);
+/// let block_number =
+///     call_ephemeral::<EphemeralBlockNumber::queryCall, _, _>(deploy_builder, None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub async fn call_ephemeral<C, N, P>(
+    deploy_builder: RawCallBuilder<P, N>,
+    options: impl Into<CallOptions>,
+) -> Result<C::Return, Error>
+where
+    C: SolCall,
+    N: Network,
+    P: Provider<N>,
+{
+    let options = options.into().resolve(&deploy_builder.provider).await?;
+    match options.apply(deploy_builder).call_raw().await {
+        Err(ContractError::TransportError(TransportError::ErrorResp(payload))) => {
+            match payload.as_revert_data() {
+                Some(data) => Ok(C::abi_decode_returns(data.as_ref())?),
+                None => Err(Error::InvalidRevertData(payload)),
+            }
+        }
+        Err(err) => Err(Error::ContractError(err)),
+        Ok(_) => Err(Error::UnexpectedSuccess),
+    }
+}
+
+/// Call an ephemeral contract and return the decoded data. See [`call_ephemeral`].
 #[macro_export]
 macro_rules! call_ephemeral_contract {
-    ($deploy_builder:expr, $call_type:ty, $block_id:expr) => {{
-        let deploy_builder = match $block_id {
-            Some(block_id) => $deploy_builder.block(block_id),
-            None => $deploy_builder,
+    ($deploy_builder:expr, $call_type:ty, $options:expr) => {
+        $crate::caller::call_ephemeral::<$call_type, _, _>($deploy_builder, $options).await
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::{Bytes, U256},
+        providers::ProviderBuilder,
+        rpc::{
+            json_rpc::ErrorPayload,
+            types::{Block, Header},
+        },
+        sol,
+        sol_types::SolValue,
+        transports::mock::Asserter,
+    };
+
+    sol! {
+        #[sol(rpc, bytecode = "4360005260206000fd")]
+        contract EphemeralBlockNumber {
+            function query() external returns (uint256 blockNumber);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_ephemeral() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let revert_data = Bytes::from(U256::from(17000000).abi_encode());
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::value::to_raw_value(&revert_data).unwrap()),
+        });
+        asserter.push_success(&Bytes::new());
+        let block_number = call_ephemeral::<EphemeralBlockNumber::queryCall, _, _>(
+            EphemeralBlockNumber::deploy_builder(&provider),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(block_number, U256::from(17000000));
+        let res = call_ephemeral::<EphemeralBlockNumber::queryCall, _, _>(
+            EphemeralBlockNumber::deploy_builder(&provider),
+            None,
+        )
+        .await;
+        assert!(matches!(res, Err(Error::UnexpectedSuccess)));
+    }
+
+    #[test]
+    fn test_call_options() {
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(Asserter::new());
+        let from = Address::repeat_byte(1);
+        let options = CallOptions::from(BlockId::number(17000000))
+            .gas(30_000_000)
+            .sender(from)
+            .value(U256::from(1));
+        assert_eq!(options.block_id, Some(BlockId::number(17000000)));
+        let call_builder = options.apply(EphemeralBlockNumber::deploy_builder(&provider));
+        let tx = call_builder.as_ref();
+        assert_eq!(tx.gas, Some(30_000_000));
+        assert_eq!(tx.from, Some(from));
+        assert_eq!(tx.value, Some(U256::from(1)));
+        assert_eq!(CallOptions::from(None), CallOptions::default());
+        let options = CallOptions::default().at(BlockOrTimestamp::Timestamp(1_000));
+        assert_eq!(options.timestamp, Some(1_000));
+        let options = options.at(BlockId::latest().into());
+        assert_eq!(options.block_id, Some(BlockId::latest()));
+        assert_eq!(options.timestamp, None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_timestamp() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let block = |number: u64| Block::<()> {
+            header: Header::new(alloy::consensus::Header {
+                number,
+                timestamp: 1_000 + number * 12,
+                ..Default::default()
+            }),
+            ..Default::default()
         };
-        match deploy_builder.call_raw().await {
-            Err(ContractError::TransportError(TransportError::ErrorResp(payload))) => {
-                match payload.as_revert_data() {
-                    Some(data) => Ok(<$call_type as SolCall>::abi_decode_returns(data.as_ref())?),
-                    None => Err(Error::InvalidRevertData(payload)),
-                }
-            }
-            Err(err) => Err(Error::ContractError(err)),
-            Ok(_) => panic!("deployment should revert"),
+        // the genesis block, the latest block, then the binary search
+        for number in [0, 10, 5, 7, 6] {
+            asserter.push_success(&block(number));
+        }
+        let options = CallOptions::from(BlockOrTimestamp::Timestamp(1_000 + 5 * 12 + 11));
+        let resolved = options.clone().resolve(&provider).await.unwrap();
+        assert_eq!(resolved.block_id, Some(BlockId::number(5)));
+        assert_eq!(resolved.timestamp, None);
+        // the lens functions resolve the timestamp before the call
+        for number in [0, 10, 5, 7, 6] {
+            asserter.push_success(&block(number));
+        }
+        let revert_data = Bytes::from(U256::from(5).abi_encode());
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::value::to_raw_value(&revert_data).unwrap()),
+        });
+        let block_number = call_ephemeral::<EphemeralBlockNumber::queryCall, _, _>(
+            EphemeralBlockNumber::deploy_builder(&provider),
+            options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(block_number, U256::from(5));
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_with_shared_resolver() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let block = |number: u64| Block::<()> {
+            header: Header::new(alloy::consensus::Header {
+                number,
+                timestamp: 1_000 + number * 12,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut resolver = BlockResolver::new();
+        // a cold lookup fetches the genesis block, the latest block, then binary searches
+        let first = [0, 10, 5, 7, 6];
+        for number in first {
+            asserter.push_success(&block(number));
+        }
+        let options = CallOptions::from(BlockOrTimestamp::Timestamp(1_000 + 5 * 12 + 11))
+            .resolve_with(&mut resolver, &provider)
+            .await
+            .unwrap();
+        assert_eq!(options.block_id, Some(BlockId::number(5)));
+        assert!(asserter.read_q().is_empty());
+        // the next lookup only searches between the anchors of the first one
+        let second = [8, 9];
+        for number in second {
+            asserter.push_success(&block(number));
         }
-    }};
+        let options = CallOptions::from(BlockOrTimestamp::Timestamp(1_000 + 8 * 12 + 11))
+            .resolve_with(&mut resolver, &provider)
+            .await
+            .unwrap();
+        assert_eq!(options.block_id, Some(BlockId::number(8)));
+        assert!(asserter.read_q().is_empty());
+        assert!(second.len() < first.len());
+        // the resolved options are passed to a lens without another lookup
+        let revert_data = Bytes::from(U256::from(8).abi_encode());
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::value::to_raw_value(&revert_data).unwrap()),
+        });
+        let block_number = call_ephemeral::<EphemeralBlockNumber::queryCall, _, _>(
+            EphemeralBlockNumber::deploy_builder(&provider),
+            options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(block_number, U256::from(8));
+        assert!(asserter.read_q().is_empty());
+    }
 }
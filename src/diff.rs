@@ -0,0 +1,680 @@
+//! ## Diff
+//!
+//! The diff module compares the storage of a contract at two blocks and returns the slots that
+//! were added, removed or changed in between. The storage of a pool is read through the pool
+//! lenses and each slot is labeled with the variable of the `UniswapV3Pool` layout it belongs to
+//! and decoded at both blocks, while any other contract is read through [`get_storage_at`] for a
+//! caller supplied set of slots.
+
+use crate::{
+    bindings::{
+        ephemeralpoolpositions::PoolUtils::PositionKey, ephemeralpoolslots::PoolUtils::Slot,
+        iuniswapv3pool::IUniswapV3Pool,
+    },
+    caller::CallOptions,
+    error::Error,
+    math::{MAX_TICK, MIN_TICK},
+    oracle::Observation,
+    pool_lens::{
+        decode_positions_slots, get_position_key, get_position_slot, get_positions_slots,
+        get_static_slots, get_tick_bitmap_slots, get_ticks_slots, PositionInfo,
+    },
+    pool_state::{word_positions, Slot0, TickInfo},
+    storage_lens::get_storage_at,
+    storage_slot::{get_tick_bitmap_slot, get_tick_slot, OBSERVATIONS_SLOT},
+};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{
+        aliases::{I24, I56, U160, U56},
+        Address, B256, U256,
+    },
+    providers::Provider,
+};
+use futures::future::{try_join, try_join3};
+
+/// The variable of the `UniswapV3Pool` layout a storage slot belongs to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum SlotKind {
+    /// `slot0`
+    Slot0,
+    /// `feeGrowthGlobal0X128`
+    FeeGrowthGlobal0X128,
+    /// `feeGrowthGlobal1X128`
+    FeeGrowthGlobal1X128,
+    /// `protocolFees`
+    ProtocolFees,
+    /// `liquidity`
+    Liquidity,
+    /// A word of a tick in the `ticks` mapping, where `offset` 0 holds the liquidity, 1 and 2 the
+    /// fee growth outside and 3 the oracle accumulators
+    Tick {
+        /// The tick
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+        tick: I24,
+        /// The offset of the word in the `Tick.Info` struct
+        offset: u8,
+    },
+    /// A word in the `tickBitmap` mapping
+    TickBitmap(i16),
+    /// An element of the `observations` array
+    Observation(u16),
+    /// A word of a position in the `positions` mapping, where `offset` 0 holds the liquidity, 1
+    /// and 2 the fee growth inside and 3 the fees owed
+    Position {
+        /// The key of the position in the `positions` mapping
+        key: B256,
+        /// The offset of the word in the `Position.Info` struct
+        offset: u8,
+    },
+    /// The layout of the slot is unknown
+    #[default]
+    Unknown,
+}
+
+/// The oracle accumulators of a tick, packed in the last word of its `Tick.Info`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TickOracle {
+    /// The tick accumulator on the other side of the tick from the current tick
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tick_cumulative_outside: I56,
+    /// The seconds per unit of liquidity on the other side of the tick from the current tick
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub seconds_per_liquidity_outside_x128: U160,
+    /// The seconds spent on the other side of the tick from the current tick
+    pub seconds_outside: u32,
+    /// Whether the tick is initialized
+    pub initialized: bool,
+}
+
+impl TickOracle {
+    /// Decode the oracle accumulators of a tick from the last word of its `Tick.Info`.
+    #[inline]
+    pub fn from_word(word: U256) -> Self {
+        Self {
+            tick_cumulative_outside: I56::from_raw(word.wrapping_to::<U56>()),
+            seconds_per_liquidity_outside_x128: word.wrapping_shr(56).wrapping_to(),
+            seconds_outside: word.wrapping_shr(216).wrapping_to(),
+            initialized: word.bit(248),
+        }
+    }
+}
+
+/// The decoded value of a storage slot of a pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum SlotValue {
+    /// The fields packed in `slot0`
+    Slot0(Slot0),
+    /// `feeGrowthGlobal0X128` or `feeGrowthGlobal1X128`
+    FeeGrowthGlobalX128(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))] U256,
+    ),
+    /// The protocol fees owed in each token
+    ProtocolFees {
+        /// The protocol fees owed in token0
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+        token0: u128,
+        /// The protocol fees owed in token1
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+        token1: u128,
+    },
+    /// The in-range liquidity
+    Liquidity(#[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))] u128),
+    /// The liquidity and the fee growth outside of a tick, for the first 3 words of a tick
+    Tick(TickInfo),
+    /// The oracle accumulators of a tick, for the last word of a tick
+    TickOracle(TickOracle),
+    /// An oracle observation
+    Observation(Observation),
+    /// A position, for any word of a position
+    Position(PositionInfo),
+}
+
+/// A storage slot whose value differs between two blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SlotDiff {
+    /// The storage slot
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub slot: U256,
+    /// The variable the slot belongs to
+    pub kind: SlotKind,
+    /// The value of the slot at the first block
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub before: U256,
+    /// The value of the slot at the second block
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub after: U256,
+    /// The decoded value at the first block, `None` if the layout of the slot is unknown
+    pub decoded_before: Option<SlotValue>,
+    /// The decoded value at the second block, `None` if the layout of the slot is unknown
+    pub decoded_after: Option<SlotValue>,
+}
+
+/// The difference between the storage of a contract at two blocks, sorted by slot.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StorageDiff {
+    /// The slots that were empty at the first block
+    pub added: Vec<SlotDiff>,
+    /// The slots that are empty at the second block
+    pub removed: Vec<SlotDiff>,
+    /// The slots that are non-empty at both blocks but hold a different value
+    pub changed: Vec<SlotDiff>,
+}
+
+impl StorageDiff {
+    /// Returns `true` if no slot differs.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Returns the number of slots that differ.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    /// Iterate over the added, removed and changed slots.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &SlotDiff> {
+        self.added.iter().chain(&self.removed).chain(&self.changed)
+    }
+}
+
+/// Compare two sets of storage slots. A slot missing from one of the sets is treated as empty.
+///
+/// ## Arguments
+///
+/// * `before`: The slots at the first block
+/// * `after`: The slots at the second block
+///
+/// ## Returns
+///
+/// The slots that differ, labeled as [`SlotKind::Unknown`]
+#[inline]
+pub fn diff_slots(before: &[Slot], after: &[Slot]) -> StorageDiff {
+    let mut values: BTreeMap<U256, (U256, U256)> = BTreeMap::new();
+    for slot in before {
+        values.entry(slot.slot).or_default().0 = slot.data;
+    }
+    for slot in after {
+        values.entry(slot.slot).or_default().1 = slot.data;
+    }
+    let mut diff = StorageDiff::default();
+    for (slot, (before, after)) in values {
+        let slot_diff = SlotDiff {
+            slot,
+            kind: SlotKind::Unknown,
+            before,
+            after,
+            ..Default::default()
+        };
+        if before == after {
+            continue;
+        } else if before.is_zero() {
+            diff.added.push(slot_diff);
+        } else if after.is_zero() {
+            diff.removed.push(slot_diff);
+        } else {
+            diff.changed.push(slot_diff);
+        }
+    }
+    diff
+}
+
+/// Decode the value of a pool slot of a known variable, reading the other words of a struct from
+/// the same block. A missing word is treated as empty.
+fn decode_pool_slot(
+    slot: U256,
+    kind: SlotKind,
+    words: &BTreeMap<U256, U256>,
+    positions: &BTreeMap<B256, &PositionKey>,
+) -> Option<SlotValue> {
+    let word = |slot: U256| words.get(&slot).copied().unwrap_or_default();
+    let value = match kind {
+        SlotKind::Slot0 => SlotValue::Slot0(Slot0::from_word(word(slot))),
+        SlotKind::FeeGrowthGlobal0X128 | SlotKind::FeeGrowthGlobal1X128 => {
+            SlotValue::FeeGrowthGlobalX128(word(slot))
+        }
+        SlotKind::ProtocolFees => SlotValue::ProtocolFees {
+            token0: word(slot).wrapping_to(),
+            token1: word(slot).wrapping_shr(128).wrapping_to(),
+        },
+        SlotKind::Liquidity => SlotValue::Liquidity(word(slot).wrapping_to()),
+        SlotKind::Tick { offset: 3, .. } => {
+            SlotValue::TickOracle(TickOracle::from_word(word(slot)))
+        }
+        SlotKind::Tick { offset, .. } => {
+            let base = slot - U256::from(offset);
+            SlotValue::Tick(TickInfo::from_words(&[
+                word(base),
+                word(base + U256::from(1)),
+                word(base + U256::from(2)),
+            ]))
+        }
+        SlotKind::Observation(_) => SlotValue::Observation(Observation::from_word(word(slot))),
+        SlotKind::Position { key, offset } => {
+            let base = slot - U256::from(offset);
+            let slots: Vec<Slot> = (0..4)
+                .map(|i| Slot {
+                    slot: base + U256::from(i),
+                    data: word(base + U256::from(i)),
+                })
+                .collect();
+            let (_, info) = decode_positions_slots(vec![(*positions.get(&key)?).clone()], &slots)
+                .ok()?
+                .pop()?;
+            SlotValue::Position(info)
+        }
+        SlotKind::TickBitmap(_) | SlotKind::Unknown => return None,
+    };
+    Some(value)
+}
+
+/// Compare the storage of a pool at two sets of slots, label each slot with its variable and
+/// decode it at both blocks.
+fn diff_pool_slots(
+    before: &[Slot],
+    after: &[Slot],
+    tick_spacing: i32,
+    positions: &[PositionKey],
+) -> StorageDiff {
+    let mut diff = diff_slots(before, after);
+    let mut kinds = BTreeMap::new();
+    let (word_pos_lower, word_pos_upper) = word_positions(tick_spacing);
+    for word_pos in word_pos_lower..=word_pos_upper {
+        kinds.insert(
+            get_tick_bitmap_slot(word_pos as i16),
+            SlotKind::TickBitmap(word_pos as i16),
+        );
+    }
+    // the ticks initialized at either block, according to the bitmap words
+    for slot in before.iter().chain(after) {
+        let Some(&SlotKind::TickBitmap(word_pos)) = kinds.get(&slot.slot) else {
+            continue;
+        };
+        for bit_pos in (0..256).filter(|&bit_pos| slot.data.bit(bit_pos)) {
+            let tick =
+                I24::unchecked_from(((i32::from(word_pos) << 8) | bit_pos as i32) * tick_spacing);
+            let tick_slot = get_tick_slot(tick);
+            for offset in 0..4 {
+                kinds.insert(
+                    tick_slot + U256::from(offset),
+                    SlotKind::Tick { tick, offset },
+                );
+            }
+        }
+    }
+    let mut position_keys = BTreeMap::new();
+    for position in positions {
+        let key = get_position_key(position.owner, position.tickLower, position.tickUpper);
+        let position_slot = get_position_slot(key);
+        for offset in 0..4 {
+            kinds.insert(
+                position_slot + U256::from(offset),
+                SlotKind::Position { key, offset },
+            );
+        }
+        position_keys.insert(key, position);
+    }
+    let words = |slots: &[Slot]| -> BTreeMap<U256, U256> {
+        slots.iter().map(|slot| (slot.slot, slot.data)).collect()
+    };
+    let (words_before, words_after) = (words(before), words(after));
+    for slot_diff in diff
+        .added
+        .iter_mut()
+        .chain(&mut diff.removed)
+        .chain(&mut diff.changed)
+    {
+        slot_diff.kind = match slot_diff.slot.try_into() {
+            Ok(0_u64) => SlotKind::Slot0,
+            Ok(1_u64) => SlotKind::FeeGrowthGlobal0X128,
+            Ok(2_u64) => SlotKind::FeeGrowthGlobal1X128,
+            Ok(3_u64) => SlotKind::ProtocolFees,
+            Ok(4_u64) => SlotKind::Liquidity,
+            Ok(slot) if (OBSERVATIONS_SLOT..OBSERVATIONS_SLOT + 65535).contains(&slot) => {
+                SlotKind::Observation((slot - OBSERVATIONS_SLOT) as u16)
+            }
+            _ => kinds
+                .get(&slot_diff.slot)
+                .copied()
+                .unwrap_or(SlotKind::Unknown),
+        };
+        slot_diff.decoded_before = decode_pool_slot(
+            slot_diff.slot,
+            slot_diff.kind,
+            &words_before,
+            &position_keys,
+        );
+        slot_diff.decoded_after =
+            decode_pool_slot(slot_diff.slot, slot_diff.kind, &words_after, &position_keys);
+    }
+    diff
+}
+
+/// Get the static, ticks, tick bitmap and positions slots of a pool at a block.
+async fn get_pool_slots<N, P>(
+    pool: Address,
+    positions: &[PositionKey],
+    provider: P,
+    options: CallOptions,
+) -> Result<Vec<Slot>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let mut slots = get_static_slots(pool, &provider, options.clone()).await?;
+    slots.extend(get_ticks_slots(pool, MIN_TICK, MAX_TICK, &provider, options.clone()).await?);
+    slots.extend(get_tick_bitmap_slots(pool, &provider, options.clone()).await?);
+    if !positions.is_empty() {
+        slots.extend(get_positions_slots(pool, positions.to_vec(), &provider, options).await?);
+    }
+    Ok(slots)
+}
+
+/// Compare the storage of a pool at two blocks, including the static slots, every initialized
+/// tick, the tick bitmap and the given positions.
+///
+/// ## Arguments
+///
+/// * `pool`: The address of a V3 pool
+/// * `positions`: The positions to compare, as they cannot be enumerated from the pool
+/// * `from`: The first block to query
+/// * `to`: The second block to query
+/// * `provider`: The alloy provider
+/// * `options`: Optional [`CallOptions`] to query with, e.g. a gas limit for reading every tick,
+///   whose block is replaced by `from` and `to`
+///
+/// ## Returns
+///
+/// The slots that differ, labeled with their variable in the pool layout and decoded at both
+/// blocks
+#[inline]
+pub async fn get_pool_storage_diff<N, P>(
+    pool: Address,
+    positions: Vec<PositionKey>,
+    from: BlockId,
+    to: BlockId,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<StorageDiff, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let options = options.into();
+    let contract = IUniswapV3Pool::new(pool, &provider);
+    let (before, after, tick_spacing) = try_join3(
+        get_pool_slots(pool, &positions, &provider, options.clone().block(from)),
+        get_pool_slots(pool, &positions, &provider, options.clone().block(to)),
+        async {
+            Ok(options
+                .clone()
+                .block(to)
+                .apply(contract.tickSpacing())
+                .call()
+                .await?)
+        },
+    )
+    .await?;
+    Ok(diff_pool_slots(
+        &before,
+        &after,
+        tick_spacing.as_i32(),
+        &positions,
+    ))
+}
+
+/// Compare the storage of any contract at two blocks for the given slots.
+///
+/// ## Arguments
+///
+/// * `address`: The contract address to fetch storage from
+/// * `slots`: The storage slots to compare
+/// * `from`: The first block to query
+/// * `to`: The second block to query
+/// * `provider`: The alloy provider
+/// * `options`: Optional [`CallOptions`] to query with, whose block is replaced by `from` and `to`
+///
+/// ## Returns
+///
+/// The slots that differ, labeled as [`SlotKind::Unknown`]
+#[inline]
+pub async fn get_storage_diff<N, P>(
+    address: Address,
+    slots: Vec<B256>,
+    from: BlockId,
+    to: BlockId,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<StorageDiff, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let to_slots = |values: Vec<B256>| -> Vec<Slot> {
+        slots
+            .iter()
+            .zip(values)
+            .map(|(slot, data)| Slot {
+                slot: U256::from_be_bytes(slot.0),
+                data: U256::from_be_bytes(data.0),
+            })
+            .collect()
+    };
+    let options = options.into();
+    let (before, after) = try_join(
+        get_storage_at(
+            address,
+            slots.clone(),
+            &provider,
+            options.clone().block(from),
+        ),
+        get_storage_at(address, slots.clone(), &provider, options.block(to)),
+    )
+    .await?;
+    Ok(diff_slots(&to_slots(before), &to_slots(after)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use alloy::primitives::address;
+
+    const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+
+    fn slot(slot: U256, data: u64) -> Slot {
+        Slot {
+            slot,
+            data: U256::from(data),
+        }
+    }
+
+    #[test]
+    fn test_diff_slots() {
+        let before = [
+            slot(U256::from(1), 1),
+            slot(U256::from(2), 2),
+            slot(U256::from(3), 3),
+        ];
+        let after = [
+            slot(U256::from(2), 0),
+            slot(U256::from(3), 4),
+            slot(U256::from(4), 5),
+        ];
+        let diff = diff_slots(&before, &after);
+        assert_eq!(diff.len(), 4);
+        assert_eq!(
+            diff.added,
+            [SlotDiff {
+                slot: U256::from(4),
+                kind: SlotKind::Unknown,
+                before: U256::ZERO,
+                after: U256::from(5),
+                ..Default::default()
+            }]
+        );
+        let removed: Vec<U256> = diff.removed.iter().map(|d| d.slot).collect();
+        assert_eq!(removed, [U256::from(1), U256::from(2)]);
+        assert_eq!(diff.changed[0].before, U256::from(3));
+        assert_eq!(diff.changed[0].after, U256::from(4));
+        assert!(diff_slots(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_diff_pool_slots() {
+        // a tick at 120 is initialized in bitmap word 0 with a tick spacing of 60
+        let word = get_tick_bitmap_slot(0);
+        let tick = get_tick_slot(I24::unchecked_from(120));
+        let position = PositionKey {
+            owner: Address::repeat_byte(1),
+            tickLower: I24::unchecked_from(-60),
+            tickUpper: I24::unchecked_from(120),
+        };
+        let key = get_position_key(position.owner, position.tickLower, position.tickUpper);
+        let position_slot = get_position_slot(key);
+        let slot0 = U256::from(1) << 240 | U256::from(1) << 160 | U256::from(1_u64 << 32);
+        let before = [
+            slot(U256::ZERO, 1),
+            slot(U256::from(8), 1),
+            slot(word, 0),
+            slot(position_slot, 5),
+        ];
+        let after = [
+            Slot {
+                slot: U256::ZERO,
+                data: slot0,
+            },
+            slot(U256::from(8), 1),
+            slot(U256::from(9), 1),
+            slot(word, 4),
+            slot(tick, 10),
+            slot(tick + U256::from(2), 7),
+            slot(tick + U256::from(3), 1),
+            slot(U256::from(5), 1),
+            slot(position_slot, 5),
+            slot(position_slot + U256::from(3), 3),
+        ];
+        let diff = diff_pool_slots(&before, &after, 60, &[position]);
+        let kinds: Vec<SlotKind> = diff.iter().map(|d| d.kind).collect();
+        assert_eq!(kinds.len(), 8);
+        assert!(kinds.contains(&SlotKind::Observation(1)));
+        assert!(kinds.contains(&SlotKind::TickBitmap(0)));
+        assert!(kinds.contains(&SlotKind::Unknown));
+        assert!(kinds.contains(&SlotKind::Tick {
+            tick: I24::unchecked_from(120),
+            offset: 0,
+        }));
+        assert!(kinds.contains(&SlotKind::Tick {
+            tick: I24::unchecked_from(120),
+            offset: 3,
+        }));
+        assert!(kinds.contains(&SlotKind::Position { key, offset: 3 }));
+        let slot_diff = diff.changed[0];
+        assert_eq!(slot_diff.kind, SlotKind::Slot0);
+        assert_eq!((slot_diff.before, slot_diff.after), (U256::from(1), slot0));
+        let Some(SlotValue::Slot0(slot0)) = slot_diff.decoded_after else {
+            panic!("slot0 is not decoded");
+        };
+        assert_eq!(slot0.sqrt_price_x96, U160::from(1_u64 << 32));
+        assert_eq!(slot0.tick, I24::unchecked_from(1));
+        assert!(slot0.unlocked);
+        let decoded = |kind: SlotKind| {
+            let slot_diff = diff.iter().find(|d| d.kind == kind).unwrap();
+            (slot_diff.decoded_before, slot_diff.decoded_after)
+        };
+        // every word of a tick decodes the whole struct at its block
+        let tick_info = TickInfo {
+            liquidity_gross: 10,
+            fee_growth_outside1_x128: U256::from(7),
+            ..Default::default()
+        };
+        let tick_120 = I24::unchecked_from(120);
+        for offset in [0, 2] {
+            assert_eq!(
+                decoded(SlotKind::Tick {
+                    tick: tick_120,
+                    offset,
+                }),
+                (
+                    Some(SlotValue::Tick(TickInfo::default())),
+                    Some(SlotValue::Tick(tick_info))
+                )
+            );
+        }
+        assert_eq!(
+            decoded(SlotKind::Tick {
+                tick: tick_120,
+                offset: 3,
+            })
+            .1,
+            Some(SlotValue::TickOracle(TickOracle {
+                tick_cumulative_outside: I56::ONE,
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            decoded(SlotKind::Observation(1)).1,
+            Some(SlotValue::Observation(Observation {
+                block_timestamp: 1,
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            decoded(SlotKind::Position { key, offset: 3 }),
+            (
+                Some(SlotValue::Position(PositionInfo {
+                    liquidity: 5,
+                    ..Default::default()
+                })),
+                Some(SlotValue::Position(PositionInfo {
+                    liquidity: 5,
+                    tokens_owed0: 3,
+                    ..Default::default()
+                }))
+            )
+        );
+        assert_eq!(decoded(SlotKind::TickBitmap(0)), (None, None));
+        assert_eq!(decoded(SlotKind::Unknown), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_get_pool_storage_diff() {
+        let from = BLOCK_NUMBER;
+        let to = BlockId::number(BLOCK_NUMBER.as_u64().unwrap() + 10);
+        let diff = get_pool_storage_diff(
+            POOL_ADDRESS,
+            vec![],
+            from,
+            to,
+            &*PROVIDER,
+            CallOptions::default().gas(100_000_000),
+        )
+        .await
+        .unwrap();
+        assert!(!diff.is_empty());
+        assert!(diff.iter().all(|d| d.kind != SlotKind::Unknown));
+        assert!(diff
+            .iter()
+            .all(|d| matches!(d.kind, SlotKind::TickBitmap(_)) || d.decoded_after.is_some()));
+        let slots = diff.iter().map(|d| B256::from(d.slot)).collect::<Vec<_>>();
+        let storage_diff = get_storage_diff(POOL_ADDRESS, slots, from, to, &*PROVIDER, None)
+            .await
+            .unwrap();
+        assert_eq!(storage_diff.len(), diff.len());
+    }
+}
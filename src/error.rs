@@ -1,7 +1,12 @@
 #![allow(clippy::missing_inline_in_public_items)]
 
+use alloc::string::String;
 use alloy::{
-    contract::Error as ContractError, rpc::json_rpc::ErrorPayload, sol_types::Error as AbiError,
+    contract::Error as ContractError,
+    primitives::{B256, U256},
+    rpc::json_rpc::ErrorPayload,
+    sol_types::Error as AbiError,
+    transports::TransportError,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -17,4 +22,84 @@ pub enum Error {
     /// An error occurred interacting with a contract over RPC.
     #[error("{0}")]
     ContractError(#[from] ContractError),
+
+    /// An error occurred making an RPC request.
+    #[error("{0}")]
+    TransportError(#[from] TransportError),
+
+    /// The storage slots do not match the expected layout.
+    #[error("Invalid storage slots")]
+    InvalidSlots,
+
+    /// The oracle has no observations.
+    #[error("Oracle is not initialized")]
+    OracleNotInitialized,
+
+    /// The target timestamp is older than the oldest observation.
+    #[error("Observation is too old")]
+    ObservationTooOld,
+
+    /// The tick is outside of the valid range.
+    #[error("Invalid tick")]
+    InvalidTick,
+
+    /// The sqrt price is outside of the valid range.
+    #[error("Invalid sqrt price")]
+    InvalidSqrtPrice,
+
+    /// An arithmetic operation overflowed.
+    #[error("Arithmetic overflow")]
+    Overflow,
+
+    /// The tracked pool state is inconsistent with the logs or the chain.
+    #[error("Pool state mismatch")]
+    StateMismatch,
+
+    /// The block was not found.
+    #[error("Block not found")]
+    BlockNotFound,
+
+    /// The parent hash of a block does not match the hash of the tracked head.
+    #[error("Parent hash mismatch")]
+    ParentHashMismatch,
+
+    /// The chain reorganized beyond the oldest tracked block.
+    #[error("Reorg is deeper than the tracked history")]
+    ReorgTooDeep,
+
+    /// The serialized data has an unsupported version.
+    #[error("Unsupported version")]
+    InvalidVersion,
+
+    /// The averaging period is zero.
+    #[error("Invalid period")]
+    InvalidPeriod,
+
+    /// The Merkle proof does not verify against the state root.
+    #[error("Invalid Merkle proof")]
+    InvalidProof,
+
+    /// The storage value returned by the lens does not match the proven value.
+    #[error("Storage value mismatch at slot {0}")]
+    StorageMismatch(B256),
+
+    /// The storage layout references an unknown or malformed type.
+    #[error("Invalid storage layout")]
+    InvalidStorageLayout,
+
+    /// The path of a storage variable does not match the storage layout.
+    #[error("Invalid storage path")]
+    InvalidStoragePath,
+
+    /// The deployment of an ephemeral contract did not revert with the result.
+    #[error("Ephemeral contract did not revert")]
+    UnexpectedSuccess,
+
+    /// The simulated call reverted with the decoded reason.
+    #[error("Execution reverted: {0}")]
+    Reverted(String),
+
+    /// The token ID minted by a simulation does not match the predicted token ID.
+    #[error("Token ID mismatch: predicted {0}, minted {1}")]
+    TokenIdMismatch(U256, U256),
 }
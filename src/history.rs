@@ -0,0 +1,429 @@
+//! ## History
+//!
+//! The history module runs a lens across a list or a stride of blocks to build a time series, e.g.
+//! the fees of a position or the price of a pool over time. The blocks are queried with bounded
+//! concurrency and the results are returned in the order of the blocks, each with the block
+//! timestamp and its own result so that a failed block does not fail the whole series.
+//!
+//! The [`BlockResolver`] finds the block at a timestamp by binary search, so that any lens can be
+//! queried as of a point in time by passing a [`BlockOrTimestamp`] or
+//! [`CallOptions::at`](crate::caller::CallOptions::at) as its options.
+
+use crate::error::Error;
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy::{
+    consensus::BlockHeader,
+    eips::{BlockId, BlockNumberOrTag},
+    network::{BlockResponse, Network},
+    providers::Provider,
+};
+use core::future::Future;
+use futures::{future::join, stream, StreamExt};
+
+/// The result of a lens at a block.
+#[derive(Debug)]
+pub struct HistoryPoint<T> {
+    /// The block number
+    pub block_number: u64,
+    /// The timestamp of the block, or `None` if the block could not be fetched, which does not
+    /// affect `result`
+    pub timestamp: Option<u64>,
+    /// The result of the lens at the block
+    pub result: Result<T, Error>,
+}
+
+/// Get the timestamp of a block.
+///
+/// ## Arguments
+///
+/// * `block_number`: The block number
+/// * `provider`: The alloy provider
+///
+/// ## Returns
+///
+/// The timestamp of the block in seconds, or [`Error::BlockNotFound`]
+#[inline]
+pub async fn get_block_timestamp<N, P>(block_number: u64, provider: P) -> Result<u64, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let block = provider
+        .get_block_by_number(BlockNumberOrTag::Number(block_number))
+        .await?
+        .ok_or(Error::BlockNotFound)?;
+    Ok(block.header().timestamp())
+}
+
+/// A block or a point in time to query a lens at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockOrTimestamp {
+    /// A block number, hash or tag
+    Block(BlockId),
+    /// A unix timestamp in seconds, resolved to the last block at or before it
+    Timestamp(u64),
+}
+
+impl From<BlockId> for BlockOrTimestamp {
+    #[inline]
+    fn from(block_id: BlockId) -> Self {
+        Self::Block(block_id)
+    }
+}
+
+/// Resolves timestamps to block numbers by binary search over the block timestamps.
+///
+/// Every block timestamp fetched during a search is kept as an anchor that narrows the following
+/// searches, and known anchors can be provided upfront to avoid fetching them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockResolver {
+    /// The known block timestamps indexed by block number
+    anchors: BTreeMap<u64, u64>,
+}
+
+impl BlockResolver {
+    /// Create a resolver without anchors.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            anchors: BTreeMap::new(),
+        }
+    }
+
+    /// Create a resolver from known `(block_number, timestamp)` anchors.
+    #[inline]
+    pub fn with_anchors(anchors: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        Self {
+            anchors: anchors.into_iter().collect(),
+        }
+    }
+
+    /// The known block timestamps indexed by block number.
+    #[inline]
+    pub const fn anchors(&self) -> &BTreeMap<u64, u64> {
+        &self.anchors
+    }
+
+    /// Add a known block timestamp.
+    #[inline]
+    pub fn insert_anchor(&mut self, block_number: u64, timestamp: u64) {
+        self.anchors.insert(block_number, timestamp);
+    }
+
+    /// Binary search for the last block at or before `timestamp` between the anchors.
+    async fn search<F, Fut>(&mut self, timestamp: u64, mut get_timestamp: F) -> Result<u64, Error>
+    where
+        F: FnMut(u64) -> Fut,
+        Fut: Future<Output = Result<u64, Error>>,
+    {
+        // the block timestamps are non-decreasing
+        let (mut lo, _) = self
+            .anchors
+            .iter()
+            .rev()
+            .find(|(_, &t)| t <= timestamp)
+            .map(|(&b, &t)| (b, t))
+            .ok_or(Error::BlockNotFound)?;
+        let Some(mut hi) = self.anchors.range(lo + 1..).next().map(|(&b, _)| b) else {
+            return Ok(lo);
+        };
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            let t = get_timestamp(mid).await?;
+            self.anchors.insert(mid, t);
+            if t <= timestamp {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    /// Get the last block at or before a timestamp.
+    ///
+    /// ## Arguments
+    ///
+    /// * `timestamp`: The unix timestamp in seconds
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The block number, the latest block if `timestamp` is in the future, or
+    /// [`Error::BlockNotFound`] if `timestamp` is before the genesis block
+    #[inline]
+    pub async fn block_at_timestamp<N, P>(
+        &mut self,
+        timestamp: u64,
+        provider: P,
+    ) -> Result<u64, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        if self
+            .anchors
+            .first_key_value()
+            .is_none_or(|(_, &t)| t > timestamp)
+        {
+            self.insert_anchor(0, get_block_timestamp(0, &provider).await?);
+        }
+        if self
+            .anchors
+            .last_key_value()
+            .is_none_or(|(_, &t)| t <= timestamp)
+        {
+            let block = provider
+                .get_block_by_number(BlockNumberOrTag::Latest)
+                .await?
+                .ok_or(Error::BlockNotFound)?;
+            let header = block.header();
+            self.insert_anchor(header.number(), header.timestamp());
+        }
+        self.search(timestamp, |block_number| {
+            get_block_timestamp(block_number, &provider)
+        })
+        .await
+    }
+
+    /// Resolve a block or a timestamp to a block to pass to a lens.
+    ///
+    /// ## Arguments
+    ///
+    /// * `at`: The block or the timestamp to query at
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The block to query
+    #[inline]
+    pub async fn resolve<N, P>(
+        &mut self,
+        at: BlockOrTimestamp,
+        provider: P,
+    ) -> Result<BlockId, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        match at {
+            BlockOrTimestamp::Block(block_id) => Ok(block_id),
+            BlockOrTimestamp::Timestamp(timestamp) => Ok(BlockId::number(
+                self.block_at_timestamp(timestamp, provider).await?,
+            )),
+        }
+    }
+}
+
+/// Get the blocks from `from_block` to `to_block` every `step` blocks.
+///
+/// ## Arguments
+///
+/// * `from_block`: The first block
+/// * `to_block`: The last block, included even if it is not a multiple of `step` away
+/// * `step`: The number of blocks between two consecutive blocks
+///
+/// ## Returns
+///
+/// The block numbers in ascending order
+#[inline]
+pub fn block_stride(from_block: u64, to_block: u64, step: u64) -> Vec<u64> {
+    if from_block > to_block {
+        return Vec::new();
+    }
+    let mut blocks: Vec<u64> = (from_block..=to_block)
+        .step_by(step.max(1) as usize)
+        .collect();
+    if blocks.last() != Some(&to_block) {
+        blocks.push(to_block);
+    }
+    blocks
+}
+
+/// Run a lens at each of the blocks.
+///
+/// ## Arguments
+///
+/// * `blocks`: The block numbers to query
+/// * `concurrency`: The maximum number of blocks queried at the same time
+/// * `provider`: The alloy provider used to fetch the block timestamps
+/// * `lens`: The lens to run at a block, e.g. a closure calling
+///   [`get_static_slots`](crate::pool_lens::get_static_slots) with `Some(block_id)`
+///
+/// ## Returns
+///
+/// A vector of the results in the order of `blocks`
+#[inline]
+pub async fn query_history<N, P, F, Fut, T>(
+    blocks: impl IntoIterator<Item = u64>,
+    concurrency: usize,
+    provider: P,
+    lens: F,
+) -> Vec<HistoryPoint<T>>
+where
+    N: Network,
+    P: Provider<N>,
+    F: Fn(BlockId) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let provider = &provider;
+    let lens = &lens;
+    stream::iter(blocks)
+        .map(|block_number| async move {
+            let (timestamp, result) = join(
+                get_block_timestamp(block_number, provider),
+                lens(BlockId::number(block_number)),
+            )
+            .await;
+            HistoryPoint {
+                block_number,
+                timestamp: timestamp.ok(),
+                result,
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pool_lens::get_static_slots, tests::*};
+    use alloy::{
+        primitives::address,
+        providers::ProviderBuilder,
+        rpc::types::{Block, Header},
+        transports::mock::Asserter,
+    };
+
+    #[test]
+    fn test_block_stride() {
+        assert_eq!(block_stride(10, 20, 5), vec![10, 15, 20]);
+        assert_eq!(block_stride(10, 22, 5), vec![10, 15, 20, 22]);
+        assert_eq!(block_stride(10, 10, 5), vec![10]);
+        assert_eq!(block_stride(10, 12, 0), vec![10, 11, 12]);
+        assert!(block_stride(20, 10, 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_history_partial_failure() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let block = |number: u64| Block::<()> {
+            header: Header::new(alloy::consensus::Header {
+                number,
+                timestamp: 1_000 + number * 12,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        asserter.push_success(&block(1));
+        asserter.push_failure_msg("block unavailable");
+        asserter.push_success(&block(3));
+        let points = query_history([1, 2, 3], 1, provider, |block_id| async move {
+            match block_id.as_u64() {
+                Some(3) => Err(Error::InvalidSlots),
+                number => Ok(number.unwrap()),
+            }
+        })
+        .await;
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].block_number, 1);
+        assert_eq!(points[0].timestamp, Some(1_012));
+        assert_eq!(points[0].result.as_ref().unwrap(), &1);
+        // the block could not be fetched but the lens result is kept
+        assert_eq!(points[1].timestamp, None);
+        assert_eq!(points[1].result.as_ref().unwrap(), &2);
+        // the lens failed
+        assert_eq!(points[2].timestamp, Some(1_036));
+        assert!(matches!(points[2].result, Err(Error::InvalidSlots)));
+    }
+
+    #[tokio::test]
+    async fn test_block_resolver_search() {
+        let calls = core::cell::Cell::new(0);
+        let get_timestamp = |block_number: u64| {
+            calls.set(calls.get() + 1);
+            async move { Ok(1_000 + block_number * 12) }
+        };
+        let mut resolver = BlockResolver::with_anchors([(0, 1_000), (1_000, 13_000)]);
+        assert_eq!(
+            resolver
+                .search(1_000 + 500 * 12 + 5, get_timestamp)
+                .await
+                .unwrap(),
+            500
+        );
+        assert!(calls.get() <= 10);
+        // the anchors found in the first search are reused
+        let calls_before = calls.get();
+        assert_eq!(
+            resolver
+                .search(1_000 + 500 * 12, get_timestamp)
+                .await
+                .unwrap(),
+            500
+        );
+        assert_eq!(calls.get(), calls_before);
+        // after the newest anchor
+        assert_eq!(resolver.search(20_000, get_timestamp).await.unwrap(), 1_000);
+        // before the oldest anchor
+        assert!(matches!(
+            resolver.search(999, get_timestamp).await,
+            Err(Error::BlockNotFound)
+        ));
+
+        // multiple blocks with the same timestamp resolve to the last of them
+        let mut resolver = BlockResolver::with_anchors([(0, 1_000), (1_000, 1_500)]);
+        let get_timestamp = |block_number: u64| async move { Ok(1_000 + block_number / 2) };
+        assert_eq!(resolver.search(1_100, get_timestamp).await.unwrap(), 201);
+    }
+
+    #[tokio::test]
+    async fn test_block_at_timestamp() {
+        let provider = PROVIDER.clone();
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let timestamp = get_block_timestamp(block_number, provider.clone())
+            .await
+            .unwrap();
+        let mut resolver = BlockResolver::new();
+        let block_id = resolver
+            .resolve(BlockOrTimestamp::Timestamp(timestamp), provider.clone())
+            .await
+            .unwrap();
+        assert_eq!(block_id, BLOCK_NUMBER);
+        assert_eq!(
+            resolver
+                .block_at_timestamp(timestamp + 1, provider)
+                .await
+                .unwrap(),
+            block_number
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_history() {
+        let provider = PROVIDER.clone();
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let blocks = block_stride(block_number - 1000, block_number, 100);
+        let points = query_history(blocks.clone(), 4, provider.clone(), |block_id| {
+            get_static_slots(
+                address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+                provider.clone(),
+                Some(block_id),
+            )
+        })
+        .await;
+        assert_eq!(
+            points.iter().map(|p| p.block_number).collect::<Vec<_>>(),
+            blocks
+        );
+        assert!(points
+            .windows(2)
+            .all(|w| w[0].timestamp.unwrap() < w[1].timestamp.unwrap()));
+        assert!(points.iter().all(|p| p.result.is_ok()));
+    }
+}
@@ -17,16 +17,41 @@
 
 extern crate alloc;
 
+pub mod analytics;
 pub mod bindings;
 pub mod caller;
+pub mod diff;
 pub mod error;
+pub mod history;
+pub mod math;
+pub mod oracle;
 pub mod pool_lens;
+pub mod pool_state;
 pub mod position_lens;
+pub mod registry;
+#[cfg(feature = "serde")]
+mod serde_helpers;
+pub mod simulation;
+pub mod snapshot;
+pub mod state_override;
+pub mod storage_layout;
 pub mod storage_lens;
+pub mod storage_slot;
+pub mod token_lens;
+pub mod tracker;
 
 #[cfg(test)]
 mod tests;
 
+// dependencies of the `uniswap-lens` binary
+#[cfg(feature = "cli")]
+use {clap as _, serde_json as _, tokio as _};
+
 pub mod prelude {
-    pub use super::{error::Error, pool_lens::*, position_lens::*, storage_lens::*};
+    pub use super::{
+        analytics::*, caller::*, diff::*, error::Error, history::*, oracle::*, pool_lens::*,
+        pool_state::*, position_lens::*, registry::*, simulation::*, snapshot::*,
+        state_override::*, storage_layout::*, storage_lens::*, storage_slot::*, token_lens::*,
+        tracker::*,
+    };
 }
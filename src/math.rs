@@ -0,0 +1,745 @@
+//! ## Math
+//!
+//! Bit-exact ports of `TickMath`, `FullMath`, `SqrtPriceMath` and `LiquidityAmounts` from the
+//! Uniswap V3 libraries used by the lens contracts, so that lens results can be valued client side.
+
+use crate::error::Error;
+use alloy::primitives::{
+    aliases::{I24, U160, U24},
+    ruint::UintTryFrom,
+    uint, I256, U256, U512,
+};
+
+/// The minimum tick that may be passed to [`get_sqrt_ratio_at_tick`] computed from log base
+/// 1.0001 of 2**-128.
+pub const MIN_TICK: I24 = I24::from_raw(U24::from_limbs([0xf27618]));
+/// The maximum tick that may be passed to [`get_sqrt_ratio_at_tick`] computed from log base
+/// 1.0001 of 2**128.
+pub const MAX_TICK: I24 = I24::from_raw(U24::from_limbs([0xd89e8]));
+
+/// The minimum value that can be returned from [`get_sqrt_ratio_at_tick`]. Equivalent to
+/// `get_sqrt_ratio_at_tick(MIN_TICK)`.
+pub const MIN_SQRT_RATIO: U160 = uint!(4295128739_U160);
+/// The maximum value that can be returned from [`get_sqrt_ratio_at_tick`]. Equivalent to
+/// `get_sqrt_ratio_at_tick(MAX_TICK)`.
+pub const MAX_SQRT_RATIO: U160 = uint!(1461446703485210103287273052203988822378723970342_U160);
+
+/// 2^96
+pub const Q96: U256 = U256::from_limbs([0, 1 << 32, 0, 0]);
+/// 2^128
+pub const Q128: U256 = U256::from_limbs([0, 0, 1, 0]);
+
+/// Calculates floor(a×b÷denominator) with full precision.
+///
+/// ## Arguments
+///
+/// * `a`: The multiplicand
+/// * `b`: The multiplier
+/// * `denominator`: The divisor
+///
+/// ## Returns
+///
+/// The 256-bit result, or an error if the denominator is zero or the result overflows
+#[inline]
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256, Error> {
+    if denominator.is_zero() {
+        return Err(Error::Overflow);
+    }
+    let product: U512 = a.widening_mul(b);
+    let (quotient, _) = product.div_rem(U512::from(denominator));
+    U256::uint_try_from(quotient).map_err(|_| Error::Overflow)
+}
+
+/// Calculates ceil(a×b÷denominator) with full precision.
+///
+/// ## Arguments
+///
+/// * `a`: The multiplicand
+/// * `b`: The multiplier
+/// * `denominator`: The divisor
+///
+/// ## Returns
+///
+/// The 256-bit result, or an error if the denominator is zero or the result overflows
+#[inline]
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256, Error> {
+    if denominator.is_zero() {
+        return Err(Error::Overflow);
+    }
+    let product: U512 = a.widening_mul(b);
+    let (quotient, remainder) = product.div_rem(U512::from(denominator));
+    let quotient = U256::uint_try_from(quotient).map_err(|_| Error::Overflow)?;
+    if remainder.is_zero() {
+        Ok(quotient)
+    } else {
+        quotient.checked_add(U256::ONE).ok_or(Error::Overflow)
+    }
+}
+
+/// Add a signed liquidity delta to liquidity, equivalent to `LiquidityMath.addDelta`.
+///
+/// ## Arguments
+///
+/// * `x`: The liquidity before change
+/// * `y`: The delta by which liquidity should be changed
+///
+/// ## Returns
+///
+/// The liquidity after, or an error if the result underflows or overflows
+#[inline]
+pub const fn add_delta(x: u128, y: i128) -> Result<u128, Error> {
+    match x.checked_add_signed(y) {
+        Some(z) => Ok(z),
+        None => Err(Error::Overflow),
+    }
+}
+
+/// Returns ceil(x / y), where y is nonzero.
+fn div_rounding_up(x: U256, y: U256) -> U256 {
+    let (quotient, remainder) = x.div_rem(y);
+    if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + U256::ONE
+    }
+}
+
+/// Calculates sqrt(1.0001^tick) * 2^96.
+///
+/// ## Arguments
+///
+/// * `tick`: The input tick for the above formula
+///
+/// ## Returns
+///
+/// A Fixed point Q64.96 number representing the sqrt of the ratio of the two assets
+/// (token1/token0) at the given tick
+#[inline]
+pub fn get_sqrt_ratio_at_tick(tick: I24) -> Result<U160, Error> {
+    let tick = tick.as_i32();
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK.as_i32() as u32 {
+        return Err(Error::InvalidTick);
+    }
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001_u128)
+    } else {
+        Q128
+    };
+    for (bit, multiplier) in [
+        (0x2, 0xfff97272373d413259a46990580e213a_u128),
+        (0x4, 0xfff2e50f5f656932ef12357cf3c7fdcc),
+        (0x8, 0xffe5caca7e10e4e61c3624eaa0941cd0),
+        (0x10, 0xffcb9843d60f6159c9db58835c926644),
+        (0x20, 0xff973b41fa98c081472e6896dfb254c0),
+        (0x40, 0xff2ea16466c96a3843ec78b326b52861),
+        (0x80, 0xfe5dee046a99a2a811c461f1969c3053),
+        (0x100, 0xfcbe86c7900a88aedcffc83b479aa3a4),
+        (0x200, 0xf987a7253ac413176f2b074cf7815e54),
+        (0x400, 0xf3392b0822b70005940c7a398e4b70f3),
+        (0x800, 0xe7159475a2c29b7443b29c7fa6e889d9),
+        (0x1000, 0xd097f3bdfd2022b8845ad8f792aa5825),
+        (0x2000, 0xa9f746462d870fdf8a65dc1f90e061e5),
+        (0x4000, 0x70d869a156d2a1b890bb3df62baf32f7),
+        (0x8000, 0x31be135f97d08fd981231505542fcfa6),
+        (0x10000, 0x9aa508b5b7a84e1c677de54f3e99bc9),
+        (0x20000, 0x5d6af8dedb81196699c329225ee604),
+        (0x40000, 0x2216e584f5fa1ea926041bedfe98),
+        (0x80000, 0x48a170391f7dc42444e8fa2),
+    ] {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * U256::from(multiplier)) >> 128;
+        }
+    }
+
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // this divides by 1<<32 rounding up to go from a Q128.128 to a Q128.96.
+    // we then downcast because we know the result always fits within 160 bits due to our tick
+    // input constraint. we round up in the division so getTickAtSqrtRatio of the output price is
+    // always consistent
+    let rounded: U256 = (ratio >> 32) + U256::from((ratio.as_limbs()[0] as u32 != 0) as u8);
+    Ok(rounded.to())
+}
+
+/// Calculates the greatest tick value such that getRatioAtTick(tick) <= ratio.
+///
+/// ## Arguments
+///
+/// * `sqrt_price_x96`: The sqrt ratio for which to compute the tick as a Q64.96
+///
+/// ## Returns
+///
+/// The greatest tick for which the ratio is less than or equal to the input ratio
+#[inline]
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U160) -> Result<I24, Error> {
+    // second inequality must be < because the price can never reach the price at the max tick
+    if sqrt_price_x96 < MIN_SQRT_RATIO || sqrt_price_x96 >= MAX_SQRT_RATIO {
+        return Err(Error::InvalidSqrtPrice);
+    }
+    let ratio: U256 = U256::from(sqrt_price_x96) << 32;
+
+    let msb = ratio.bit_len() - 1;
+    let mut r = if msb >= 128 {
+        ratio >> (msb - 127)
+    } else {
+        ratio << (127 - msb)
+    };
+
+    let mut log_2 = I256::from_raw(U256::from(msb)) - I256::from_raw(U256::from(128));
+    log_2 <<= 64;
+    for shift in (50..64).rev() {
+        r = (r * r).wrapping_shr(127);
+        let f = r.wrapping_shr(128);
+        log_2 |= I256::from_raw(f << shift);
+        r >>= f.to::<usize>();
+    }
+
+    // 128.128 number
+    let log_sqrt10001 = log_2 * I256::from_raw(uint!(255738958999603826347141_U256));
+
+    let tick_low = (log_sqrt10001
+        - I256::from_raw(uint!(3402992956809132418596140100660247210_U256)))
+    .asr(128)
+    .as_i32();
+    let tick_hi = (log_sqrt10001
+        + I256::from_raw(uint!(291339464771989622907027621153398088495_U256)))
+    .asr(128)
+    .as_i32();
+
+    let tick_low = I24::try_from(tick_low).map_err(|_| Error::InvalidSqrtPrice)?;
+    let tick_hi = I24::try_from(tick_hi).map_err(|_| Error::InvalidSqrtPrice)?;
+    Ok(if tick_low == tick_hi {
+        tick_low
+    } else if get_sqrt_ratio_at_tick(tick_hi)? <= sqrt_price_x96 {
+        tick_hi
+    } else {
+        tick_low
+    })
+}
+
+/// Gets the amount0 delta between two prices, i.e. liquidity / sqrt(lower) - liquidity /
+/// sqrt(upper).
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price
+/// * `sqrt_ratio_b_x96`: Another sqrt price
+/// * `liquidity`: The amount of usable liquidity
+/// * `round_up`: Whether to round the amount up or down
+///
+/// ## Returns
+///
+/// Amount of token0 required to cover a position of size liquidity between the two passed prices
+#[inline]
+pub fn get_amount0_delta(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256, Error> {
+    let (sqrt_ratio_a_x96, sqrt_ratio_b_x96) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (U256::from(sqrt_ratio_b_x96), U256::from(sqrt_ratio_a_x96))
+    } else {
+        (U256::from(sqrt_ratio_a_x96), U256::from(sqrt_ratio_b_x96))
+    };
+    if sqrt_ratio_a_x96.is_zero() {
+        return Err(Error::InvalidSqrtPrice);
+    }
+
+    let numerator1 = U256::from(liquidity) << 96;
+    let numerator2 = sqrt_ratio_b_x96 - sqrt_ratio_a_x96;
+
+    if round_up {
+        Ok(div_rounding_up(
+            mul_div_rounding_up(numerator1, numerator2, sqrt_ratio_b_x96)?,
+            sqrt_ratio_a_x96,
+        ))
+    } else {
+        Ok(mul_div(numerator1, numerator2, sqrt_ratio_b_x96)? / sqrt_ratio_a_x96)
+    }
+}
+
+/// Gets the amount1 delta between two prices, i.e. liquidity * (sqrt(upper) - sqrt(lower)).
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price
+/// * `sqrt_ratio_b_x96`: Another sqrt price
+/// * `liquidity`: The amount of usable liquidity
+/// * `round_up`: Whether to round the amount up, or down
+///
+/// ## Returns
+///
+/// Amount of token1 required to cover a position of size liquidity between the two passed prices
+#[inline]
+pub fn get_amount1_delta(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256, Error> {
+    let (sqrt_ratio_a_x96, sqrt_ratio_b_x96) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    let difference = U256::from(sqrt_ratio_b_x96 - sqrt_ratio_a_x96);
+
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), difference, Q96)
+    } else {
+        mul_div(U256::from(liquidity), difference, Q96)
+    }
+}
+
+/// Helper that gets the signed token0 delta.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price
+/// * `sqrt_ratio_b_x96`: Another sqrt price
+/// * `liquidity`: The change in liquidity for which to compute the amount0 delta
+///
+/// ## Returns
+///
+/// Amount of token0 corresponding to the passed liquidity delta between the two prices
+#[inline]
+pub fn get_amount0_delta_signed(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: i128,
+) -> Result<I256, Error> {
+    let amount = get_amount0_delta(
+        sqrt_ratio_a_x96,
+        sqrt_ratio_b_x96,
+        liquidity.unsigned_abs(),
+        liquidity >= 0,
+    )?;
+    let amount = I256::try_from(amount).map_err(|_| Error::Overflow)?;
+    Ok(if liquidity < 0 { -amount } else { amount })
+}
+
+/// Helper that gets the signed token1 delta.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price
+/// * `sqrt_ratio_b_x96`: Another sqrt price
+/// * `liquidity`: The change in liquidity for which to compute the amount1 delta
+///
+/// ## Returns
+///
+/// Amount of token1 corresponding to the passed liquidity delta between the two prices
+#[inline]
+pub fn get_amount1_delta_signed(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: i128,
+) -> Result<I256, Error> {
+    let amount = get_amount1_delta(
+        sqrt_ratio_a_x96,
+        sqrt_ratio_b_x96,
+        liquidity.unsigned_abs(),
+        liquidity >= 0,
+    )?;
+    let amount = I256::try_from(amount).map_err(|_| Error::Overflow)?;
+    Ok(if liquidity < 0 { -amount } else { amount })
+}
+
+/// Computes the amount of liquidity received for a given amount of token0 and price range.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `amount0`: The amount0 being sent in
+///
+/// ## Returns
+///
+/// The amount of returned liquidity
+#[inline]
+pub fn get_liquidity_for_amount0(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    amount0: U256,
+) -> Result<u128, Error> {
+    let (sqrt_ratio_a_x96, sqrt_ratio_b_x96) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (U256::from(sqrt_ratio_b_x96), U256::from(sqrt_ratio_a_x96))
+    } else {
+        (U256::from(sqrt_ratio_a_x96), U256::from(sqrt_ratio_b_x96))
+    };
+    let intermediate = mul_div(sqrt_ratio_a_x96, sqrt_ratio_b_x96, Q96)?;
+    let liquidity = mul_div(amount0, intermediate, sqrt_ratio_b_x96 - sqrt_ratio_a_x96)?;
+    u128::try_from(liquidity).map_err(|_| Error::Overflow)
+}
+
+/// Computes the amount of liquidity received for a given amount of token1 and price range.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `amount1`: The amount1 being sent in
+///
+/// ## Returns
+///
+/// The amount of returned liquidity
+#[inline]
+pub fn get_liquidity_for_amount1(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    amount1: U256,
+) -> Result<u128, Error> {
+    let difference = U256::from(sqrt_ratio_a_x96.abs_diff(sqrt_ratio_b_x96));
+    let liquidity = mul_div(amount1, Q96, difference)?;
+    u128::try_from(liquidity).map_err(|_| Error::Overflow)
+}
+
+/// Computes the maximum amount of liquidity received for a given amount of token0, token1, the
+/// current pool prices and the prices at the tick boundaries.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_x96`: A sqrt price representing the current pool prices
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `amount0`: The amount of token0 being sent in
+/// * `amount1`: The amount of token1 being sent in
+///
+/// ## Returns
+///
+/// The maximum amount of liquidity received
+#[inline]
+pub fn get_liquidity_for_amounts(
+    sqrt_ratio_x96: U160,
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    amount0: U256,
+    amount1: U256,
+) -> Result<u128, Error> {
+    let (sqrt_ratio_a_x96, sqrt_ratio_b_x96) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if sqrt_ratio_x96 <= sqrt_ratio_a_x96 {
+        get_liquidity_for_amount0(sqrt_ratio_a_x96, sqrt_ratio_b_x96, amount0)
+    } else if sqrt_ratio_x96 < sqrt_ratio_b_x96 {
+        let liquidity0 = get_liquidity_for_amount0(sqrt_ratio_x96, sqrt_ratio_b_x96, amount0)?;
+        let liquidity1 = get_liquidity_for_amount1(sqrt_ratio_a_x96, sqrt_ratio_x96, amount1)?;
+        Ok(liquidity0.min(liquidity1))
+    } else {
+        get_liquidity_for_amount1(sqrt_ratio_a_x96, sqrt_ratio_b_x96, amount1)
+    }
+}
+
+/// Computes the amount of token0 for a given amount of liquidity and a price range.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `liquidity`: The liquidity being valued
+///
+/// ## Returns
+///
+/// The amount of token0
+#[inline]
+pub fn get_amount0_for_liquidity(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: u128,
+) -> Result<U256, Error> {
+    get_amount0_delta(sqrt_ratio_a_x96, sqrt_ratio_b_x96, liquidity, false)
+}
+
+/// Computes the amount of token1 for a given amount of liquidity and a price range.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `liquidity`: The liquidity being valued
+///
+/// ## Returns
+///
+/// The amount of token1
+#[inline]
+pub fn get_amount1_for_liquidity(
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: u128,
+) -> Result<U256, Error> {
+    get_amount1_delta(sqrt_ratio_a_x96, sqrt_ratio_b_x96, liquidity, false)
+}
+
+/// Computes the token0 and token1 value for a given amount of liquidity, the current pool prices
+/// and the prices at the tick boundaries.
+///
+/// ## Arguments
+///
+/// * `sqrt_ratio_x96`: A sqrt price representing the current pool prices
+/// * `sqrt_ratio_a_x96`: A sqrt price representing the first tick boundary
+/// * `sqrt_ratio_b_x96`: A sqrt price representing the second tick boundary
+/// * `liquidity`: The liquidity being valued
+///
+/// ## Returns
+///
+/// The amount of token0 and the amount of token1
+#[inline]
+pub fn get_amounts_for_liquidity(
+    sqrt_ratio_x96: U160,
+    sqrt_ratio_a_x96: U160,
+    sqrt_ratio_b_x96: U160,
+    liquidity: u128,
+) -> Result<(U256, U256), Error> {
+    let (sqrt_ratio_a_x96, sqrt_ratio_b_x96) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if sqrt_ratio_x96 <= sqrt_ratio_a_x96 {
+        Ok((
+            get_amount0_for_liquidity(sqrt_ratio_a_x96, sqrt_ratio_b_x96, liquidity)?,
+            U256::ZERO,
+        ))
+    } else if sqrt_ratio_x96 < sqrt_ratio_b_x96 {
+        Ok((
+            get_amount0_for_liquidity(sqrt_ratio_x96, sqrt_ratio_b_x96, liquidity)?,
+            get_amount1_for_liquidity(sqrt_ratio_a_x96, sqrt_ratio_x96, liquidity)?,
+        ))
+    } else {
+        Ok((
+            U256::ZERO,
+            get_amount1_for_liquidity(sqrt_ratio_a_x96, sqrt_ratio_b_x96, liquidity)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(tick: i32) -> I24 {
+        I24::try_from(tick).unwrap()
+    }
+
+    // encodePriceSqrt(1, 1)
+    const PRICE_1_1: U160 = uint!(79228162514264337593543950336_U160);
+    // encodePriceSqrt(121, 100)
+    const PRICE_121_100: U160 = uint!(87150978765690771352898345369_U160);
+    // encodePriceSqrt(100, 110)
+    const PRICE_100_110: U160 = uint!(75541088972021052632782079082_U160);
+    // encodePriceSqrt(110, 100)
+    const PRICE_110_100: U160 = uint!(83095197869223157896060286990_U160);
+    // encodePriceSqrt(99, 110)
+    const PRICE_99_110: U160 = uint!(75162434512514379355924140470_U160);
+    // encodePriceSqrt(111, 100)
+    const PRICE_111_100: U160 = uint!(83472048772503575395058907992_U160);
+
+    #[test]
+    fn test_mul_div() {
+        let three = U256::from(3);
+        assert_eq!(
+            mul_div(
+                Q128,
+                U256::from(50) * Q128 / U256::from(100),
+                U256::from(150) * Q128 / U256::from(100)
+            )
+            .unwrap(),
+            Q128 / three
+        );
+        assert_eq!(
+            mul_div_rounding_up(
+                Q128,
+                U256::from(50) * Q128 / U256::from(100),
+                U256::from(150) * Q128 / U256::from(100)
+            )
+            .unwrap(),
+            Q128 / three + U256::ONE
+        );
+        // all max inputs
+        assert_eq!(mul_div(U256::MAX, U256::MAX, U256::MAX).unwrap(), U256::MAX);
+        assert_eq!(
+            mul_div_rounding_up(U256::MAX, U256::MAX, U256::MAX).unwrap(),
+            U256::MAX
+        );
+        // phantom overflow
+        assert_eq!(
+            mul_div(Q128, U256::from(35) * Q128, U256::from(8) * Q128).unwrap(),
+            U256::from(4375) * Q128 / U256::from(1000)
+        );
+        assert!(matches!(
+            mul_div(Q128, Q128, U256::ONE),
+            Err(Error::Overflow)
+        ));
+        assert!(matches!(
+            mul_div(Q128, Q128, U256::ZERO),
+            Err(Error::Overflow)
+        ));
+        assert!(matches!(
+            mul_div_rounding_up(U256::MAX, U256::MAX, U256::MAX - U256::ONE),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_get_sqrt_ratio_at_tick() {
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), MIN_SQRT_RATIO);
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), MAX_SQRT_RATIO);
+        assert_eq!(get_sqrt_ratio_at_tick(tick(0)).unwrap(), PRICE_1_1);
+        for (t, expected) in [
+            (50, uint!(79426470787362580746886972461_U160)),
+            (100, uint!(79625275426524748796330556128_U160)),
+            (250, uint!(80224679980005306637834519095_U160)),
+            (500, uint!(81233731461783161732293370115_U160)),
+            (1000, uint!(83290069058676223003182343270_U160)),
+            (2500, uint!(89776708723587163891445672585_U160)),
+            (3000, uint!(92049301871182272007977902845_U160)),
+            (4000, uint!(96768528593268422080558758223_U160)),
+            (5000, uint!(101729702841318637793976746270_U160)),
+            (50000, uint!(965075977353221155028623082916_U160)),
+            (150000, uint!(143194173941309278083010301478497_U160)),
+            (250000, uint!(21246587762933397357449903968194344_U160)),
+            (500000, uint!(5697689776495288729098254600827762987878_U160)),
+            (
+                738203,
+                uint!(847134979253254120489401328389043031315994541_U160),
+            ),
+            (-50, uint!(79030349367926598376800521322_U160)),
+            (-1000, uint!(75364347830767020784054125655_U160)),
+            (-150000, uint!(43836292794701720435367485_U160)),
+            (-738203, uint!(7409801140451_U160)),
+        ] {
+            assert_eq!(
+                get_sqrt_ratio_at_tick(tick(t)).unwrap(),
+                expected,
+                "tick {t}"
+            );
+        }
+        assert!(matches!(
+            get_sqrt_ratio_at_tick(MIN_TICK - tick(1)),
+            Err(Error::InvalidTick)
+        ));
+        assert!(matches!(
+            get_sqrt_ratio_at_tick(MAX_TICK + tick(1)),
+            Err(Error::InvalidTick)
+        ));
+    }
+
+    #[test]
+    fn test_get_tick_at_sqrt_ratio() {
+        assert_eq!(get_tick_at_sqrt_ratio(MIN_SQRT_RATIO).unwrap(), MIN_TICK);
+        assert_eq!(
+            get_tick_at_sqrt_ratio(MAX_SQRT_RATIO - U160::ONE).unwrap(),
+            MAX_TICK - tick(1)
+        );
+        assert_eq!(get_tick_at_sqrt_ratio(PRICE_1_1).unwrap(), tick(0));
+        assert!(matches!(
+            get_tick_at_sqrt_ratio(MIN_SQRT_RATIO - U160::ONE),
+            Err(Error::InvalidSqrtPrice)
+        ));
+        assert!(matches!(
+            get_tick_at_sqrt_ratio(MAX_SQRT_RATIO),
+            Err(Error::InvalidSqrtPrice)
+        ));
+        // the greatest tick whose sqrt ratio is at most the input
+        for t in (MIN_TICK.as_i32() + 1..MAX_TICK.as_i32()).step_by(997) {
+            let sqrt_ratio = get_sqrt_ratio_at_tick(tick(t)).unwrap();
+            assert_eq!(get_tick_at_sqrt_ratio(sqrt_ratio).unwrap(), tick(t));
+            assert_eq!(
+                get_tick_at_sqrt_ratio(sqrt_ratio - U160::ONE).unwrap(),
+                tick(t - 1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_amount_delta() {
+        let liquidity = 10_u128.pow(18);
+        assert_eq!(
+            get_amount0_delta(PRICE_1_1, PRICE_121_100, liquidity, true).unwrap(),
+            U256::from(90909090909090910_u64)
+        );
+        assert_eq!(
+            get_amount0_delta(PRICE_1_1, PRICE_121_100, liquidity, false).unwrap(),
+            U256::from(90909090909090909_u64)
+        );
+        assert_eq!(
+            get_amount1_delta(PRICE_1_1, PRICE_121_100, liquidity, true).unwrap(),
+            U256::from(100000000000000000_u64)
+        );
+        assert_eq!(
+            get_amount1_delta(PRICE_1_1, PRICE_121_100, liquidity, false).unwrap(),
+            U256::from(99999999999999999_u64)
+        );
+        assert_eq!(
+            get_amount0_delta_signed(PRICE_1_1, PRICE_121_100, -(liquidity as i128)).unwrap(),
+            I256::try_from(-90909090909090909_i64).unwrap()
+        );
+        assert_eq!(
+            get_amount1_delta_signed(PRICE_121_100, PRICE_1_1, liquidity as i128).unwrap(),
+            I256::try_from(100000000000000000_i64).unwrap()
+        );
+        assert!(get_amount0_delta(PRICE_1_1, PRICE_1_1, liquidity, true)
+            .unwrap()
+            .is_zero());
+        assert!(matches!(
+            get_amount0_delta(U160::ZERO, PRICE_1_1, liquidity, true),
+            Err(Error::InvalidSqrtPrice)
+        ));
+    }
+
+    #[test]
+    fn test_get_liquidity_for_amounts() {
+        let (amount0, amount1) = (U256::from(100), U256::from(200));
+        for (sqrt_price_x96, expected) in [
+            // price inside
+            (PRICE_1_1, 2148),
+            // price below
+            (PRICE_99_110, 1048),
+            // price above
+            (PRICE_111_100, 2097),
+            // price on the boundaries
+            (PRICE_100_110, 1048),
+            (PRICE_110_100, 2097),
+        ] {
+            assert_eq!(
+                get_liquidity_for_amounts(
+                    sqrt_price_x96,
+                    PRICE_100_110,
+                    PRICE_110_100,
+                    amount0,
+                    amount1
+                )
+                .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_amounts_for_liquidity() {
+        for (sqrt_price_x96, liquidity, expected) in [
+            // price inside
+            (PRICE_1_1, 2148, (99, 99)),
+            // price below
+            (PRICE_99_110, 1048, (99, 0)),
+            // price above
+            (PRICE_111_100, 2097, (0, 199)),
+            // price on the boundaries
+            (PRICE_100_110, 1048, (99, 0)),
+            (PRICE_110_100, 2097, (0, 199)),
+        ] {
+            assert_eq!(
+                get_amounts_for_liquidity(sqrt_price_x96, PRICE_100_110, PRICE_110_100, liquidity)
+                    .unwrap(),
+                (U256::from(expected.0), U256::from(expected.1))
+            );
+        }
+    }
+}
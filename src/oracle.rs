@@ -0,0 +1,490 @@
+//! ## Oracle
+//!
+//! The oracle module decodes the `observations` ring buffer of a pool returned by
+//! [`get_static_slots`](crate::pool_lens::get_static_slots) and reproduces `Oracle.observe` and
+//! `OracleLibrary.consult` offline.
+
+use crate::{bindings::ephemeralpoolslots::PoolUtils::Slot, error::Error};
+use alloc::vec::Vec;
+use alloy::primitives::{
+    aliases::{I24, I56, U160, U24, U56},
+    U256,
+};
+
+/// The number of slots preceding the `observations` array in the output of `get_static_slots`.
+const OBSERVATIONS_OFFSET: usize = 5;
+
+/// An observation of the pool's tick and liquidity accumulators, as stored in `UniswapV3Pool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Observation {
+    /// The block timestamp of the observation
+    pub block_timestamp: u32,
+    /// The tick accumulator, i.e. tick * time elapsed since the pool was first initialized
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tick_cumulative: I56,
+    /// The seconds per liquidity, i.e. seconds elapsed / max(1, liquidity) since the pool was
+    /// first initialized
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub seconds_per_liquidity_cumulative_x128: U160,
+    /// Whether the observation is initialized
+    pub initialized: bool,
+}
+
+impl Observation {
+    /// Decode an observation from its packed storage word.
+    #[inline]
+    pub fn from_word(word: U256) -> Self {
+        Self {
+            block_timestamp: word.wrapping_to(),
+            tick_cumulative: I56::from_raw(word.wrapping_shr(32).wrapping_to::<U56>()),
+            seconds_per_liquidity_cumulative_x128: word.wrapping_shr(88).wrapping_to(),
+            initialized: word.bit(248),
+        }
+    }
+
+    /// Transform a previous observation into a new observation, given the passage of time and the
+    /// current tick and liquidity values.
+    ///
+    /// ## Arguments
+    ///
+    /// * `block_timestamp`: The timestamp of the new observation
+    /// * `tick`: The active tick at the time of the new observation
+    /// * `liquidity`: The total in-range liquidity at the time of the new observation
+    ///
+    /// ## Returns
+    ///
+    /// The new observation
+    #[inline]
+    pub fn transform(&self, block_timestamp: u32, tick: I24, liquidity: u128) -> Self {
+        let delta = block_timestamp.wrapping_sub(self.block_timestamp);
+        Self {
+            block_timestamp,
+            tick_cumulative: self
+                .tick_cumulative
+                .wrapping_add(I56::from(tick).wrapping_mul(I56::from_raw(U56::from(delta)))),
+            seconds_per_liquidity_cumulative_x128: self
+                .seconds_per_liquidity_cumulative_x128
+                .wrapping_add((U160::from(delta) << 128) / U160::from(liquidity.max(1))),
+            initialized: true,
+        }
+    }
+}
+
+/// The oracle state of a pool, i.e. the `observations` array and the relevant fields of `slot0`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Oracle {
+    /// The populated part of the `observations` array
+    pub observations: Vec<Observation>,
+    /// The index of the most recently written observation
+    pub index: u16,
+    /// The current tick of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick: I24,
+    /// The current in-range liquidity of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+}
+
+/// Comparator for 32-bit timestamps, safe for 0 or 1 overflows.
+///
+/// `a` and `b` must be chronologically before or equal to `time`.
+const fn lte(time: u32, a: u32, b: u32) -> bool {
+    // if there hasn't been overflow, no need to adjust
+    if a <= time && b <= time {
+        return a <= b;
+    }
+    let a_adjusted = if a > time {
+        a as u64
+    } else {
+        a as u64 + (1 << 32)
+    };
+    let b_adjusted = if b > time {
+        b as u64
+    } else {
+        b as u64 + (1 << 32)
+    };
+    a_adjusted <= b_adjusted
+}
+
+impl Oracle {
+    /// Decode the oracle state from the static storage slots of a pool.
+    ///
+    /// ## Arguments
+    ///
+    /// * `slots`: The slots returned by [`get_static_slots`](crate::pool_lens::get_static_slots)
+    ///
+    /// ## Returns
+    ///
+    /// The oracle state of the pool
+    #[inline]
+    pub fn from_static_slots(slots: &[Slot]) -> Result<Self, Error> {
+        if slots.len() < OBSERVATIONS_OFFSET {
+            return Err(Error::InvalidSlots);
+        }
+        let slot0 = slots[0].data;
+        let cardinality = slot0.wrapping_shr(200).wrapping_to::<u16>() as usize;
+        if slots.len() != OBSERVATIONS_OFFSET + cardinality {
+            return Err(Error::InvalidSlots);
+        }
+        Ok(Self {
+            observations: slots[OBSERVATIONS_OFFSET..]
+                .iter()
+                .map(|slot| Observation::from_word(slot.data))
+                .collect(),
+            index: slot0.wrapping_shr(184).wrapping_to(),
+            tick: I24::from_raw(slot0.wrapping_shr(160).wrapping_to::<U24>()),
+            liquidity: slots[4].data.wrapping_to(),
+        })
+    }
+
+    /// The number of populated elements in the oracle array.
+    #[inline]
+    pub const fn cardinality(&self) -> u16 {
+        self.observations.len() as u16
+    }
+
+    /// Fetches the observations `before_or_at` and `at_or_after` a target, i.e. where
+    /// `[before_or_at, at_or_after]` is satisfied. The result may be the same observation, or
+    /// adjacent observations.
+    fn binary_search(&self, time: u32, target: u32) -> (Observation, Observation) {
+        let cardinality = self.observations.len();
+        // oldest observation
+        let mut l = (self.index as usize + 1) % cardinality;
+        // newest observation
+        let mut r = l + cardinality - 1;
+        loop {
+            let i = (l + r) / 2;
+            let before_or_at = self.observations[i % cardinality];
+            // we've landed on an uninitialized tick, keep searching higher (more recently)
+            if !before_or_at.initialized {
+                l = i + 1;
+                continue;
+            }
+            let at_or_after = self.observations[(i + 1) % cardinality];
+            let target_at_or_after = lte(time, before_or_at.block_timestamp, target);
+            // check if we've found the answer!
+            if target_at_or_after && lte(time, target, at_or_after.block_timestamp) {
+                return (before_or_at, at_or_after);
+            }
+            if !target_at_or_after {
+                r = i - 1;
+            } else {
+                l = i + 1;
+            }
+        }
+    }
+
+    /// Fetches the observations `before_or_at` and `at_or_after` a given target, i.e. where
+    /// `[before_or_at, at_or_after]` is satisfied.
+    fn get_surrounding_observations(
+        &self,
+        time: u32,
+        target: u32,
+    ) -> Result<(Observation, Observation), Error> {
+        // optimistically set before to the newest observation
+        let before_or_at = self.observations[self.index as usize];
+        // if the target is chronologically at or after the newest observation, we can early return
+        if lte(time, before_or_at.block_timestamp, target) {
+            return if before_or_at.block_timestamp == target {
+                // if newest observation equals target, we're in the same block, so we can ignore
+                // atOrAfter
+                Ok((before_or_at, Observation::default()))
+            } else {
+                // otherwise, we need to transform
+                Ok((
+                    before_or_at,
+                    before_or_at.transform(target, self.tick, self.liquidity),
+                ))
+            };
+        }
+        // now, set before to the oldest observation
+        let cardinality = self.observations.len();
+        let mut before_or_at = self.observations[(self.index as usize + 1) % cardinality];
+        if !before_or_at.initialized {
+            before_or_at = self.observations[0];
+        }
+        // ensure that the target is chronologically at or after the oldest observation
+        if !lte(time, before_or_at.block_timestamp, target) {
+            return Err(Error::ObservationTooOld);
+        }
+        // if we've reached this point, we have to binary search
+        Ok(self.binary_search(time, target))
+    }
+
+    /// Returns the accumulator values as of `seconds_ago` from the given time.
+    ///
+    /// ## Arguments
+    ///
+    /// * `time`: The current block timestamp
+    /// * `seconds_ago`: The amount of time to look back, in seconds, at which point to return an
+    ///   observation
+    ///
+    /// ## Returns
+    ///
+    /// The tick * time elapsed since the pool was first initialized, as of `seconds_ago`, and the
+    /// time elapsed / max(1, liquidity) since the pool was first initialized, as of `seconds_ago`
+    #[inline]
+    pub fn observe_single(&self, time: u32, seconds_ago: u32) -> Result<(I56, U160), Error> {
+        if self.observations.is_empty() || self.index as usize >= self.observations.len() {
+            return Err(Error::OracleNotInitialized);
+        }
+        if seconds_ago == 0 {
+            let mut last = self.observations[self.index as usize];
+            if last.block_timestamp != time {
+                last = last.transform(time, self.tick, self.liquidity);
+            }
+            return Ok((
+                last.tick_cumulative,
+                last.seconds_per_liquidity_cumulative_x128,
+            ));
+        }
+
+        let target = time.wrapping_sub(seconds_ago);
+        let (before_or_at, at_or_after) = self.get_surrounding_observations(time, target)?;
+
+        if target == before_or_at.block_timestamp {
+            // we're at the left boundary
+            Ok((
+                before_or_at.tick_cumulative,
+                before_or_at.seconds_per_liquidity_cumulative_x128,
+            ))
+        } else if target == at_or_after.block_timestamp {
+            // we're at the right boundary
+            Ok((
+                at_or_after.tick_cumulative,
+                at_or_after.seconds_per_liquidity_cumulative_x128,
+            ))
+        } else {
+            // we're in the middle
+            let observation_time_delta = at_or_after
+                .block_timestamp
+                .wrapping_sub(before_or_at.block_timestamp);
+            let target_delta = target.wrapping_sub(before_or_at.block_timestamp);
+            let tick_cumulative = before_or_at.tick_cumulative.wrapping_add(
+                (at_or_after
+                    .tick_cumulative
+                    .wrapping_sub(before_or_at.tick_cumulative)
+                    / I56::from_raw(U56::from(observation_time_delta)))
+                .wrapping_mul(I56::from_raw(U56::from(target_delta))),
+            );
+            let seconds_per_liquidity_delta = U256::from(
+                at_or_after
+                    .seconds_per_liquidity_cumulative_x128
+                    .wrapping_sub(before_or_at.seconds_per_liquidity_cumulative_x128),
+            );
+            let seconds_per_liquidity_cumulative_x128 = before_or_at
+                .seconds_per_liquidity_cumulative_x128
+                .wrapping_add(
+                    (seconds_per_liquidity_delta * U256::from(target_delta)
+                        / U256::from(observation_time_delta))
+                    .wrapping_to(),
+                );
+            Ok((tick_cumulative, seconds_per_liquidity_cumulative_x128))
+        }
+    }
+
+    /// Returns the accumulator values as of each time seconds ago from the given time in the array
+    /// of `seconds_agos`, equivalent to `IUniswapV3Pool.observe` at the given time.
+    ///
+    /// ## Arguments
+    ///
+    /// * `time`: The current block timestamp
+    /// * `seconds_agos`: Each amount of time to look back, in seconds, at which point to return an
+    ///   observation
+    ///
+    /// ## Returns
+    ///
+    /// The tick cumulatives and the seconds per liquidity cumulatives as of each `seconds_ago`
+    #[inline]
+    pub fn observe(&self, time: u32, seconds_agos: &[u32]) -> Result<(Vec<I56>, Vec<U160>), Error> {
+        let mut tick_cumulatives = Vec::with_capacity(seconds_agos.len());
+        let mut seconds_per_liquidity_cumulative_x128s = Vec::with_capacity(seconds_agos.len());
+        for &seconds_ago in seconds_agos {
+            let (tick_cumulative, seconds_per_liquidity_cumulative_x128) =
+                self.observe_single(time, seconds_ago)?;
+            tick_cumulatives.push(tick_cumulative);
+            seconds_per_liquidity_cumulative_x128s.push(seconds_per_liquidity_cumulative_x128);
+        }
+        Ok((tick_cumulatives, seconds_per_liquidity_cumulative_x128s))
+    }
+
+    /// Calculates time-weighted means of tick and liquidity over the last `seconds_ago` seconds,
+    /// equivalent to `OracleLibrary.consult` at the given time.
+    ///
+    /// ## Arguments
+    ///
+    /// * `time`: The current block timestamp
+    /// * `seconds_ago`: Number of seconds in the past from which to calculate the time-weighted
+    ///   means
+    ///
+    /// ## Returns
+    ///
+    /// The arithmetic mean tick and the harmonic mean liquidity from `time - seconds_ago` to `time`
+    #[inline]
+    pub fn consult(&self, time: u32, seconds_ago: u32) -> Result<(I24, u128), Error> {
+        if seconds_ago == 0 {
+            return Err(Error::InvalidPeriod);
+        }
+        let (tick_cumulative_start, seconds_per_liquidity_start) =
+            self.observe_single(time, seconds_ago)?;
+        let (tick_cumulative_end, seconds_per_liquidity_end) = self.observe_single(time, 0)?;
+
+        let tick_cumulatives_delta = tick_cumulative_end
+            .wrapping_sub(tick_cumulative_start)
+            .as_i64();
+        let seconds_per_liquidity_cumulatives_delta =
+            seconds_per_liquidity_end.wrapping_sub(seconds_per_liquidity_start);
+
+        let period = seconds_ago as i64;
+        let mut arithmetic_mean_tick = tick_cumulatives_delta / period;
+        // always round to negative infinity
+        if tick_cumulatives_delta < 0 && tick_cumulatives_delta % period != 0 {
+            arithmetic_mean_tick -= 1;
+        }
+        let arithmetic_mean_tick = I24::from_raw(U24::from(arithmetic_mean_tick as u32 & 0xffffff));
+
+        // We are multiplying here instead of shifting to ensure that harmonicMeanLiquidity doesn't
+        // overflow uint128
+        let seconds_ago_x160 = U256::from(seconds_ago) * U256::from(U160::MAX);
+        let harmonic_mean_liquidity = seconds_ago_x160
+            .checked_div(U256::from(seconds_per_liquidity_cumulatives_delta) << 32)
+            .ok_or(Error::InvalidPeriod)?
+            .saturating_to();
+        Ok((arithmetic_mean_tick, harmonic_mean_liquidity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bindings::iuniswapv3pool::IUniswapV3Pool, pool_lens::get_static_slots, tests::*};
+    use alloy::{
+        primitives::{address, Address},
+        providers::Provider,
+    };
+
+    const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+
+    fn encode_observation(observation: &Observation) -> U256 {
+        U256::from(observation.block_timestamp)
+            | (U256::from(observation.tick_cumulative.into_raw()) << 32)
+            | (U256::from(observation.seconds_per_liquidity_cumulative_x128) << 88)
+            | (U256::from(observation.initialized as u8) << 248)
+    }
+
+    /// An oracle with three observations written 12 seconds apart at a constant tick of -100 and
+    /// liquidity of 1e18, where the most recent observation is at index 0.
+    fn mock_oracle() -> Oracle {
+        let tick = I24::try_from(-100).unwrap();
+        let liquidity = 10_u128.pow(18);
+        let first = Observation {
+            block_timestamp: 1000,
+            initialized: true,
+            ..Default::default()
+        };
+        let second = first.transform(1012, tick, liquidity);
+        let third = second.transform(1024, tick, liquidity);
+        Oracle {
+            observations: vec![third, first, second],
+            index: 0,
+            tick,
+            liquidity,
+        }
+    }
+
+    #[test]
+    fn test_observation_from_word() {
+        let observation = Observation {
+            block_timestamp: 1_681_000_000,
+            tick_cumulative: I56::try_from(-12_345_678_901_i64).unwrap(),
+            seconds_per_liquidity_cumulative_x128: U160::from(123_456_789_u64) << 100,
+            initialized: true,
+        };
+        assert_eq!(
+            Observation::from_word(encode_observation(&observation)),
+            observation
+        );
+    }
+
+    #[test]
+    fn test_observe_single() {
+        let oracle = mock_oracle();
+        let [third, first, second] = oracle.observations[..] else {
+            unreachable!()
+        };
+        // exactly at an observation
+        assert_eq!(
+            oracle.observe_single(1024, 12).unwrap(),
+            (
+                second.tick_cumulative,
+                second.seconds_per_liquidity_cumulative_x128
+            )
+        );
+        // interpolated between two observations
+        let (tick_cumulative, _) = oracle.observe_single(1024, 18).unwrap();
+        assert_eq!(tick_cumulative, I56::try_from(-600).unwrap());
+        // extrapolated from the last observation
+        let (tick_cumulative, seconds_per_liquidity) = oracle.observe_single(1030, 0).unwrap();
+        assert_eq!(tick_cumulative, I56::try_from(-3000).unwrap());
+        assert_eq!(
+            seconds_per_liquidity,
+            third
+                .transform(1030, oracle.tick, oracle.liquidity)
+                .seconds_per_liquidity_cumulative_x128
+        );
+        // the oldest observation
+        assert_eq!(
+            oracle.observe_single(1024, 24).unwrap().0,
+            first.tick_cumulative
+        );
+        assert!(matches!(
+            oracle.observe_single(1024, 25),
+            Err(Error::ObservationTooOld)
+        ));
+    }
+
+    #[test]
+    fn test_consult() {
+        let oracle = mock_oracle();
+        let (tick, liquidity) = oracle.consult(1030, 30).unwrap();
+        assert_eq!(tick, oracle.tick);
+        // harmonic mean liquidity is subject to rounding
+        assert!(oracle.liquidity.abs_diff(liquidity) < 10);
+        assert!(matches!(oracle.consult(1030, 0), Err(Error::InvalidPeriod)));
+    }
+
+    #[tokio::test]
+    async fn test_observe() {
+        let provider = PROVIDER.clone();
+        let slots = get_static_slots(POOL_ADDRESS, provider.clone(), Some(BLOCK_NUMBER))
+            .await
+            .unwrap();
+        let oracle = Oracle::from_static_slots(&slots).unwrap();
+        let time = provider
+            .get_block(BLOCK_NUMBER)
+            .await
+            .unwrap()
+            .unwrap()
+            .header
+            .timestamp as u32;
+        let seconds_agos = vec![0, 60, 600, 1800, 3600];
+        let pool = IUniswapV3Pool::new(POOL_ADDRESS, provider);
+        let expected = pool
+            .observe(seconds_agos.clone())
+            .block(BLOCK_NUMBER)
+            .call()
+            .await
+            .unwrap();
+        let (tick_cumulatives, seconds_per_liquidity_cumulative_x128s) =
+            oracle.observe(time, &seconds_agos).unwrap();
+        assert_eq!(tick_cumulatives, expected.tickCumulatives);
+        assert_eq!(
+            seconds_per_liquidity_cumulative_x128s,
+            expected.secondsPerLiquidityCumulativeX128s
+        );
+    }
+}
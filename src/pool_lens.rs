@@ -18,18 +18,15 @@ use crate::{
         ephemeralpooltickbitmap::EphemeralPoolTickBitmap,
         ephemeralpoolticks::EphemeralPoolTicks,
     },
-    call_ephemeral_contract,
+    caller::{call_ephemeral, CallOptions},
     error::Error,
+    storage_slot::{mapping_slot, POSITIONS_SLOT},
 };
 use alloc::vec::Vec;
 use alloy::{
-    contract::Error as ContractError,
-    eips::BlockId,
     network::Network,
-    primitives::{aliases::I24, Address},
+    primitives::{aliases::I24, keccak256, Address, B256, U256},
     providers::Provider,
-    sol_types::SolCall,
-    transports::TransportError,
 };
 
 /// Get the populated ticks in a tick range.
@@ -40,7 +37,7 @@ use alloy::{
 /// * `tick_lower`: The lower tick boundary
 /// * `tick_upper`: The upper tick boundary
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -51,7 +48,7 @@ pub async fn get_populated_ticks_in_range<N, P>(
     tick_lower: I24,
     tick_upper: I24,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<(Vec<PopulatedTick>, I24), Error>
 where
     N: Network,
@@ -59,7 +56,7 @@ where
 {
     let deploy_builder =
         EphemeralGetPopulatedTicksInRange::deploy_builder(provider, pool, tick_lower, tick_upper);
-    match call_ephemeral_contract!(deploy_builder, getPopulatedTicksInRangeCall, block_id) {
+    match call_ephemeral::<getPopulatedTicksInRangeCall, _, _>(deploy_builder, options).await {
         Ok(getPopulatedTicksInRangeReturn {
             populatedTicks,
             tickSpacing,
@@ -76,8 +73,8 @@ where
 
 /// Call an ephemeral contract and return the decoded storage slots
 macro_rules! get_pool_storage {
-    ($deploy_builder:expr, $block_id:expr) => {
-        call_ephemeral_contract!($deploy_builder, getSlotsCall, $block_id)
+    ($deploy_builder:expr, $options:expr) => {
+        call_ephemeral::<getSlotsCall, _, _>($deploy_builder, $options).await
     };
 }
 
@@ -87,7 +84,7 @@ macro_rules! get_pool_storage {
 ///
 /// * `pool`: The address of a V3 pool
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -96,13 +93,13 @@ macro_rules! get_pool_storage {
 pub async fn get_static_slots<N, P>(
     pool: Address,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<Slot>, Error>
 where
     N: Network,
     P: Provider<N>,
 {
-    get_pool_storage!(EphemeralPoolSlots::deploy_builder(provider, pool), block_id)
+    get_pool_storage!(EphemeralPoolSlots::deploy_builder(provider, pool), options)
 }
 
 /// Get the storage slots in the `ticks` mapping between `tick_lower` and `tick_upper`.
@@ -113,7 +110,7 @@ where
 /// * `tick_lower`: The lower tick boundary
 /// * `tick_upper`: The upper tick boundary
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -124,7 +121,7 @@ pub async fn get_ticks_slots<N, P>(
     tick_lower: I24,
     tick_upper: I24,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<Slot>, Error>
 where
     N: Network,
@@ -132,7 +129,7 @@ where
 {
     get_pool_storage!(
         EphemeralPoolTicks::deploy_builder(provider, pool, tick_lower, tick_upper),
-        block_id
+        options
     )
 }
 
@@ -142,7 +139,7 @@ where
 ///
 /// * `pool`: The address of a V3 pool
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -151,7 +148,7 @@ where
 pub async fn get_tick_bitmap_slots<N, P>(
     pool: Address,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<Slot>, Error>
 where
     N: Network,
@@ -159,7 +156,7 @@ where
 {
     get_pool_storage!(
         EphemeralPoolTickBitmap::deploy_builder(provider, pool),
-        block_id
+        options
     )
 }
 
@@ -170,7 +167,7 @@ where
 /// * `pool`: The address of a V3 pool
 /// * `positions`: A vector of position keys
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -180,7 +177,7 @@ pub async fn get_positions_slots<N, P>(
     pool: Address,
     positions: Vec<PositionKey>,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<Slot>, Error>
 where
     N: Network,
@@ -188,10 +185,111 @@ where
 {
     get_pool_storage!(
         EphemeralPoolPositions::deploy_builder(provider, pool, positions),
-        block_id
+        options
     )
 }
 
+/// A position in the pool's `positions` mapping, as stored in `UniswapV3Pool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PositionInfo {
+    /// The amount of liquidity owned by this position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The fee growth of token0 per unit of liquidity as of the last update to liquidity or fees
+    /// owed
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside0_last_x128: U256,
+    /// The fee growth of token1 per unit of liquidity as of the last update to liquidity or fees
+    /// owed
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside1_last_x128: U256,
+    /// The fees owed to the position owner in token0
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed0: u128,
+    /// The fees owed to the position owner in token1
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed1: u128,
+}
+
+/// Compute the key of a position in the pool's `positions` mapping, equivalent to
+/// `PoolUtils.getPositionKey`.
+///
+/// ## Arguments
+///
+/// * `owner`: The address of the position owner
+/// * `tick_lower`: The lower tick boundary of the position
+/// * `tick_upper`: The upper tick boundary of the position
+///
+/// ## Returns
+///
+/// `keccak256(abi.encodePacked(owner, tickLower, tickUpper))`
+#[inline]
+pub fn get_position_key(owner: Address, tick_lower: I24, tick_upper: I24) -> B256 {
+    let mut packed = [0_u8; 26];
+    packed[..20].copy_from_slice(owner.as_slice());
+    packed[20..23].copy_from_slice(&tick_lower.into_raw().to_be_bytes::<3>());
+    packed[23..].copy_from_slice(&tick_upper.into_raw().to_be_bytes::<3>());
+    keccak256(packed)
+}
+
+/// Compute the storage slot of a position in the pool's `positions` mapping.
+///
+/// ## Arguments
+///
+/// * `position_key`: The position key returned by [`get_position_key`]
+///
+/// ## Returns
+///
+/// The first of the 4 storage slots occupied by the position
+#[inline]
+pub fn get_position_slot(position_key: B256) -> U256 {
+    mapping_slot(&position_key, U256::from(POSITIONS_SLOT))
+}
+
+/// Decode the storage slots returned by [`get_positions_slots`] into positions.
+///
+/// ## Arguments
+///
+/// * `positions`: The position keys passed to [`get_positions_slots`]
+/// * `slots`: The storage slots returned by [`get_positions_slots`]
+///
+/// ## Returns
+///
+/// A vector of position keys and the corresponding positions
+#[inline]
+pub fn decode_positions_slots(
+    positions: Vec<PositionKey>,
+    slots: &[Slot],
+) -> Result<Vec<(PositionKey, PositionInfo)>, Error> {
+    if slots.len() != positions.len() << 2 {
+        return Err(Error::InvalidSlots);
+    }
+    positions
+        .into_iter()
+        .zip(slots.chunks_exact(4))
+        .map(|(key, slots)| {
+            let slot = get_position_slot(get_position_key(key.owner, key.tickLower, key.tickUpper));
+            if slots
+                .iter()
+                .enumerate()
+                .any(|(i, s)| s.slot != slot + U256::from(i))
+            {
+                return Err(Error::InvalidSlots);
+            }
+            let info = PositionInfo {
+                liquidity: slots[0].data.wrapping_to(),
+                fee_growth_inside0_last_x128: slots[1].data,
+                fee_growth_inside1_last_x128: slots[2].data,
+                tokens_owed0: slots[3].data.wrapping_to(),
+                tokens_owed1: slots[3].data.wrapping_shr(128).wrapping_to(),
+            };
+            Ok((key, info))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,7 +298,10 @@ mod tests {
         tests::*,
     };
     use alloy::{
-        primitives::address, providers::MulticallBuilder, rpc::types::Filter, sol_types::SolEvent,
+        primitives::address,
+        providers::MulticallBuilder,
+        rpc::types::Filter,
+        sol_types::{SolEvent, SolValue},
     };
     use futures::future::join_all;
 
@@ -326,12 +427,89 @@ mod tests {
         assert!(!positions.is_empty());
         let slots = get_positions_slots(
             POOL_ADDRESS,
-            positions,
+            positions.clone(),
             provider.clone(),
             Some(BLOCK_NUMBER),
         )
         .await
         .unwrap();
-        verify_slots(slots, provider).await;
+        let decoded = decode_positions_slots(positions, &slots).unwrap();
+        verify_slots(slots, provider.clone()).await;
+
+        let pool = IUniswapV3Pool::new(POOL_ADDRESS, provider);
+        let (key, info) = &decoded[0];
+        let position = pool
+            .positions(get_position_key(key.owner, key.tickLower, key.tickUpper))
+            .block(BLOCK_NUMBER)
+            .call()
+            .await
+            .unwrap();
+        assert_eq!(info.liquidity, position._liquidity);
+        assert_eq!(
+            info.fee_growth_inside0_last_x128,
+            position.feeGrowthInside0LastX128
+        );
+        assert_eq!(
+            info.fee_growth_inside1_last_x128,
+            position.feeGrowthInside1LastX128
+        );
+        assert_eq!(info.tokens_owed0, position.tokensOwed0);
+        assert_eq!(info.tokens_owed1, position.tokensOwed1);
+    }
+
+    #[test]
+    fn test_decode_positions_slots() {
+        let key = PositionKey {
+            owner: address!("C36442b4a4522E871399CD717aBDD847Ab11FE88"),
+            tickLower: I24::try_from(-887220).unwrap(),
+            tickUpper: I24::try_from(887220).unwrap(),
+        };
+        // keccak256(abi.encodePacked(owner, tickLower, tickUpper))
+        let mut packed = key.owner.to_vec();
+        packed.extend_from_slice(&[0xf2, 0x76, 0x4c, 0x0d, 0x89, 0xb4]);
+        let position_key = get_position_key(key.owner, key.tickLower, key.tickUpper);
+        assert_eq!(position_key, keccak256(packed));
+        // keccak256(abi.encode(key, 7))
+        let slot = get_position_slot(position_key);
+        assert_eq!(
+            slot,
+            U256::from_be_bytes(keccak256((position_key, U256::from(7)).abi_encode()).0)
+        );
+
+        let slots: Vec<Slot> = [
+            U256::from(1000),
+            U256::from(2),
+            U256::from(3),
+            (U256::from(5) << 128) | U256::from(4),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, data)| Slot {
+            slot: slot + U256::from(i),
+            data,
+        })
+        .collect();
+        let decoded = decode_positions_slots(vec![key.clone()], &slots).unwrap();
+        assert_eq!(
+            decoded[0].1,
+            PositionInfo {
+                liquidity: 1000,
+                fee_growth_inside0_last_x128: U256::from(2),
+                fee_growth_inside1_last_x128: U256::from(3),
+                tokens_owed0: 4,
+                tokens_owed1: 5,
+            }
+        );
+        // the slots do not match the keys
+        assert!(matches!(
+            decode_positions_slots(vec![key.clone()], &slots[1..]),
+            Err(Error::InvalidSlots)
+        ));
+        let mut wrong_key = key;
+        wrong_key.tickLower = I24::ZERO;
+        assert!(matches!(
+            decode_positions_slots(vec![wrong_key], &slots),
+            Err(Error::InvalidSlots)
+        ));
     }
 }
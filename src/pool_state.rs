@@ -0,0 +1,1084 @@
+//! ## Pool State
+//!
+//! The pool state module decodes the storage slots returned by
+//! [`get_static_slots`](crate::pool_lens::get_static_slots),
+//! [`get_ticks_slots`](crate::pool_lens::get_ticks_slots) and
+//! [`get_tick_bitmap_slots`](crate::pool_lens::get_tick_bitmap_slots) into the state of a pool, and
+//! keeps it current by applying the `Swap`, `Mint`, `Burn`, `Flash`, `SetFeeProtocol` and other
+//! logs of the pool in order instead of fetching the slots again every block. The oracle
+//! observations are not tracked, and the tracked state should periodically be verified against a
+//! fresh snapshot from the lens.
+
+use crate::{
+    bindings::{
+        ephemeralpoolslots::PoolUtils::Slot, iuniswapv3pool::IUniswapV3Pool::IUniswapV3PoolEvents,
+    },
+    caller::CallOptions,
+    error::Error,
+    math::{
+        add_delta, get_amount0_delta, get_amount1_delta, get_sqrt_ratio_at_tick,
+        get_tick_at_sqrt_ratio, mul_div, mul_div_rounding_up, MAX_TICK, MIN_TICK, Q128,
+    },
+    registry::{get_logs_chunked, ScanConfig},
+    snapshot::PoolSnapshot,
+    storage_slot::{get_tick_bitmap_slot, get_tick_slot},
+};
+use alloc::collections::BTreeMap;
+use alloy::{
+    network::Network,
+    primitives::{
+        aliases::{I24, U160, U24},
+        Address, I256, U256,
+    },
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    sol_types::SolEventInterface,
+};
+
+/// The denominator of the swap fee, i.e. the fee is in hundredths of a bip.
+const FEE_DENOMINATOR: u32 = 1_000_000;
+
+/// The liquidity and fee growth of an initialized tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TickInfo {
+    /// The total position liquidity that references this tick
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity_gross: u128,
+    /// The amount of net liquidity added when the tick is crossed from left to right
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity_net: i128,
+    /// The fee growth of token0 per unit of liquidity on the other side of this tick
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_outside0_x128: U256,
+    /// The fee growth of token1 per unit of liquidity on the other side of this tick
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_outside1_x128: U256,
+}
+
+impl TickInfo {
+    /// Decode a tick from the first 3 storage words of its `Tick.Info`, i.e. the liquidity and the
+    /// fee growth outside.
+    #[inline]
+    pub fn from_words(words: &[U256; 3]) -> Self {
+        Self {
+            liquidity_gross: words[0].wrapping_to(),
+            liquidity_net: words[0].wrapping_shr(128).wrapping_to::<u128>() as i128,
+            fee_growth_outside0_x128: words[1],
+            fee_growth_outside1_x128: words[2],
+        }
+    }
+}
+
+/// The fields packed in the `slot0` of a V3 pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Slot0 {
+    /// The current sqrt price of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub sqrt_price_x96: U160,
+    /// The current tick of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick: I24,
+    /// The index of the last oracle observation that was written
+    pub observation_index: u16,
+    /// The current maximum number of observations stored in the pool
+    pub observation_cardinality: u16,
+    /// The next maximum number of observations, to be updated when the observation is written
+    pub observation_cardinality_next: u16,
+    /// The protocol fee for both tokens, token0 in the lower 4 bits and token1 in the upper 4 bits
+    pub fee_protocol: u8,
+    /// Whether the pool is currently unlocked
+    pub unlocked: bool,
+}
+
+impl Slot0 {
+    /// Decode `slot0` from its packed storage word.
+    #[inline]
+    pub fn from_word(word: U256) -> Self {
+        Self {
+            sqrt_price_x96: word.wrapping_to(),
+            tick: I24::from_raw(word.wrapping_shr(160).wrapping_to()),
+            observation_index: word.wrapping_shr(184).wrapping_to(),
+            observation_cardinality: word.wrapping_shr(200).wrapping_to(),
+            observation_cardinality_next: word.wrapping_shr(216).wrapping_to(),
+            fee_protocol: word.wrapping_shr(232).wrapping_to(),
+            unlocked: word.bit(240),
+        }
+    }
+}
+
+/// The state of a V3 pool excluding the oracle observations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PoolState {
+    /// The address of the pool
+    pub address: Address,
+    /// The fee collected upon every swap in the pool, denominated in hundredths of a bip
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::fee"))]
+    pub fee: U24,
+    /// The minimum number of ticks between initialized ticks
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_spacing: I24,
+    /// The current sqrt price of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub sqrt_price_x96: U160,
+    /// The current tick of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick: I24,
+    /// The index of the last oracle observation that was written
+    pub observation_index: u16,
+    /// The current maximum number of observations stored in the pool
+    pub observation_cardinality: u16,
+    /// The next maximum number of observations, to be updated when the observation is written
+    pub observation_cardinality_next: u16,
+    /// The protocol fee for both tokens, token0 in the lower 4 bits and token1 in the upper 4 bits
+    pub fee_protocol: u8,
+    /// Whether the pool is currently unlocked
+    pub unlocked: bool,
+    /// The all-time fee growth of token0 per unit of liquidity
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_global0_x128: U256,
+    /// The all-time fee growth of token1 per unit of liquidity
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_global1_x128: U256,
+    /// The uncollected protocol fees of token0
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub protocol_fees_token0: u128,
+    /// The uncollected protocol fees of token1
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub protocol_fees_token1: u128,
+    /// The current in-range liquidity of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The initialized ticks
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::ticks"))]
+    pub ticks: BTreeMap<I24, TickInfo>,
+    /// The non-empty words of the tick bitmap indexed by word position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick_bitmap"))]
+    pub tick_bitmap: BTreeMap<i16, U256>,
+    /// The block number of the state
+    pub block_number: u64,
+    /// The index of the last log applied in `block_number`, or `None` if the state is at the end
+    /// of the block
+    pub log_index: Option<u64>,
+}
+
+/// Get the word positions of the tick bitmap covering the full tick range.
+pub(crate) fn word_positions(tick_spacing: i32) -> (i32, i32) {
+    (
+        MIN_TICK.as_i32().div_euclid(tick_spacing) >> 8,
+        MAX_TICK.as_i32().div_euclid(tick_spacing) >> 8,
+    )
+}
+
+/// Convert an `i32` to a tick.
+fn to_tick(tick: i32) -> Result<I24, Error> {
+    I24::try_from(tick).map_err(|_| Error::InvalidTick)
+}
+
+impl PoolState {
+    /// Decode the state of a pool from its storage slots.
+    ///
+    /// ## Arguments
+    ///
+    /// * `address`: The address of the pool
+    /// * `fee`: The fee of the pool
+    /// * `tick_spacing`: The tick spacing of the pool
+    /// * `static_slots`: The storage slots returned by
+    ///   [`get_static_slots`](crate::pool_lens::get_static_slots)
+    /// * `ticks_slots`: The storage slots returned by
+    ///   [`get_ticks_slots`](crate::pool_lens::get_ticks_slots) over the full tick range
+    /// * `tick_bitmap_slots`: The storage slots returned by
+    ///   [`get_tick_bitmap_slots`](crate::pool_lens::get_tick_bitmap_slots)
+    ///
+    /// ## Returns
+    ///
+    /// The pool state, or [`Error::InvalidSlots`] if the slots do not match the pool layout
+    #[inline]
+    pub fn from_slots(
+        address: Address,
+        fee: U24,
+        tick_spacing: I24,
+        static_slots: &[Slot],
+        ticks_slots: &[Slot],
+        tick_bitmap_slots: &[Slot],
+    ) -> Result<Self, Error> {
+        if static_slots.len() < 5
+            || static_slots[..5]
+                .iter()
+                .enumerate()
+                .any(|(i, s)| s.slot != U256::from(i))
+        {
+            return Err(Error::InvalidSlots);
+        }
+        let slot0 = Slot0::from_word(static_slots[0].data);
+        let spacing = tick_spacing.as_i32();
+        if spacing <= 0 {
+            return Err(Error::InvalidTick);
+        }
+
+        let (word_pos_lower, word_pos_upper) = word_positions(spacing);
+        if tick_bitmap_slots.len() != (word_pos_upper - word_pos_lower + 1) as usize {
+            return Err(Error::InvalidSlots);
+        }
+        let mut tick_bitmap = BTreeMap::new();
+        for (word_pos, slot) in (word_pos_lower..=word_pos_upper).zip(tick_bitmap_slots) {
+            if slot.slot != get_tick_bitmap_slot(word_pos as i16) {
+                return Err(Error::InvalidSlots);
+            }
+            if !slot.data.is_zero() {
+                tick_bitmap.insert(word_pos as i16, slot.data);
+            }
+        }
+
+        // the populated ticks are returned in ascending order of the bitmap
+        let mut ticks = BTreeMap::new();
+        let mut chunks = ticks_slots.chunks_exact(4);
+        for (&word_pos, word) in &tick_bitmap {
+            for bit_pos in 0..256 {
+                if !word.bit(bit_pos) {
+                    continue;
+                }
+                let tick = to_tick((((word_pos as i32) << 8) | bit_pos as i32) * spacing)?;
+                let slots = chunks.next().ok_or(Error::InvalidSlots)?;
+                let slot = get_tick_slot(tick);
+                if slots
+                    .iter()
+                    .enumerate()
+                    .any(|(i, s)| s.slot != slot + U256::from(i))
+                {
+                    return Err(Error::InvalidSlots);
+                }
+                ticks.insert(
+                    tick,
+                    TickInfo::from_words(&[slots[0].data, slots[1].data, slots[2].data]),
+                );
+            }
+        }
+        if chunks.next().is_some() || !chunks.remainder().is_empty() {
+            return Err(Error::InvalidSlots);
+        }
+
+        Ok(Self {
+            address,
+            fee,
+            tick_spacing,
+            sqrt_price_x96: slot0.sqrt_price_x96,
+            tick: slot0.tick,
+            observation_index: slot0.observation_index,
+            observation_cardinality: slot0.observation_cardinality,
+            observation_cardinality_next: slot0.observation_cardinality_next,
+            fee_protocol: slot0.fee_protocol,
+            unlocked: slot0.unlocked,
+            fee_growth_global0_x128: static_slots[1].data,
+            fee_growth_global1_x128: static_slots[2].data,
+            protocol_fees_token0: static_slots[3].data.wrapping_to(),
+            protocol_fees_token1: static_slots[3].data.wrapping_shr(128).wrapping_to(),
+            liquidity: static_slots[4].data.wrapping_to(),
+            ticks,
+            tick_bitmap,
+            block_number: 0,
+            log_index: None,
+        })
+    }
+
+    /// Fetch a snapshot of the state of a pool at the end of a block.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The address of a V3 pool
+    /// * `block_number`: The block number to query
+    /// * `provider`: The alloy provider
+    /// * `options`: Optional [`CallOptions`] to query with, whose block is replaced by
+    ///   `block_number`
+    ///
+    /// ## Returns
+    ///
+    /// The pool state
+    #[inline]
+    pub async fn fetch<N, P>(
+        pool: Address,
+        block_number: u64,
+        provider: P,
+        options: impl Into<CallOptions>,
+    ) -> Result<Self, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        PoolSnapshot::fetch(pool, block_number, provider, options)
+            .await?
+            .to_state()
+    }
+
+    /// Whether a log is already reflected in the state.
+    const fn is_applied(&self, block_number: u64, log_index: u64) -> bool {
+        if block_number != self.block_number {
+            return block_number < self.block_number;
+        }
+        match self.log_index {
+            Some(index) => log_index <= index,
+            None => true,
+        }
+    }
+
+    /// Apply a log of the pool to the state.
+    ///
+    /// Logs emitted by other contracts, logs that are already reflected in the state and events
+    /// that do not change the tracked state are ignored. On error, the state is left partially
+    /// updated and should be fetched again.
+    ///
+    /// ## Arguments
+    ///
+    /// * `log`: A log emitted by the pool
+    ///
+    /// ## Returns
+    ///
+    /// Whether the log was applied, or [`Error::StateMismatch`] if the state does not agree with
+    /// the log
+    #[inline]
+    pub fn apply_log(&mut self, log: &Log) -> Result<bool, Error> {
+        if log.address() != self.address {
+            return Ok(false);
+        }
+        if let (Some(block_number), Some(log_index)) = (log.block_number, log.log_index) {
+            if self.is_applied(block_number, log_index) {
+                return Ok(false);
+            }
+        }
+        match log.topic0() {
+            Some(topic0) if IUniswapV3PoolEvents::SELECTORS.contains(topic0) => {}
+            _ => return Ok(false),
+        }
+        match IUniswapV3PoolEvents::decode_raw_log(log.topics(), &log.data().data)? {
+            IUniswapV3PoolEvents::Initialize(event) => {
+                self.sqrt_price_x96 = event.sqrtPriceX96;
+                self.tick = event.tick;
+                self.observation_cardinality = 1;
+                self.observation_cardinality_next = 1;
+                self.unlocked = true;
+            }
+            IUniswapV3PoolEvents::Mint(event) => {
+                let delta = i128::try_from(event.amount).map_err(|_| Error::Overflow)?;
+                self.modify_position(event.tickLower, event.tickUpper, delta)?;
+            }
+            IUniswapV3PoolEvents::Burn(event) => {
+                let delta = i128::try_from(event.amount).map_err(|_| Error::Overflow)?;
+                self.modify_position(event.tickLower, event.tickUpper, -delta)?;
+            }
+            IUniswapV3PoolEvents::Swap(event) => {
+                self.swap(
+                    event.amount0,
+                    event.amount1,
+                    event.sqrtPriceX96,
+                    event.liquidity,
+                    event.tick,
+                )?;
+            }
+            IUniswapV3PoolEvents::Flash(event) => self.flash(event.paid0, event.paid1)?,
+            IUniswapV3PoolEvents::SetFeeProtocol(event) => {
+                self.fee_protocol = event.feeProtocol0New + (event.feeProtocol1New << 4);
+            }
+            IUniswapV3PoolEvents::CollectProtocol(event) => {
+                self.protocol_fees_token0 = self.protocol_fees_token0.wrapping_sub(event.amount0);
+                self.protocol_fees_token1 = self.protocol_fees_token1.wrapping_sub(event.amount1);
+            }
+            IUniswapV3PoolEvents::IncreaseObservationCardinalityNext(event) => {
+                self.observation_cardinality_next = event.observationCardinalityNextNew;
+            }
+            // collecting tokens owed only changes the positions
+            IUniswapV3PoolEvents::Collect(_) => {}
+        }
+        if let Some(block_number) = log.block_number {
+            self.block_number = block_number;
+            self.log_index = log.log_index;
+        }
+        Ok(true)
+    }
+
+    /// Apply the logs of the pool to the state in order.
+    ///
+    /// ## Arguments
+    ///
+    /// * `logs`: The logs sorted by block number and log index
+    ///
+    /// ## Returns
+    ///
+    /// The number of logs applied
+    #[inline]
+    pub fn apply_logs(&mut self, logs: &[Log]) -> Result<usize, Error> {
+        let mut applied = 0;
+        for log in logs {
+            if self.apply_log(log)? {
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Apply the logs of the pool from the block after the state up to `to_block`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `to_block`: The last block to apply, inclusive
+    /// * `config`: The scan configuration
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The number of logs applied
+    #[inline]
+    pub async fn sync<N, P>(
+        &mut self,
+        to_block: u64,
+        config: &ScanConfig,
+        provider: P,
+    ) -> Result<usize, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        // the logs in the current block after `log_index` are applied as well
+        let from_block = match self.log_index {
+            Some(_) => self.block_number,
+            None => self.block_number + 1,
+        };
+        if from_block > to_block {
+            return Ok(0);
+        }
+        let filter = Filter::new().address(self.address);
+        let logs = get_logs_chunked(&filter, from_block, to_block, config, provider).await?;
+        let applied = self.apply_logs(&logs)?;
+        self.block_number = to_block;
+        self.log_index = None;
+        Ok(applied)
+    }
+
+    /// Verify the tracked state against another snapshot of the same pool.
+    ///
+    /// ## Arguments
+    ///
+    /// * `other`: A snapshot of the pool at the same block, e.g. from [`PoolState::fetch`]
+    ///
+    /// ## Returns
+    ///
+    /// [`Error::StateMismatch`] if any of the tracked fields differ
+    #[inline]
+    pub fn verify(&self, other: &Self) -> Result<(), Error> {
+        if self.address == other.address
+            && self.sqrt_price_x96 == other.sqrt_price_x96
+            && self.tick == other.tick
+            && self.fee_protocol == other.fee_protocol
+            && self.fee_growth_global0_x128 == other.fee_growth_global0_x128
+            && self.fee_growth_global1_x128 == other.fee_growth_global1_x128
+            && self.protocol_fees_token0 == other.protocol_fees_token0
+            && self.protocol_fees_token1 == other.protocol_fees_token1
+            && self.liquidity == other.liquidity
+            && self.ticks == other.ticks
+            && self.tick_bitmap == other.tick_bitmap
+        {
+            Ok(())
+        } else {
+            Err(Error::StateMismatch)
+        }
+    }
+
+    /// Fetch a fresh snapshot at the block of the state and verify the tracked state against it.
+    ///
+    /// ## Arguments
+    ///
+    /// * `provider`: The alloy provider
+    /// * `options`: Optional [`CallOptions`] to query with, whose block is replaced by the block of
+    ///   the state
+    ///
+    /// ## Returns
+    ///
+    /// [`Error::StateMismatch`] if the tracked state has diverged from the chain
+    #[inline]
+    pub async fn verify_with_lens<N, P>(
+        &self,
+        provider: P,
+        options: impl Into<CallOptions>,
+    ) -> Result<(), Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        if self.log_index.is_some() {
+            // the lens can only observe the state at the end of a block
+            return Err(Error::StateMismatch);
+        }
+        self.verify(&Self::fetch(self.address, self.block_number, provider, options).await?)
+    }
+
+    /// Flip the initialized state of a tick in the bitmap.
+    fn flip_tick(&mut self, tick: I24) {
+        let compressed = tick.as_i32() / self.tick_spacing.as_i32();
+        let word_pos = (compressed >> 8) as i16;
+        let mask = U256::ONE << (compressed & 0xff) as usize;
+        let word = self.tick_bitmap.entry(word_pos).or_default();
+        *word ^= mask;
+        if word.is_zero() {
+            self.tick_bitmap.remove(&word_pos);
+        }
+    }
+
+    /// Update a tick when liquidity is added or removed, returning whether the tick was flipped.
+    fn update_tick(&mut self, tick: I24, delta: i128, upper: bool) -> Result<bool, Error> {
+        let below_current = tick <= self.tick;
+        let (fee_growth0, fee_growth1) =
+            (self.fee_growth_global0_x128, self.fee_growth_global1_x128);
+        let info = self.ticks.entry(tick).or_default();
+        let liquidity_gross_before = info.liquidity_gross;
+        let liquidity_gross_after = add_delta(liquidity_gross_before, delta)?;
+        if liquidity_gross_before == 0 && below_current {
+            // by convention, all growth before a tick was initialized happened below the tick
+            info.fee_growth_outside0_x128 = fee_growth0;
+            info.fee_growth_outside1_x128 = fee_growth1;
+        }
+        info.liquidity_gross = liquidity_gross_after;
+        info.liquidity_net = if upper {
+            info.liquidity_net.checked_sub(delta)
+        } else {
+            info.liquidity_net.checked_add(delta)
+        }
+        .ok_or(Error::Overflow)?;
+        Ok((liquidity_gross_after == 0) != (liquidity_gross_before == 0))
+    }
+
+    /// Add or remove liquidity of a position, mirroring `UniswapV3Pool._modifyPosition`.
+    fn modify_position(
+        &mut self,
+        tick_lower: I24,
+        tick_upper: I24,
+        delta: i128,
+    ) -> Result<(), Error> {
+        if delta == 0 {
+            return Ok(());
+        }
+        for (tick, upper) in [(tick_lower, false), (tick_upper, true)] {
+            if self.update_tick(tick, delta, upper)? {
+                self.flip_tick(tick);
+                if delta < 0 {
+                    self.ticks.remove(&tick);
+                }
+            }
+        }
+        if tick_lower <= self.tick && self.tick < tick_upper {
+            self.liquidity = add_delta(self.liquidity, delta)?;
+        }
+        Ok(())
+    }
+
+    /// Get the next initialized tick within the word of the current tick, mirroring
+    /// `TickBitmap.nextInitializedTickWithinOneWord`.
+    fn next_initialized_tick_within_one_word(&self, tick: i32, lte: bool) -> (i32, bool) {
+        let spacing = self.tick_spacing.as_i32();
+        let compressed = tick.div_euclid(spacing);
+        if lte {
+            let bit_pos = (compressed & 0xff) as usize;
+            let word = self.tick_bitmap.get(&((compressed >> 8) as i16));
+            // all the 1s at or to the right of the current bit position
+            let mask = (U256::ONE << bit_pos) - U256::ONE + (U256::ONE << bit_pos);
+            match word
+                .map(|word| word & mask)
+                .filter(|masked| !masked.is_zero())
+            {
+                Some(masked) => {
+                    let msb = masked.bit_len() - 1;
+                    ((compressed - (bit_pos - msb) as i32) * spacing, true)
+                }
+                None => ((compressed - bit_pos as i32) * spacing, false),
+            }
+        } else {
+            let compressed = compressed + 1;
+            let bit_pos = (compressed & 0xff) as usize;
+            let word = self.tick_bitmap.get(&((compressed >> 8) as i16));
+            // all the 1s at or to the left of the bit position
+            let mask = !((U256::ONE << bit_pos) - U256::ONE);
+            match word
+                .map(|word| word & mask)
+                .filter(|masked| !masked.is_zero())
+            {
+                Some(masked) => {
+                    let lsb = masked.trailing_zeros();
+                    ((compressed + (lsb - bit_pos) as i32) * spacing, true)
+                }
+                None => ((compressed + (255 - bit_pos) as i32) * spacing, false),
+            }
+        }
+    }
+
+    /// Accrue the fee of a swap step to the protocol and the liquidity providers.
+    fn accrue_fee(
+        fee_amount: U256,
+        fee_protocol: u8,
+        liquidity: u128,
+        fee_growth_global: &mut U256,
+        protocol_fee: &mut u128,
+    ) -> Result<(), Error> {
+        let mut fee_amount = fee_amount;
+        if fee_protocol > 0 {
+            let delta = fee_amount / U256::from(fee_protocol);
+            fee_amount -= delta;
+            *protocol_fee = protocol_fee.wrapping_add(delta.wrapping_to());
+        }
+        if liquidity > 0 {
+            *fee_growth_global =
+                fee_growth_global.wrapping_add(mul_div(fee_amount, Q128, U256::from(liquidity))?);
+        }
+        Ok(())
+    }
+
+    /// Replay a swap from the amounts and the final state in a `Swap` event, mirroring the steps of
+    /// `UniswapV3Pool.swap` to accrue the fees and cross the initialized ticks.
+    fn swap(
+        &mut self,
+        amount0: I256,
+        amount1: I256,
+        sqrt_price_x96: U160,
+        liquidity: u128,
+        tick: I24,
+    ) -> Result<(), Error> {
+        let zero_for_one = if amount0.is_positive() {
+            true
+        } else if amount1.is_positive() {
+            false
+        } else {
+            sqrt_price_x96 < self.sqrt_price_x96
+        };
+        // the amount paid into the pool including the fee
+        let amount_in = if zero_for_one { amount0 } else { amount1 };
+        let amount_in = if amount_in.is_positive() {
+            amount_in.into_raw()
+        } else {
+            U256::ZERO
+        };
+        let fee_protocol = if zero_for_one {
+            self.fee_protocol % 16
+        } else {
+            self.fee_protocol >> 4
+        };
+        let fee = U256::from(self.fee);
+        let fee_complement = U256::from(FEE_DENOMINATOR) - fee;
+        let mut fee_growth_global = if zero_for_one {
+            self.fee_growth_global0_x128
+        } else {
+            self.fee_growth_global1_x128
+        };
+        let mut protocol_fee = 0_u128;
+        let mut state_liquidity = self.liquidity;
+        let mut state_price = self.sqrt_price_x96;
+        let mut state_tick = self.tick.as_i32();
+        let mut consumed = U256::ZERO;
+        loop {
+            if state_price == sqrt_price_x96 {
+                // the remaining input that did not move the price is taken as fee
+                if consumed < amount_in {
+                    Self::accrue_fee(
+                        amount_in - consumed,
+                        fee_protocol,
+                        state_liquidity,
+                        &mut fee_growth_global,
+                        &mut protocol_fee,
+                    )?;
+                }
+                break;
+            }
+            let (tick_next, initialized) =
+                self.next_initialized_tick_within_one_word(state_tick, zero_for_one);
+            let tick_next = tick_next.clamp(MIN_TICK.as_i32(), MAX_TICK.as_i32());
+            let price_next = get_sqrt_ratio_at_tick(to_tick(tick_next)?)?;
+            let price_target = if zero_for_one {
+                price_next.max(sqrt_price_x96)
+            } else {
+                price_next.min(sqrt_price_x96)
+            };
+            let step_in = if zero_for_one {
+                get_amount0_delta(price_target, state_price, state_liquidity, true)?
+            } else {
+                get_amount1_delta(state_price, price_target, state_liquidity, true)?
+            };
+            let fee_amount = if price_target == price_next {
+                mul_div_rounding_up(step_in, fee, fee_complement)?
+            } else {
+                // the step ending at the final price takes the rest of the input as fee
+                amount_in
+                    .checked_sub(consumed)
+                    .and_then(|remaining| remaining.checked_sub(step_in))
+                    .ok_or(Error::StateMismatch)?
+            };
+            consumed += step_in + fee_amount;
+            Self::accrue_fee(
+                fee_amount,
+                fee_protocol,
+                state_liquidity,
+                &mut fee_growth_global,
+                &mut protocol_fee,
+            )?;
+            state_price = price_target;
+            if state_price == price_next {
+                if initialized {
+                    let (fee_growth0, fee_growth1) = if zero_for_one {
+                        (fee_growth_global, self.fee_growth_global1_x128)
+                    } else {
+                        (self.fee_growth_global0_x128, fee_growth_global)
+                    };
+                    let info = self
+                        .ticks
+                        .get_mut(&to_tick(tick_next)?)
+                        .ok_or(Error::StateMismatch)?;
+                    info.fee_growth_outside0_x128 =
+                        fee_growth0.wrapping_sub(info.fee_growth_outside0_x128);
+                    info.fee_growth_outside1_x128 =
+                        fee_growth1.wrapping_sub(info.fee_growth_outside1_x128);
+                    let liquidity_net = if zero_for_one {
+                        -info.liquidity_net
+                    } else {
+                        info.liquidity_net
+                    };
+                    state_liquidity = add_delta(state_liquidity, liquidity_net)?;
+                }
+                state_tick = if zero_for_one {
+                    tick_next - 1
+                } else {
+                    tick_next
+                };
+            } else {
+                state_tick = get_tick_at_sqrt_ratio(state_price)?.as_i32();
+            }
+        }
+        if state_liquidity != liquidity || state_tick != tick.as_i32() {
+            return Err(Error::StateMismatch);
+        }
+        if zero_for_one {
+            self.fee_growth_global0_x128 = fee_growth_global;
+            self.protocol_fees_token0 = self.protocol_fees_token0.wrapping_add(protocol_fee);
+        } else {
+            self.fee_growth_global1_x128 = fee_growth_global;
+            self.protocol_fees_token1 = self.protocol_fees_token1.wrapping_add(protocol_fee);
+        }
+        self.sqrt_price_x96 = sqrt_price_x96;
+        self.tick = tick;
+        self.liquidity = liquidity;
+        Ok(())
+    }
+
+    /// Accrue the fees paid in a flash loan, mirroring `UniswapV3Pool.flash`.
+    fn flash(&mut self, paid0: U256, paid1: U256) -> Result<(), Error> {
+        if !paid0.is_zero() {
+            Self::accrue_fee(
+                paid0,
+                self.fee_protocol % 16,
+                self.liquidity,
+                &mut self.fee_growth_global0_x128,
+                &mut self.protocol_fees_token0,
+            )?;
+        }
+        if !paid1.is_zero() {
+            Self::accrue_fee(
+                paid1,
+                self.fee_protocol >> 4,
+                self.liquidity,
+                &mut self.fee_growth_global1_x128,
+                &mut self.protocol_fees_token1,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::iuniswapv3pool::IUniswapV3Pool::{Burn, Flash, Mint, SetFeeProtocol, Swap},
+        tests::*,
+    };
+    use alloc::vec::Vec;
+    use alloy::{
+        primitives::{address, LogData},
+        sol_types::SolEvent,
+    };
+
+    const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+    const LIQUIDITY: u128 = 1_000_000_000_000_000_000;
+
+    fn tick(tick: i32) -> I24 {
+        I24::try_from(tick).unwrap()
+    }
+
+    fn price(t: i32) -> U160 {
+        get_sqrt_ratio_at_tick(tick(t)).unwrap()
+    }
+
+    fn log(data: LogData, log_index: u64) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address: POOL_ADDRESS,
+                data,
+            },
+            block_number: Some(1),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    fn mint(amount: u128) -> LogData {
+        Mint {
+            sender: Address::ZERO,
+            owner: Address::ZERO,
+            tickLower: tick(-120),
+            tickUpper: tick(120),
+            amount,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        }
+        .encode_log_data()
+    }
+
+    fn swap(amount0: I256, amount1: I256, t: i32, liquidity: u128) -> LogData {
+        Swap {
+            sender: Address::ZERO,
+            recipient: Address::ZERO,
+            amount0,
+            amount1,
+            sqrtPriceX96: price(t),
+            liquidity,
+            tick: tick(t),
+        }
+        .encode_log_data()
+    }
+
+    /// A 0.3% pool at tick 0 with a position of [`LIQUIDITY`] in [-120, 120).
+    fn mock_state() -> PoolState {
+        let mut state = PoolState {
+            address: POOL_ADDRESS,
+            fee: U24::from(3000),
+            tick_spacing: tick(60),
+            sqrt_price_x96: price(0),
+            fee_growth_global0_x128: U256::from(5),
+            ..Default::default()
+        };
+        assert!(state.apply_log(&log(mint(LIQUIDITY), 0)).unwrap());
+        state
+    }
+
+    /// Encode a state into the slots returned by the lens.
+    fn to_slots(state: &PoolState) -> (Vec<Slot>, Vec<Slot>, Vec<Slot>) {
+        let slot = |slot: U256, data: U256| Slot { slot, data };
+        let slot0 = U256::from(state.sqrt_price_x96)
+            | (U256::from(state.tick.into_raw()) << 160)
+            | (U256::from(state.observation_cardinality) << 200)
+            | (U256::from(state.fee_protocol) << 232);
+        let static_slots = vec![
+            slot(U256::ZERO, slot0),
+            slot(U256::from(1), state.fee_growth_global0_x128),
+            slot(U256::from(2), state.fee_growth_global1_x128),
+            slot(
+                U256::from(3),
+                U256::from(state.protocol_fees_token0)
+                    | (U256::from(state.protocol_fees_token1) << 128),
+            ),
+            slot(U256::from(4), U256::from(state.liquidity)),
+        ];
+        let mut ticks_slots = Vec::new();
+        for (t, info) in &state.ticks {
+            let s = get_tick_slot(*t);
+            ticks_slots.push(slot(
+                s,
+                U256::from(info.liquidity_gross) | (U256::from(info.liquidity_net as u128) << 128),
+            ));
+            ticks_slots.push(slot(s + U256::from(1), info.fee_growth_outside0_x128));
+            ticks_slots.push(slot(s + U256::from(2), info.fee_growth_outside1_x128));
+            ticks_slots.push(slot(s + U256::from(3), U256::ZERO));
+        }
+        let (lower, upper) = word_positions(state.tick_spacing.as_i32());
+        let tick_bitmap_slots = (lower..=upper)
+            .map(|word_pos| {
+                slot(
+                    get_tick_bitmap_slot(word_pos as i16),
+                    state
+                        .tick_bitmap
+                        .get(&(word_pos as i16))
+                        .copied()
+                        .unwrap_or_default(),
+                )
+            })
+            .collect();
+        (static_slots, ticks_slots, tick_bitmap_slots)
+    }
+
+    #[test]
+    fn test_from_slots() {
+        let state = mock_state();
+        let (static_slots, ticks_slots, tick_bitmap_slots) = to_slots(&state);
+        let decoded = PoolState::from_slots(
+            state.address,
+            state.fee,
+            state.tick_spacing,
+            &static_slots,
+            &ticks_slots,
+            &tick_bitmap_slots,
+        )
+        .unwrap();
+        decoded.verify(&state).unwrap();
+        // a missing tick
+        assert!(matches!(
+            PoolState::from_slots(
+                state.address,
+                state.fee,
+                state.tick_spacing,
+                &static_slots,
+                &ticks_slots[4..],
+                &tick_bitmap_slots,
+            ),
+            Err(Error::InvalidSlots)
+        ));
+    }
+
+    #[test]
+    fn test_mint_burn_flash() {
+        let mut state = mock_state();
+        assert_eq!(state.liquidity, LIQUIDITY);
+        assert_eq!(state.ticks.len(), 2);
+        // the lower tick is initialized at or below the current tick
+        let lower = state.ticks[&tick(-120)];
+        assert_eq!(lower.liquidity_net, LIQUIDITY as i128);
+        assert_eq!(lower.fee_growth_outside0_x128, U256::from(5));
+        let upper = state.ticks[&tick(120)];
+        assert_eq!(upper.liquidity_net, -(LIQUIDITY as i128));
+        assert_eq!(upper.fee_growth_outside0_x128, U256::ZERO);
+        assert_eq!(state.tick_bitmap[&-1], U256::ONE << 254);
+        assert_eq!(state.tick_bitmap[&0], U256::from(4));
+        // the same log is not applied twice
+        assert!(!state.apply_log(&log(mint(LIQUIDITY), 0)).unwrap());
+
+        let set_fee_protocol = SetFeeProtocol {
+            feeProtocol0Old: 0,
+            feeProtocol1Old: 0,
+            feeProtocol0New: 4,
+            feeProtocol1New: 5,
+        };
+        state
+            .apply_log(&log(set_fee_protocol.encode_log_data(), 1))
+            .unwrap();
+        assert_eq!(state.fee_protocol, 4 + (5 << 4));
+
+        let flash = Flash {
+            sender: Address::ZERO,
+            recipient: Address::ZERO,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+            paid0: U256::from(1000),
+            paid1: U256::ZERO,
+        };
+        state.apply_log(&log(flash.encode_log_data(), 2)).unwrap();
+        assert_eq!(state.protocol_fees_token0, 250);
+        assert_eq!(
+            state.fee_growth_global0_x128,
+            U256::from(5) + mul_div(U256::from(750), Q128, U256::from(LIQUIDITY)).unwrap()
+        );
+
+        let burn = Burn {
+            owner: Address::ZERO,
+            tickLower: tick(-120),
+            tickUpper: tick(120),
+            amount: LIQUIDITY,
+            amount0: U256::ZERO,
+            amount1: U256::ZERO,
+        };
+        state.apply_log(&log(burn.encode_log_data(), 3)).unwrap();
+        assert_eq!(state.liquidity, 0);
+        assert!(state.ticks.is_empty());
+        assert!(state.tick_bitmap.is_empty());
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut state = mock_state();
+        let fee = U256::from(3000);
+        let fee_complement = U256::from(997000);
+        let q128_per_liquidity =
+            |amount: U256| mul_div(amount, Q128, U256::from(LIQUIDITY)).unwrap();
+
+        // swap token0 for token1 within the range, the last step takes the rest as fee
+        let amount0 =
+            get_amount0_delta(price(-60), price(0), LIQUIDITY, true).unwrap() + U256::from(12345);
+        state
+            .apply_log(&log(
+                swap(I256::from_raw(amount0), I256::MINUS_ONE, -60, LIQUIDITY),
+                1,
+            ))
+            .unwrap();
+        let fee_growth0 = U256::from(5) + q128_per_liquidity(U256::from(12345));
+        assert_eq!(state.fee_growth_global0_x128, fee_growth0);
+        assert_eq!(state.tick, tick(-60));
+
+        // cross the lower tick of the position into a range without liquidity
+        let step_in = get_amount0_delta(price(-120), price(-60), LIQUIDITY, true).unwrap();
+        let step_fee = mul_div_rounding_up(step_in, fee, fee_complement).unwrap();
+        state
+            .apply_log(&log(
+                swap(I256::from_raw(step_in + step_fee), I256::MINUS_ONE, -180, 0),
+                2,
+            ))
+            .unwrap();
+        let fee_growth0 = fee_growth0 + q128_per_liquidity(step_fee);
+        assert_eq!(state.fee_growth_global0_x128, fee_growth0);
+        assert_eq!(state.liquidity, 0);
+        assert_eq!(
+            state.ticks[&tick(-120)].fee_growth_outside0_x128,
+            fee_growth0 - U256::from(5)
+        );
+
+        // swap token1 for token0 back into the range
+        let step_in = get_amount1_delta(price(-120), price(-60), LIQUIDITY, true).unwrap();
+        let step_fee = mul_div_rounding_up(step_in, fee, fee_complement).unwrap();
+        let amount1 = step_in
+            + step_fee
+            + get_amount1_delta(price(-60), price(0), LIQUIDITY, true).unwrap()
+            + U256::from(777);
+        state
+            .apply_log(&log(
+                swap(I256::MINUS_ONE, I256::from_raw(amount1), 0, LIQUIDITY),
+                3,
+            ))
+            .unwrap();
+        assert_eq!(
+            state.fee_growth_global1_x128,
+            q128_per_liquidity(step_fee) + q128_per_liquidity(U256::from(777))
+        );
+        assert_eq!(
+            state.ticks[&tick(-120)].fee_growth_outside0_x128,
+            U256::from(5)
+        );
+        assert_eq!(state.liquidity, LIQUIDITY);
+
+        // the liquidity in the log does not agree with the state
+        assert!(matches!(
+            state.apply_log(&log(swap(I256::MINUS_ONE, I256::ONE, 60, 0), 4)),
+            Err(Error::StateMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sync_and_verify() {
+        let provider = PROVIDER.clone();
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let mut state = PoolState::fetch(POOL_ADDRESS, block_number - 100, provider.clone(), None)
+            .await
+            .unwrap();
+        let applied = state
+            .sync(block_number, &ScanConfig::default(), provider.clone())
+            .await
+            .unwrap();
+        assert!(applied > 0);
+        assert_eq!(state.block_number, block_number);
+        state.verify_with_lens(provider, None).await.unwrap();
+    }
+}
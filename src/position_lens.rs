@@ -7,21 +7,26 @@ use crate::{
         ephemeralallpositionsbyowner::{
             EphemeralAllPositionsByOwner, EphemeralAllPositionsByOwner::allPositionsCall,
         },
+        ephemeralgetfeesowed::{
+            EphemeralGetFeesOwed, EphemeralGetFeesOwed::getFeesOwedCall, PoolUtils::PositionFees,
+        },
         ephemeralgetposition::{EphemeralGetPosition, EphemeralGetPosition::getPositionCall},
         ephemeralgetpositions::{EphemeralGetPositions, EphemeralGetPositions::getPositionsCall},
+        ephemeralgettotalamounts::{
+            EphemeralGetTotalAmounts, EphemeralGetTotalAmounts::getTotalAmountsCall,
+            PoolUtils::PositionAmounts,
+        },
+        ephemeralpoolpositions::PoolUtils::PositionKey,
     },
-    call_ephemeral_contract,
+    caller::{call_ephemeral, CallOptions},
     error::Error,
+    math::{get_amounts_for_liquidity, get_sqrt_ratio_at_tick, Q96},
 };
 use alloc::vec::Vec;
 use alloy::{
-    contract::Error as ContractError,
-    eips::BlockId,
     network::Network,
     primitives::{Address, U256},
     providers::Provider,
-    sol_types::SolCall,
-    transports::TransportError,
 };
 
 /// Get the details of a position given the token ID.
@@ -31,7 +36,7 @@ use alloy::{
 /// * `npm`: The address of the non-fungible position manager
 /// * `token_id`: The token ID of the position
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -41,14 +46,14 @@ pub async fn get_position_details<N, P>(
     npm: Address,
     token_id: U256,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<EphemeralGetPosition::PositionState, Error>
 where
     N: Network,
     P: Provider<N>,
 {
     let deploy_builder = EphemeralGetPosition::deploy_builder(provider, npm, token_id);
-    call_ephemeral_contract!(deploy_builder, getPositionCall, block_id)
+    call_ephemeral::<getPositionCall, _, _>(deploy_builder, options).await
 }
 
 /// Get the details of multiple positions given the token IDs.
@@ -58,7 +63,7 @@ where
 /// * `npm`: The address of the non-fungible position manager
 /// * `token_ids`: The token IDs of the positions
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -68,14 +73,14 @@ pub async fn get_positions<N, P>(
     npm: Address,
     token_ids: Vec<U256>,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<EphemeralGetPositions::PositionState>, Error>
 where
     N: Network,
     P: Provider<N>,
 {
     let deploy_builder = EphemeralGetPositions::deploy_builder(provider, npm, token_ids);
-    call_ephemeral_contract!(deploy_builder, getPositionsCall, block_id)
+    call_ephemeral::<getPositionsCall, _, _>(deploy_builder, options).await
 }
 
 /// Get all positions owned by an address.
@@ -85,7 +90,7 @@ where
 /// * `npm`: The address of the non-fungible position manager
 /// * `owner`: The address of the owner
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block number to query
+/// * `options`: Optional block number or [`CallOptions`] to query with
 ///
 /// ## Returns
 ///
@@ -95,16 +100,181 @@ pub async fn get_all_positions_by_owner<N, P>(
     npm: Address,
     owner: Address,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<EphemeralAllPositionsByOwner::PositionState>, Error>
 where
     N: Network,
     P: Provider<N>,
 {
     let deploy_builder = EphemeralAllPositionsByOwner::deploy_builder(provider, npm, owner);
-    call_ephemeral_contract!(deploy_builder, allPositionsCall, block_id)
+    call_ephemeral::<allPositionsCall, _, _>(deploy_builder, options).await
+}
+
+/// Convert position keys to the `PositionKey` type of another binding module.
+macro_rules! convert_keys {
+    ($keys:expr, $module:ident) => {
+        $keys
+            .into_iter()
+            .map(
+                |PositionKey {
+                     owner,
+                     tickLower,
+                     tickUpper,
+                 }| crate::bindings::$module::PoolUtils::PositionKey {
+                    owner,
+                    tickLower,
+                    tickUpper,
+                },
+            )
+            .collect::<Vec<_>>()
+    };
+}
+
+/// Get the fees owed to positions minted directly on a pool.
+///
+/// ## Arguments
+///
+/// * `pool`: The address of a V3 pool
+/// * `keys`: A vector of position keys
+/// * `provider`: The alloy provider
+/// * `options`: Optional block number or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The fees owed to each position
+#[inline]
+pub async fn get_fees_owed<N, P>(
+    pool: Address,
+    keys: Vec<PositionKey>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<PositionFees>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let keys = convert_keys!(keys, ephemeralgetfeesowed);
+    let deploy_builder = EphemeralGetFeesOwed::deploy_builder(provider, pool, keys);
+    call_ephemeral::<getFeesOwedCall, _, _>(deploy_builder, options).await
+}
+
+/// Get the total amounts of token0 and token1 including fees held in positions minted directly on
+/// a pool.
+///
+/// ## Arguments
+///
+/// * `pool`: The address of a V3 pool
+/// * `keys`: A vector of position keys
+/// * `provider`: The alloy provider
+/// * `options`: Optional block number or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The total amounts held in each position
+#[inline]
+pub async fn get_total_amounts<N, P>(
+    pool: Address,
+    keys: Vec<PositionKey>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<PositionAmounts>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let keys = convert_keys!(keys, ephemeralgettotalamounts);
+    let deploy_builder = EphemeralGetTotalAmounts::deploy_builder(provider, pool, keys);
+    call_ephemeral::<getTotalAmountsCall, _, _>(deploy_builder, options).await
+}
+
+/// Convert a raw token amount to a decimal-adjusted value.
+///
+/// Scaled in `f64` because `10^decimals` overflows `U256` above 77 and `f64::powi` needs `std`.
+fn to_decimal(amount: U256, decimals: u8) -> f64 {
+    f64::from(amount) / (0..decimals).fold(1.0, |scale, _| scale * 10.0)
+}
+
+/// Implements the valuation methods for the `PositionState` returned by each position lens.
+macro_rules! impl_position_state {
+    ($($position_state:ty),+ $(,)?) => {$(
+        impl $position_state {
+            /// Whether the current tick of the pool is within the tick range of the position.
+            #[inline]
+            pub fn in_range(&self) -> bool {
+                self.position.tickLower <= self.slot0.tick && self.slot0.tick < self.position.tickUpper
+            }
+
+            /// Get the amounts of token0 and token1 underlying the position's liquidity at the
+            /// current sqrt price of the pool.
+            ///
+            /// ## Returns
+            ///
+            /// The amount of token0 and the amount of token1
+            #[inline]
+            pub fn amounts(&self) -> Result<(U256, U256), Error> {
+                get_amounts_for_liquidity(
+                    self.slot0.sqrtPriceX96,
+                    get_sqrt_ratio_at_tick(self.position.tickLower)?,
+                    get_sqrt_ratio_at_tick(self.position.tickUpper)?,
+                    self.position.liquidity,
+                )
+            }
+
+            /// Get the uncollected fees of the position, i.e. the real-time tokens owed.
+            ///
+            /// ## Returns
+            ///
+            /// The amount of token0 and the amount of token1 owed to the position
+            #[inline]
+            pub const fn fees(&self) -> (u128, u128) {
+                (self.position.tokensOwed0, self.position.tokensOwed1)
+            }
+
+            /// Get the decimal-adjusted amounts of token0 and token1 underlying the position.
+            ///
+            /// ## Returns
+            ///
+            /// The amount of token0 and the amount of token1 in units of the tokens
+            #[inline]
+            pub fn amounts_adjusted(&self) -> Result<(f64, f64), Error> {
+                let (amount0, amount1) = self.amounts()?;
+                Ok((
+                    to_decimal(amount0, self.decimals0),
+                    to_decimal(amount1, self.decimals1),
+                ))
+            }
+
+            /// Get the decimal-adjusted uncollected fees of the position.
+            ///
+            /// ## Returns
+            ///
+            /// The amount of token0 and the amount of token1 owed in units of the tokens
+            #[inline]
+            pub fn fees_adjusted(&self) -> (f64, f64) {
+                (
+                    to_decimal(U256::from(self.position.tokensOwed0), self.decimals0),
+                    to_decimal(U256::from(self.position.tokensOwed1), self.decimals1),
+                )
+            }
+
+            /// Get the decimal-adjusted price of token0 in terms of token1 at the current sqrt
+            /// price of the pool.
+            #[inline]
+            pub fn price(&self) -> f64 {
+                let sqrt_price = f64::from(self.slot0.sqrtPriceX96) / f64::from(Q96);
+                sqrt_price * sqrt_price * to_decimal(U256::ONE, self.decimals1)
+                    / to_decimal(U256::ONE, self.decimals0)
+            }
+        }
+    )+};
 }
 
+impl_position_state!(
+    EphemeralGetPosition::PositionState,
+    EphemeralGetPositions::PositionState,
+    EphemeralAllPositionsByOwner::PositionState,
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,10 +284,15 @@ mod tests {
             iuniswapv3nonfungiblepositionmanager::IUniswapV3NonfungiblePositionManager,
             iuniswapv3pool::IUniswapV3Pool,
         },
+        math::get_tick_at_sqrt_ratio,
         tests::*,
     };
     use alloy::{
-        primitives::{address, aliases::U24, b256, keccak256, uint, B256},
+        primitives::{
+            address,
+            aliases::{I24, U24},
+            b256, keccak256, uint, B256,
+        },
         providers::{MulticallBuilder, RootProvider},
         sol_types::SolValue,
     };
@@ -143,6 +318,74 @@ mod tests {
         factory.create2(keccak256(pool_key.abi_encode()), init_code_hash)
     }
 
+    #[test]
+    fn test_position_state_valuation() {
+        let tick = |t: i32| I24::try_from(t).unwrap();
+        // 1 WETH = 2000 USDC with USDC as token0
+        let sqrt_price_x96 = uint!(1771595571142957166518320255467520_U160);
+        let mut state = EphemeralGetPosition::PositionState {
+            tokenId: uint!(1_U256),
+            owner: Address::ZERO,
+            position: PositionFull {
+                nonce: Default::default(),
+                operator: Address::ZERO,
+                token0: Address::ZERO,
+                token1: Address::ZERO,
+                fee: U24::from(3000),
+                tickLower: tick(-887220),
+                tickUpper: tick(887220),
+                liquidity: 10_u128.pow(15),
+                feeGrowthInside0LastX128: U256::ZERO,
+                feeGrowthInside1LastX128: U256::ZERO,
+                tokensOwed0: 2_000_000,
+                tokensOwed1: 10_u128.pow(15),
+            },
+            slot0: Slot0 {
+                sqrtPriceX96: sqrt_price_x96,
+                tick: get_tick_at_sqrt_ratio(sqrt_price_x96).unwrap(),
+                observationIndex: 0,
+                observationCardinality: 1,
+                observationCardinalityNext: 1,
+                feeProtocol: 0,
+                unlocked: true,
+            },
+            activeLiquidity: 10_u128.pow(15),
+            decimals0: 6,
+            decimals1: 18,
+        };
+        assert!(state.in_range());
+        assert!((state.price() - 1.0 / 2000.0).abs() < 1e-9);
+        // a full range position holds sqrt(k) / sqrt(P) of token0 and sqrt(k) * sqrt(P) of token1
+        let (amount0, amount1) = state.amounts_adjusted().unwrap();
+        assert!((amount0 - 44721.35955).abs() < 1e-3);
+        assert!((amount1 - 22.36067977).abs() < 1e-6);
+        assert_eq!(state.fees(), (2_000_000, 10_u128.pow(15)));
+        assert_eq!(state.fees_adjusted(), (2.0, 0.001));
+
+        // below the range, the position is entirely in token0
+        state.position.tickLower = state.slot0.tick + tick(1);
+        assert!(!state.in_range());
+        let (amount0, amount1) = state.amounts().unwrap();
+        assert!(!amount0.is_zero() && amount1.is_zero());
+        // the upper tick is exclusive
+        state.position.tickLower = tick(-887220);
+        state.position.tickUpper = state.slot0.tick;
+        assert!(!state.in_range());
+        let (amount0, amount1) = state.amounts().unwrap();
+        assert!(amount0.is_zero() && !amount1.is_zero());
+    }
+
+    #[test]
+    fn test_to_decimal() {
+        assert_eq!(to_decimal(U256::from(2_000_000), 6), 2.0);
+        assert_eq!(to_decimal(U256::from(1), 0), 1.0);
+        // 10^200 exceeds U256 and must not wrap
+        let value = to_decimal(U256::from(10).pow(U256::from(70)), 200);
+        assert!((value / 1e-130 - 1.0).abs() < 1e-9);
+        let value = to_decimal(U256::MAX, u8::MAX);
+        assert!(value > 0.0 && value < 1e-177);
+    }
+
     #[tokio::test]
     async fn test_get_position_details() {
         let provider = PROVIDER.clone();
@@ -177,6 +420,46 @@ mod tests {
         assert_eq!(tick, slot0.tick);
     }
 
+    #[tokio::test]
+    async fn test_get_fees_owed_and_total_amounts() {
+        let provider = PROVIDER.clone();
+        let state = get_position_details(
+            NPM_ADDRESS,
+            uint!(4_U256),
+            provider.clone(),
+            Some(BLOCK_NUMBER),
+        )
+        .await
+        .unwrap();
+        let PositionFull {
+            token0,
+            token1,
+            fee,
+            tickLower,
+            tickUpper,
+            ..
+        } = state.position;
+        let pool = compute_pool_address(FACTORY_ADDRESS, token0, token1, fee, POOL_INIT_CODE_HASH);
+        // positions minted through the NPM are owned by the NPM on the pool
+        let keys = vec![PositionKey {
+            owner: NPM_ADDRESS,
+            tickLower,
+            tickUpper,
+        }];
+        let fees = get_fees_owed(pool, keys.clone(), provider.clone(), Some(BLOCK_NUMBER))
+            .await
+            .unwrap();
+        let amounts = get_total_amounts(pool, keys, provider, Some(BLOCK_NUMBER))
+            .await
+            .unwrap();
+        assert_eq!(fees.len(), 1);
+        assert_eq!(amounts.len(), 1);
+        // the pool position aggregates every NPM position with the same tick range
+        let (amount0, amount1) = state.amounts().unwrap();
+        assert!(amounts[0].amount0 >= amount0 + U256::from(fees[0].tokensOwed0));
+        assert!(amounts[0].amount1 >= amount1 + U256::from(fees[0].tokensOwed1));
+    }
+
     async fn verify_position_details(
         positions: Vec<EphemeralGetPositions::PositionState>,
         npm: IUniswapV3NonfungiblePositionManager::IUniswapV3NonfungiblePositionManagerInstance<
@@ -0,0 +1,573 @@
+//! ## Registry
+//!
+//! The registry module enumerates the pools deployed by a V3 factory from its `PoolCreated` logs
+//! and indexes them by token pair, fee and tick spacing. A registry can be synced incrementally and
+//! serialized to bytes so that a scan does not have to start from the factory deployment each time.
+
+use crate::{bindings::iuniswapv3factory::IUniswapV3Factory::PoolCreated, error::Error};
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::Network,
+    primitives::{
+        aliases::{I24, U24},
+        Address,
+    },
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    sol_types::{SolEvent, SolValue},
+    transports::TransportError,
+};
+
+/// The serialization format version of [`PoolRegistry::to_bytes`].
+const REGISTRY_VERSION: u8 = 1;
+
+/// A pool created by the factory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PoolEntry {
+    /// The address of the pool
+    pub pool: Address,
+    /// The first of the two tokens of the pool, sorted by address
+    pub token0: Address,
+    /// The second of the two tokens of the pool, sorted by address
+    pub token1: Address,
+    /// The fee collected upon every swap in the pool, denominated in hundredths of a bip
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::fee"))]
+    pub fee: U24,
+    /// The minimum number of ticks between initialized ticks
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_spacing: I24,
+    /// The block number in which the pool was created
+    pub block_number: u64,
+}
+
+impl PoolEntry {
+    /// Decode a pool entry from a `PoolCreated` log.
+    ///
+    /// ## Arguments
+    ///
+    /// * `log`: A `PoolCreated` log emitted by the factory
+    ///
+    /// ## Returns
+    ///
+    /// The pool entry
+    #[inline]
+    pub fn from_log(log: &Log) -> Result<Self, Error> {
+        let PoolCreated {
+            token0,
+            token1,
+            fee,
+            tickSpacing,
+            pool,
+        } = PoolCreated::decode_log_data(log.data())?;
+        Ok(Self {
+            pool,
+            token0,
+            token1,
+            fee,
+            tick_spacing: tickSpacing,
+            block_number: log.block_number.unwrap_or_default(),
+        })
+    }
+}
+
+/// The configuration of a log scan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanConfig {
+    /// The number of blocks to query in each `eth_getLogs` request
+    pub chunk_size: u64,
+    /// The smallest chunk size to fall back to when the provider rejects a request for returning
+    /// too many results
+    pub min_chunk_size: u64,
+    /// The number of times to retry a request that failed for any other reason
+    pub max_retries: u32,
+}
+
+impl Default for ScanConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            chunk_size: 100_000,
+            min_chunk_size: 100,
+            max_retries: 3,
+        }
+    }
+}
+
+/// Whether an RPC error indicates that a `eth_getLogs` request returned too many results or
+/// spanned too many blocks, in which case the request should be retried with a smaller range.
+#[inline]
+pub fn is_too_many_results(err: &TransportError) -> bool {
+    const PATTERNS: [&str; 7] = [
+        "too many",
+        "more than",
+        "limit exceeded",
+        "range is too large",
+        "block range",
+        "response size",
+        "query timeout",
+    ];
+    err.as_error_resp().is_some_and(|payload| {
+        let message = payload.message.to_ascii_lowercase();
+        PATTERNS.iter().any(|pattern| message.contains(pattern))
+    })
+}
+
+/// Get the logs matching a filter over a block range in chunks, halving the chunk size whenever
+/// the provider returns too many results.
+///
+/// ## Arguments
+///
+/// * `filter`: The log filter without a block range
+/// * `from_block`: The first block to scan
+/// * `to_block`: The last block to scan, inclusive
+/// * `config`: The scan configuration
+/// * `provider`: The alloy provider
+///
+/// ## Returns
+///
+/// The logs in the block range in the order returned by the provider
+#[inline]
+pub async fn get_logs_chunked<N, P>(
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    config: &ScanConfig,
+    provider: P,
+) -> Result<Vec<Log>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let mut logs = Vec::new();
+    let mut chunk_size = config.chunk_size.max(1);
+    let mut start = from_block;
+    let mut retries = 0;
+    while start <= to_block {
+        let end = start.saturating_add(chunk_size - 1).min(to_block);
+        let chunk = filter
+            .clone()
+            .from_block(BlockNumberOrTag::Number(start))
+            .to_block(BlockNumberOrTag::Number(end));
+        match provider.get_logs(&chunk).await {
+            Ok(chunk_logs) => {
+                logs.extend(chunk_logs);
+                retries = 0;
+                let Some(next) = end.checked_add(1) else {
+                    break;
+                };
+                start = next;
+            }
+            Err(err) if is_too_many_results(&err) && chunk_size > config.min_chunk_size => {
+                chunk_size = (chunk_size / 2).max(config.min_chunk_size);
+            }
+            Err(err) => {
+                if retries >= config.max_retries {
+                    return Err(err.into());
+                }
+                retries += 1;
+            }
+        }
+    }
+    Ok(logs)
+}
+
+/// An in-memory index of the pools created by a V3 factory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolRegistry {
+    /// The address of the factory
+    factory: Address,
+    /// The last block that has been scanned
+    last_block: Option<u64>,
+    /// The pools in the order they were added
+    pools: Vec<PoolEntry>,
+    /// The index of each pool in `pools` by address
+    by_address: BTreeMap<Address, usize>,
+    /// The indices of the pools in `pools` by sorted token pair
+    by_pair: BTreeMap<(Address, Address), Vec<usize>>,
+    /// The indices of the pools in `pools` by fee tier
+    by_fee: BTreeMap<U24, Vec<usize>>,
+    /// The indices of the pools in `pools` by tick spacing
+    by_tick_spacing: BTreeMap<I24, Vec<usize>>,
+}
+
+/// Sort a token pair by address.
+fn sort_tokens(token_a: Address, token_b: Address) -> (Address, Address) {
+    if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    }
+}
+
+impl PoolRegistry {
+    /// Create an empty registry for a factory.
+    #[inline]
+    pub const fn new(factory: Address) -> Self {
+        Self {
+            factory,
+            last_block: None,
+            pools: Vec::new(),
+            by_address: BTreeMap::new(),
+            by_pair: BTreeMap::new(),
+            by_fee: BTreeMap::new(),
+            by_tick_spacing: BTreeMap::new(),
+        }
+    }
+
+    /// The address of the factory.
+    #[inline]
+    pub const fn factory(&self) -> Address {
+        self.factory
+    }
+
+    /// The last block that has been scanned, if any.
+    #[inline]
+    pub const fn last_block(&self) -> Option<u64> {
+        self.last_block
+    }
+
+    /// The number of pools in the registry.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    /// Whether the registry has no pools.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// Iterate over the pools in the order they were added.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &PoolEntry> {
+        self.pools.iter()
+    }
+
+    /// Add a pool to the registry.
+    ///
+    /// ## Returns
+    ///
+    /// Whether the pool was not already in the registry
+    #[inline]
+    pub fn insert(&mut self, entry: PoolEntry) -> bool {
+        if self.by_address.contains_key(&entry.pool) {
+            return false;
+        }
+        let index = self.pools.len();
+        self.by_address.insert(entry.pool, index);
+        self.by_pair
+            .entry(sort_tokens(entry.token0, entry.token1))
+            .or_default()
+            .push(index);
+        self.by_fee.entry(entry.fee).or_default().push(index);
+        self.by_tick_spacing
+            .entry(entry.tick_spacing)
+            .or_default()
+            .push(index);
+        self.pools.push(entry);
+        true
+    }
+
+    /// Add the pools in `PoolCreated` logs to the registry, ignoring logs from other contracts.
+    ///
+    /// ## Arguments
+    ///
+    /// * `logs`: The logs emitted by the factory
+    ///
+    /// ## Returns
+    ///
+    /// The number of pools added
+    #[inline]
+    pub fn apply_logs(&mut self, logs: &[Log]) -> Result<usize, Error> {
+        let mut added = 0;
+        for log in logs {
+            if log.address() != self.factory || log.topic0() != Some(&PoolCreated::SIGNATURE_HASH) {
+                continue;
+            }
+            if self.insert(PoolEntry::from_log(log)?) {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Scan the `PoolCreated` logs of the factory from the block after the last scanned block, or
+    /// `from_block` if the registry has never been synced, up to `to_block`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `from_block`: The first block to scan if the registry has never been synced, e.g. the
+    ///   deployment block of the factory
+    /// * `to_block`: The last block to scan, inclusive
+    /// * `config`: The scan configuration
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The number of pools added
+    #[inline]
+    pub async fn sync<N, P>(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        config: &ScanConfig,
+        provider: P,
+    ) -> Result<usize, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        let from_block = self.last_block.map_or(from_block, |block| block + 1);
+        if from_block > to_block {
+            return Ok(0);
+        }
+        let filter = Filter::new()
+            .address(self.factory)
+            .event_signature(PoolCreated::SIGNATURE_HASH);
+        let logs = get_logs_chunked(&filter, from_block, to_block, config, provider).await?;
+        let added = self.apply_logs(&logs)?;
+        self.last_block = Some(to_block);
+        Ok(added)
+    }
+
+    /// Get a pool by address.
+    #[inline]
+    pub fn get(&self, pool: Address) -> Option<&PoolEntry> {
+        self.by_address.get(&pool).map(|&i| &self.pools[i])
+    }
+
+    /// Get the pools of a token pair in any order.
+    #[inline]
+    pub fn pools_for_pair(
+        &self,
+        token_a: Address,
+        token_b: Address,
+    ) -> impl Iterator<Item = &PoolEntry> {
+        self.by_pair
+            .get(&sort_tokens(token_a, token_b))
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.pools[i])
+    }
+
+    /// Get the pool of a token pair in any order and a fee tier.
+    #[inline]
+    pub fn get_pool(&self, token_a: Address, token_b: Address, fee: U24) -> Option<&PoolEntry> {
+        self.pools_for_pair(token_a, token_b)
+            .find(|entry| entry.fee == fee)
+    }
+
+    /// Get the pools with a fee tier.
+    #[inline]
+    pub fn pools_with_fee(&self, fee: U24) -> impl Iterator<Item = &PoolEntry> {
+        self.by_fee
+            .get(&fee)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.pools[i])
+    }
+
+    /// Get the pools with a tick spacing.
+    #[inline]
+    pub fn pools_with_tick_spacing(&self, tick_spacing: I24) -> impl Iterator<Item = &PoolEntry> {
+        self.by_tick_spacing
+            .get(&tick_spacing)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.pools[i])
+    }
+
+    /// Serialize the registry into a versioned, ABI encoded byte array.
+    #[inline]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pools: Vec<_> = self
+            .pools
+            .iter()
+            .map(|entry| {
+                (
+                    entry.pool,
+                    entry.token0,
+                    entry.token1,
+                    entry.fee,
+                    entry.tick_spacing,
+                    entry.block_number,
+                )
+            })
+            .collect();
+        let mut bytes = Vec::from([REGISTRY_VERSION]);
+        bytes.extend(
+            (
+                self.factory,
+                self.last_block.is_some(),
+                self.last_block.unwrap_or_default(),
+                pools,
+            )
+                .abi_encode_params(),
+        );
+        bytes
+    }
+
+    /// Deserialize a registry from the output of [`PoolRegistry::to_bytes`].
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let Some((&REGISTRY_VERSION, data)) = bytes.split_first() else {
+            return Err(Error::InvalidVersion);
+        };
+        type Encoded = (
+            Address,
+            bool,
+            u64,
+            Vec<(Address, Address, Address, U24, I24, u64)>,
+        );
+        let (factory, synced, last_block, pools) = Encoded::abi_decode_params(data)?;
+        let mut registry = Self::new(factory);
+        registry.last_block = synced.then_some(last_block);
+        for (pool, token0, token1, fee, tick_spacing, block_number) in pools {
+            registry.insert(PoolEntry {
+                pool,
+                token0,
+                token1,
+                fee,
+                tick_spacing,
+                block_number,
+            });
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use alloy::{
+        primitives::{address, Log as PrimitiveLog},
+        rpc::json_rpc::ErrorPayload,
+    };
+
+    const FACTORY_ADDRESS: Address = address!("1F98431c8aD98523631AE4a59f267346ea31F984");
+    const USDC: Address = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+    fn pool_created_log(pool: Address, fee: u32, tick_spacing: i32, block_number: u64) -> Log {
+        let event = PoolCreated {
+            token0: USDC,
+            token1: WETH,
+            fee: U24::from(fee),
+            tickSpacing: I24::try_from(tick_spacing).unwrap(),
+            pool,
+        };
+        Log {
+            inner: PrimitiveLog {
+                address: FACTORY_ADDRESS,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(block_number),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = PoolRegistry::new(FACTORY_ADDRESS);
+        let pool_500 = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+        let pool_3000 = address!("8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8");
+        let mut logs = vec![
+            pool_created_log(pool_3000, 3000, 60, 12370624),
+            pool_created_log(pool_500, 500, 10, 12376729),
+            // a duplicate
+            pool_created_log(pool_500, 500, 10, 12376729),
+        ];
+        // a log from another contract
+        let mut other = pool_created_log(Address::ZERO, 100, 1, 12376730);
+        other.inner.address = USDC;
+        logs.push(other);
+        assert_eq!(registry.apply_logs(&logs).unwrap(), 2);
+        assert_eq!(registry.len(), 2);
+
+        assert_eq!(
+            registry.get(pool_500).unwrap().tick_spacing,
+            I24::try_from(10).unwrap()
+        );
+        assert_eq!(
+            registry.get_pool(WETH, USDC, U24::from(3000)).unwrap().pool,
+            pool_3000
+        );
+        assert_eq!(registry.pools_for_pair(WETH, USDC).count(), 2);
+        assert!(registry.get_pool(USDC, WETH, U24::from(100)).is_none());
+        assert_eq!(
+            registry
+                .pools_with_fee(U24::from(500))
+                .map(|entry| entry.block_number)
+                .collect::<Vec<_>>(),
+            vec![12376729]
+        );
+        assert_eq!(
+            registry
+                .pools_with_tick_spacing(I24::try_from(60).unwrap())
+                .count(),
+            1
+        );
+        assert_eq!(registry.pools_with_fee(U24::from(100)).count(), 0);
+
+        let bytes = registry.to_bytes();
+        let decoded = PoolRegistry::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, registry);
+        assert_eq!(
+            decoded.pools_with_fee(U24::from(3000)).next().unwrap().pool,
+            pool_3000
+        );
+        assert!(matches!(
+            PoolRegistry::from_bytes(&bytes[1..]),
+            Err(Error::InvalidVersion)
+        ));
+    }
+
+    #[test]
+    fn test_is_too_many_results() {
+        let err = |message: &'static str| {
+            TransportError::ErrorResp(ErrorPayload {
+                code: -32005,
+                message: message.into(),
+                data: None,
+            })
+        };
+        assert!(is_too_many_results(&err(
+            "query returned more than 10000 results"
+        )));
+        assert!(is_too_many_results(&err(
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range"
+        )));
+        assert!(!is_too_many_results(&err("execution reverted")));
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let provider = PROVIDER.clone();
+        // the factory was deployed at block 12369621
+        let from_block = 12369621;
+        let config = ScanConfig {
+            chunk_size: 1000,
+            ..Default::default()
+        };
+        let mut registry = PoolRegistry::new(FACTORY_ADDRESS);
+        registry
+            .sync(from_block, from_block + 9999, &config, provider.clone())
+            .await
+            .unwrap();
+        assert_eq!(registry.last_block(), Some(from_block + 9999));
+        let len = registry.len();
+        assert!(len > 0);
+        // incremental sync
+        registry
+            .sync(from_block, from_block + 19999, &config, provider)
+            .await
+            .unwrap();
+        assert!(registry.len() >= len);
+        assert!(registry.get_pool(USDC, WETH, U24::from(3000)).is_some());
+    }
+}
@@ -0,0 +1,375 @@
+//! ## Serde Helpers
+//!
+//! Serialization of the lens results with the `serde` feature. Big integers are encoded as decimal
+//! strings so that they survive JSON parsers limited to 53-bit numbers, while ticks, fees and other
+//! small integers are plain numbers. The `sol!` generated types are implemented through mirror
+//! structs since their definitions cannot be annotated.
+
+use crate::bindings::{
+    ephemeralallpositionsbyowner::EphemeralAllPositionsByOwner,
+    ephemeralgetfeesowed::PoolUtils::PositionFees,
+    ephemeralgetpopulatedticksinrange::PoolUtils::PopulatedTick,
+    ephemeralgetposition::EphemeralGetPosition, ephemeralgetpositions::EphemeralGetPositions,
+    ephemeralgettokenmetadata::TokenUtils::TokenMetadata,
+    ephemeralgettotalamounts::PoolUtils::PositionAmounts,
+    ephemeralpoolpositions::PoolUtils::PositionKey, ephemeralpoolslots::PoolUtils::Slot,
+};
+use alloc::string::String;
+use alloy::primitives::{
+    aliases::{I24, U160, U24, U96},
+    Address, U256,
+};
+
+/// Encodes an integer as a decimal string.
+pub(crate) mod decimal {
+    use alloc::string::{String, ToString};
+    use core::{fmt::Display, str::FromStr};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Encodes a tick as a number.
+pub(crate) mod tick {
+    use alloy::primitives::aliases::I24;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(value: &I24, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(value.as_i32())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<I24, D::Error> {
+        I24::try_from(i32::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Encodes a fee as a number.
+pub(crate) mod fee {
+    use alloy::primitives::aliases::U24;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(value: &U24, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(value.to())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U24, D::Error> {
+        U24::try_from(u32::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Encodes the initialized ticks of a pool as a map from tick to tick info.
+pub(crate) mod ticks {
+    use crate::pool_state::TickInfo;
+    use alloc::collections::BTreeMap;
+    use alloy::primitives::aliases::I24;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &BTreeMap<I24, TickInfo>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(value.iter().map(|(tick, info)| (tick.as_i32(), info)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<I24, TickInfo>, D::Error> {
+        BTreeMap::<i32, TickInfo>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(tick, info)| Ok((I24::try_from(tick).map_err(D::Error::custom)?, info)))
+            .collect()
+    }
+}
+
+/// Encodes the tick bitmap of a pool as a map from word position to decimal string.
+pub(crate) mod tick_bitmap {
+    use alloc::{
+        collections::BTreeMap,
+        string::{String, ToString},
+    };
+    use alloy::primitives::U256;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &BTreeMap<i16, U256>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            value
+                .iter()
+                .map(|(word_pos, word)| (word_pos, word.to_string())),
+        )
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<i16, U256>, D::Error> {
+        BTreeMap::<i16, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(word_pos, word)| Ok((word_pos, word.parse().map_err(D::Error::custom)?)))
+            .collect()
+    }
+}
+
+/// Decodes `null` as the default value, e.g. the `types` of a storage layout without variables.
+pub(crate) mod nullable {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for `sol!` generated structs through a mirror struct
+/// with the same fields.
+macro_rules! impl_serde {
+    ($($ty:ty { $($field:ident: $field_ty:ty $(=> $with:literal)?),+ $(,)? })+) => {$(
+        const _: () = {
+            #[allow(non_snake_case)]
+            #[derive(serde::Serialize, serde::Deserialize)]
+            struct Mirror {
+                $($(#[serde(with = $with)])? $field: $field_ty,)+
+            }
+
+            impl serde::Serialize for $ty {
+                #[inline]
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    Mirror {
+                        $($field: Clone::clone(&self.$field),)+
+                    }
+                    .serialize(serializer)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $ty {
+                #[inline]
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let Mirror { $($field,)+ } = Mirror::deserialize(deserializer)?;
+                    Ok(Self { $($field,)+ })
+                }
+            }
+        };
+    )+};
+}
+
+/// Implements serde for the `PositionState` returned by each position lens and its members.
+macro_rules! impl_serde_position_state {
+    ($($contract:ident),+ $(,)?) => {$(
+        impl_serde! {
+            $contract::PositionFull {
+                nonce: U96 => "decimal",
+                operator: Address,
+                token0: Address,
+                token1: Address,
+                fee: U24 => "fee",
+                tickLower: I24 => "tick",
+                tickUpper: I24 => "tick",
+                liquidity: u128 => "decimal",
+                feeGrowthInside0LastX128: U256 => "decimal",
+                feeGrowthInside1LastX128: U256 => "decimal",
+                tokensOwed0: u128 => "decimal",
+                tokensOwed1: u128 => "decimal",
+            }
+            $contract::Slot0 {
+                sqrtPriceX96: U160 => "decimal",
+                tick: I24 => "tick",
+                observationIndex: u16,
+                observationCardinality: u16,
+                observationCardinalityNext: u16,
+                feeProtocol: u32,
+                unlocked: bool,
+            }
+            $contract::PositionState {
+                tokenId: U256 => "decimal",
+                owner: Address,
+                position: $contract::PositionFull,
+                slot0: $contract::Slot0,
+                activeLiquidity: u128 => "decimal",
+                decimals0: u8,
+                decimals1: u8,
+            }
+        }
+    )+};
+}
+
+impl_serde_position_state!(
+    EphemeralGetPosition,
+    EphemeralGetPositions,
+    EphemeralAllPositionsByOwner,
+);
+
+impl_serde! {
+    PositionKey {
+        owner: Address,
+        tickLower: I24 => "tick",
+        tickUpper: I24 => "tick",
+    }
+    PositionFees {
+        tokensOwed0: u128 => "decimal",
+        tokensOwed1: u128 => "decimal",
+    }
+    PositionAmounts {
+        amount0: U256 => "decimal",
+        amount1: U256 => "decimal",
+    }
+    PopulatedTick {
+        tick: I24 => "tick",
+        liquidityNet: i128 => "decimal",
+        liquidityGross: u128 => "decimal",
+        feeGrowthOutside0X128: U256 => "decimal",
+        feeGrowthOutside1X128: U256 => "decimal",
+    }
+    Slot {
+        slot: U256 => "decimal",
+        data: U256 => "decimal",
+    }
+    TokenMetadata {
+        token: Address,
+        name: String,
+        symbol: String,
+        decimals: u8,
+        totalSupply: U256 => "decimal",
+        hasName: bool,
+        hasSymbol: bool,
+        hasDecimals: bool,
+        hasTotalSupply: bool,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_state::{PoolState, TickInfo};
+    use alloy::primitives::address;
+    use serde_json::json;
+
+    #[test]
+    fn test_populated_tick() {
+        let tick = PopulatedTick {
+            tick: I24::try_from(-887220).unwrap(),
+            liquidityNet: -1,
+            liquidityGross: u128::MAX,
+            feeGrowthOutside0X128: U256::MAX,
+            feeGrowthOutside1X128: U256::ZERO,
+        };
+        let value = serde_json::to_value(&tick).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "tick": -887220,
+                "liquidityNet": "-1",
+                "liquidityGross": u128::MAX.to_string(),
+                "feeGrowthOutside0X128": U256::MAX.to_string(),
+                "feeGrowthOutside1X128": "0",
+            })
+        );
+        let decoded: PopulatedTick = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.tick, tick.tick);
+        assert_eq!(decoded.liquidityNet, tick.liquidityNet);
+        assert_eq!(decoded.feeGrowthOutside0X128, tick.feeGrowthOutside0X128);
+        // big integers must be strings
+        assert!(serde_json::from_value::<Slot>(json!({ "slot": 0, "data": "1" })).is_err());
+    }
+
+    #[test]
+    fn test_position_state() {
+        let state = EphemeralGetPosition::PositionState {
+            tokenId: U256::from(1),
+            owner: address!("0000000000000000000000000000000000000001"),
+            position: EphemeralGetPosition::PositionFull {
+                nonce: U96::ZERO,
+                operator: Address::ZERO,
+                token0: Address::ZERO,
+                token1: Address::ZERO,
+                fee: U24::from(500),
+                tickLower: I24::try_from(-60).unwrap(),
+                tickUpper: I24::try_from(60).unwrap(),
+                liquidity: 1000,
+                feeGrowthInside0LastX128: U256::ZERO,
+                feeGrowthInside1LastX128: U256::ZERO,
+                tokensOwed0: 0,
+                tokensOwed1: 0,
+            },
+            slot0: EphemeralGetPosition::Slot0 {
+                sqrtPriceX96: U160::from(1) << 96,
+                tick: I24::ZERO,
+                observationIndex: 0,
+                observationCardinality: 1,
+                observationCardinalityNext: 1,
+                feeProtocol: 0,
+                unlocked: true,
+            },
+            activeLiquidity: 0,
+            decimals0: 6,
+            decimals1: 18,
+        };
+        let value = serde_json::to_value(&state).unwrap();
+        assert_eq!(value["tokenId"], "1");
+        assert_eq!(value["position"]["fee"], 500);
+        assert_eq!(value["position"]["tickLower"], -60);
+        assert_eq!(value["position"]["liquidity"], "1000");
+        assert_eq!(
+            value["slot0"]["sqrtPriceX96"],
+            (U160::from(1) << 96_usize).to_string()
+        );
+        assert_eq!(value["slot0"]["unlocked"], true);
+        let decoded: EphemeralGetPosition::PositionState =
+            serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+    }
+
+    #[test]
+    fn test_pool_state() {
+        let mut state = PoolState {
+            fee: U24::from(3000),
+            tick_spacing: I24::try_from(60).unwrap(),
+            liquidity: 1000,
+            ..Default::default()
+        };
+        state.ticks.insert(
+            I24::try_from(-120).unwrap(),
+            TickInfo {
+                liquidity_gross: 1000,
+                liquidity_net: 1000,
+                ..Default::default()
+            },
+        );
+        state.tick_bitmap.insert(-1, U256::ONE << 254);
+        let value = serde_json::to_value(&state).unwrap();
+        assert_eq!(value["tickSpacing"], 60);
+        assert_eq!(value["ticks"]["-120"]["liquidityNet"], "1000");
+        assert_eq!(
+            value["tickBitmap"]["-1"],
+            (U256::ONE << 254_usize).to_string()
+        );
+        let decoded: PoolState = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded, state);
+    }
+}
@@ -0,0 +1,569 @@
+//! ## Simulation
+//!
+//! The simulation module previews `mint` and `increaseLiquidity` by calling
+//! `NonfungiblePositionManager` with `eth_call` from the sender, so that the liquidity and the
+//! amounts are computed by the NPM itself, including its rounding and slippage checks. The call is
+//! batched with `positions(tokenId)` in a `multicall` to read the resulting position in the same
+//! execution. The token balances and approvals of the sender are injected through state overrides,
+//! and a revert is returned as [`Error::Reverted`] with the decoded reason.
+
+use crate::{
+    bindings::{
+        imulticall::IMulticall,
+        iuniswapv3nonfungiblepositionmanager::{
+            INonfungiblePositionManager::{IncreaseLiquidityParams, MintParams},
+            IUniswapV3NonfungiblePositionManager::{
+                increaseLiquidityCall, mintCall, positionsCall, positionsReturn,
+            },
+        },
+    },
+    caller::CallOptions,
+    error::Error,
+    state_override::WhatIf,
+    storage_lens::get_storage_at,
+    storage_slot::{extract_field, NPM_NEXT_ID_SLOT},
+};
+use alloc::{string::String, vec};
+use alloy::{
+    contract::Error as ContractError,
+    eips::BlockId,
+    network::Network,
+    primitives::{
+        aliases::{I24, U24, U96},
+        hex, Address, B256, U256,
+    },
+    providers::Provider,
+    sol_types::{decode_revert_reason, Error as AbiError, Revert, SolCall, SolError},
+    transports::TransportError,
+};
+
+/// A token balance and approval injected for the sender of a simulation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Funding {
+    /// The address of the token
+    pub token: Address,
+    /// The balance of the sender and its allowance to the NPM
+    pub amount: U256,
+    /// The storage slot of the balance mapping of the token, e.g. 3 for WETH
+    pub balances_slot: U256,
+    /// The storage slot of the allowance mapping of the token, e.g. 4 for WETH
+    pub allowances_slot: U256,
+}
+
+/// A position as returned by `positions(tokenId)` of `NonfungiblePositionManager`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MintedPosition {
+    /// The nonce for permits
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub nonce: U96,
+    /// The address approved to spend the position
+    pub operator: Address,
+    /// The address of token0
+    pub token0: Address,
+    /// The address of token1
+    pub token1: Address,
+    /// The fee tier of the pool
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::fee"))]
+    pub fee: U24,
+    /// The lower tick of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_lower: I24,
+    /// The upper tick of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_upper: I24,
+    /// The liquidity of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The fee growth of token0 inside the range as of the last update
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside0_last_x128: U256,
+    /// The fee growth of token1 inside the range as of the last update
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside1_last_x128: U256,
+    /// The uncollected amount of token0 owed to the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed0: u128,
+    /// The uncollected amount of token1 owed to the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed1: u128,
+}
+
+impl From<positionsReturn> for MintedPosition {
+    #[inline]
+    fn from(position: positionsReturn) -> Self {
+        Self {
+            nonce: position.nonce,
+            operator: position.operator,
+            token0: position.token0,
+            token1: position.token1,
+            fee: position.fee,
+            tick_lower: position.tickLower,
+            tick_upper: position.tickUpper,
+            liquidity: position.liquidity,
+            fee_growth_inside0_last_x128: position.feeGrowthInside0LastX128,
+            fee_growth_inside1_last_x128: position.feeGrowthInside1LastX128,
+            tokens_owed0: position.tokensOwed0,
+            tokens_owed1: position.tokensOwed1,
+        }
+    }
+}
+
+/// The outcome of a simulated `mint` or `increaseLiquidity`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MintSimulation {
+    /// The token ID of the minted or increased position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub token_id: U256,
+    /// The liquidity added to the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The amount of token0 paid
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub amount0: U256,
+    /// The amount of token1 paid
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub amount1: U256,
+    /// The resulting position
+    pub position: MintedPosition,
+}
+
+/// Set the sender of the call and merge the funding of the sender into the state overrides. The
+/// native balance of the sender is set to the `value` of the call unless it is already overridden.
+fn simulation_options(
+    npm: Address,
+    sender: Address,
+    funding: &[Funding],
+    options: impl Into<CallOptions>,
+) -> CallOptions {
+    let mut options = options.into();
+    let mut state = options.state.take().unwrap_or_default();
+    if let Some(value) = options.value {
+        state
+            .entry(sender)
+            .or_default()
+            .balance
+            .get_or_insert(value);
+    }
+    let mut what_if = WhatIf::from(state);
+    for funding in funding {
+        what_if = what_if
+            .erc20_balance(funding.token, sender, funding.amount, funding.balances_slot)
+            .erc20_allowance(
+                funding.token,
+                sender,
+                npm,
+                funding.amount,
+                funding.allowances_slot,
+            );
+    }
+    options.sender(sender).state(what_if.build())
+}
+
+/// Decode the revert reason of a failed call.
+fn revert_reason(err: ContractError) -> Error {
+    match err {
+        ContractError::TransportError(TransportError::ErrorResp(payload)) => {
+            match payload.as_revert_data() {
+                Some(data) => Error::Reverted(
+                    Revert::abi_decode(&data)
+                        .map(|revert| revert.reason)
+                        .ok()
+                        .or_else(|| decode_revert_reason(&data))
+                        .unwrap_or_else(|| hex::encode_prefixed(&data)),
+                ),
+                None => Error::Reverted(String::from(payload.message)),
+            }
+        }
+        err => Error::ContractError(err),
+    }
+}
+
+/// Get the token ID that the next mint is assigned under the same block and overrides.
+async fn next_token_id<N, P>(
+    npm: Address,
+    provider: P,
+    options: &CallOptions,
+) -> Result<U256, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let options = CallOptions {
+        block_id: options.block_id,
+        state: options.state.clone(),
+        ..Default::default()
+    };
+    let slot = B256::from(U256::from(NPM_NEXT_ID_SLOT));
+    let values = get_storage_at(npm, vec![slot], provider, options).await?;
+    let word = values.first().ok_or(Error::InvalidSlots)?;
+    // `_nextId` is a `uint176` packed with `_nextPoolId`
+    Ok(extract_field(U256::from_be_bytes(word.0), 0, 22))
+}
+
+/// Run `call` followed by `positions(tokenId)` in a `multicall` of the NPM.
+async fn call_with_position<C, N, P>(
+    npm: Address,
+    call: C,
+    token_id: U256,
+    provider: P,
+    options: CallOptions,
+) -> Result<(C::Return, MintedPosition), Error>
+where
+    C: SolCall,
+    N: Network,
+    P: Provider<N>,
+{
+    let data = vec![
+        call.abi_encode().into(),
+        positionsCall { tokenId: token_id }.abi_encode().into(),
+    ];
+    let multicall = IMulticall::new(npm, provider);
+    let results = options
+        .apply(multicall.multicall(data))
+        .call()
+        .await
+        .map_err(revert_reason)?;
+    let [returned, position] = results.as_slice() else {
+        return Err(AbiError::Overrun.into());
+    };
+    Ok((
+        C::abi_decode_returns(returned)?,
+        positionsCall::abi_decode_returns(position)?.into(),
+    ))
+}
+
+/// Simulate minting a position.
+///
+/// ## Arguments
+///
+/// * `npm`: The address of the non-fungible position manager
+/// * `sender`: The sender of the mint
+/// * `params`: The parameters of the mint, whose `deadline` must not have passed
+/// * `funding`: The token balances and approvals to inject for the sender, empty to use the actual
+///   ones
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] to query with, e.g. the ether `value` to pay
+///   with, which is also set as the native balance of the sender unless overridden
+///
+/// ## Returns
+///
+/// The token ID, liquidity, amounts and resulting position of the mint, [`Error::Reverted`], or
+/// [`Error::TokenIdMismatch`] if the NPM does not store the next token ID as expected
+#[inline]
+pub async fn simulate_mint<N, P>(
+    npm: Address,
+    sender: Address,
+    params: MintParams,
+    funding: &[Funding],
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<MintSimulation, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let mut options = simulation_options(npm, sender, funding, options)
+        .resolve(&provider)
+        .await?;
+    // predict the token ID and mint at the same block
+    if options.block_id.is_none() {
+        options = options.block(BlockId::number(provider.get_block_number().await?));
+    }
+    let token_id = next_token_id(npm, &provider, &options).await?;
+    let (minted, position) =
+        call_with_position(npm, mintCall { params }, token_id, provider, options).await?;
+    // the NPM does not have the layout of `_nextId` assumed to predict the token ID
+    if minted.tokenId != token_id {
+        return Err(Error::TokenIdMismatch(token_id, minted.tokenId));
+    }
+    Ok(MintSimulation {
+        token_id,
+        liquidity: minted.liquidity,
+        amount0: minted.amount0,
+        amount1: minted.amount1,
+        position,
+    })
+}
+
+/// Simulate increasing the liquidity of a position.
+///
+/// ## Arguments
+///
+/// * `npm`: The address of the non-fungible position manager
+/// * `sender`: The sender of the increase
+/// * `params`: The parameters of the increase, whose `deadline` must not have passed
+/// * `funding`: The token balances and approvals to inject for the sender, empty to use the actual
+///   ones
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The liquidity and amounts added and the resulting position, or [`Error::Reverted`]
+#[inline]
+pub async fn simulate_increase_liquidity<N, P>(
+    npm: Address,
+    sender: Address,
+    params: IncreaseLiquidityParams,
+    funding: &[Funding],
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<MintSimulation, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let token_id = params.tokenId;
+    let options = simulation_options(npm, sender, funding, options)
+        .resolve(&provider)
+        .await?;
+    let (increased, position) = call_with_position(
+        npm,
+        increaseLiquidityCall { params },
+        token_id,
+        provider,
+        options,
+    )
+    .await?;
+    Ok(MintSimulation {
+        token_id,
+        liquidity: increased.liquidity,
+        amount0: increased.amount0,
+        amount1: increased.amount1,
+        position,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::{
+            ephemeralstoragelens::EphemeralStorageLens::extsloadCall,
+            imulticall::IMulticall::multicallCall,
+            iuniswapv3nonfungiblepositionmanager::IUniswapV3NonfungiblePositionManager::mintReturn,
+        },
+        tests::*,
+    };
+    use alloy::{
+        primitives::{address, Bytes, U64},
+        providers::ProviderBuilder,
+        rpc::json_rpc::ErrorPayload,
+        sol_types::SolCall,
+        transports::mock::Asserter,
+    };
+
+    const NPM_ADDRESS: Address = address!("C36442b4a4522E871399CD717aBDD847Ab11FE88");
+    const USDC: Address = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+    fn mint_params(sender: Address) -> MintParams {
+        MintParams {
+            token0: USDC,
+            token1: WETH,
+            fee: U24::from(500),
+            tickLower: I24::unchecked_from(200000),
+            tickUpper: I24::unchecked_from(210000),
+            amount0Desired: U256::from(10_000_000_000_u64),
+            amount1Desired: U256::from(10_u128.pow(19)),
+            amount0Min: U256::ZERO,
+            amount1Min: U256::ZERO,
+            recipient: sender,
+            deadline: U256::MAX,
+        }
+    }
+
+    fn funding(amount0: U256, amount1: U256) -> [Funding; 2] {
+        [
+            Funding {
+                token: USDC,
+                amount: amount0,
+                balances_slot: U256::from(9),
+                allowances_slot: U256::from(10),
+            },
+            Funding {
+                token: WETH,
+                amount: amount1,
+                balances_slot: U256::from(3),
+                allowances_slot: U256::from(4),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_simulation_options() {
+        let sender = Address::repeat_byte(1);
+        let value = U256::from(10_u128.pow(18));
+        let options = simulation_options(
+            NPM_ADDRESS,
+            sender,
+            &funding(U256::from(1), U256::from(2)),
+            CallOptions::default().value(value),
+        );
+        assert_eq!(options.from, Some(sender));
+        let state = options.state.unwrap();
+        // the native balance pays for the value of the call
+        assert_eq!(state[&sender].balance, Some(value));
+        assert!(state[&USDC].state_diff.is_some() && state[&WETH].state_diff.is_some());
+        // an overridden balance is kept
+        let options = simulation_options(
+            NPM_ADDRESS,
+            sender,
+            &[],
+            CallOptions::default()
+                .value(value)
+                .state(WhatIf::new().balance(sender, U256::MAX).build()),
+        );
+        assert_eq!(options.state.unwrap()[&sender].balance, Some(U256::MAX));
+        let options = simulation_options(NPM_ADDRESS, sender, &[], None);
+        assert!(options.state.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_simulate_mint_mocked() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let sender = Address::repeat_byte(1);
+        let params = mint_params(sender);
+        let position = MintedPosition {
+            token0: params.token0,
+            token1: params.token1,
+            fee: params.fee,
+            tick_lower: params.tickLower,
+            tick_upper: params.tickUpper,
+            liquidity: 1 << 60,
+            ..Default::default()
+        };
+        let minted = MintSimulation {
+            token_id: U256::from(500000),
+            liquidity: 1 << 60,
+            amount0: U256::from(1),
+            amount1: U256::from(2),
+            position,
+        };
+        // `_nextId` packed with `_nextPoolId`
+        let next_id = B256::from(U256::from(500000) | (U256::from(1000) << 176));
+        let block_number = U64::from(17000000);
+        asserter.push_success(&block_number);
+        asserter.push_success(&Bytes::from(extsloadCall::abi_encode_returns(&vec![
+            next_id,
+        ])));
+        let results = vec![
+            Bytes::from(mintCall::abi_encode_returns(&mintReturn {
+                tokenId: minted.token_id,
+                liquidity: minted.liquidity,
+                amount0: minted.amount0,
+                amount1: minted.amount1,
+            })),
+            Bytes::from(positionsCall::abi_encode_returns(&positionsReturn {
+                nonce: position.nonce,
+                operator: position.operator,
+                token0: position.token0,
+                token1: position.token1,
+                fee: position.fee,
+                tickLower: position.tick_lower,
+                tickUpper: position.tick_upper,
+                liquidity: position.liquidity,
+                feeGrowthInside0LastX128: position.fee_growth_inside0_last_x128,
+                feeGrowthInside1LastX128: position.fee_growth_inside1_last_x128,
+                tokensOwed0: position.tokens_owed0,
+                tokensOwed1: position.tokens_owed1,
+            })),
+        ];
+        asserter.push_success(&Bytes::from(multicallCall::abi_encode_returns(&results)));
+        // the predicted token ID is off by one
+        asserter.push_success(&block_number);
+        asserter.push_success(&Bytes::from(extsloadCall::abi_encode_returns(&vec![
+            B256::from(U256::from(499999)),
+        ])));
+        asserter.push_success(&Bytes::from(multicallCall::abi_encode_returns(&results)));
+        asserter.push_success(&block_number);
+        asserter.push_success(&Bytes::from(extsloadCall::abi_encode_returns(&vec![
+            next_id,
+        ])));
+        let revert_data = Bytes::from(Revert::from("Price slippage check").abi_encode());
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::value::to_raw_value(&revert_data).unwrap()),
+        });
+        let (amount0, amount1) = (params.amount0Desired, params.amount1Desired);
+        let res = simulate_mint(
+            NPM_ADDRESS,
+            sender,
+            params.clone(),
+            &funding(amount0, amount1),
+            &provider,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res, minted);
+        let res = simulate_mint(NPM_ADDRESS, sender, params.clone(), &[], &provider, None).await;
+        assert!(matches!(
+            res,
+            Err(Error::TokenIdMismatch(predicted, token_id))
+                if predicted == U256::from(499999) && token_id == minted.token_id
+        ));
+        let res = simulate_mint(NPM_ADDRESS, sender, params, &[], &provider, None).await;
+        assert!(matches!(res, Err(Error::Reverted(reason)) if reason == "Price slippage check"));
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_simulate_mint() {
+        let provider = PROVIDER.clone();
+        let sender = Address::repeat_byte(1);
+        let params = mint_params(sender);
+        let (amount0, amount1) = (params.amount0Desired, params.amount1Desired);
+        let funding = funding(amount0, amount1);
+        let minted = simulate_mint(
+            NPM_ADDRESS,
+            sender,
+            params,
+            &funding,
+            &provider,
+            BLOCK_NUMBER,
+        )
+        .await
+        .unwrap();
+        assert_ne!(minted.liquidity, 0);
+        assert!(minted.amount0 <= amount0 && minted.amount1 <= amount1);
+        assert_eq!(minted.position.liquidity, minted.liquidity);
+        assert_eq!(minted.position.tick_lower, mint_params(sender).tickLower);
+        let increased = simulate_increase_liquidity(
+            NPM_ADDRESS,
+            sender,
+            IncreaseLiquidityParams {
+                tokenId: U256::from(4),
+                amount0Desired: amount0,
+                amount1Desired: amount1,
+                amount0Min: U256::ZERO,
+                amount1Min: U256::ZERO,
+                deadline: U256::MAX,
+            },
+            &funding,
+            &provider,
+            BLOCK_NUMBER,
+        )
+        .await
+        .unwrap();
+        assert_eq!(increased.token_id, U256::from(4));
+        assert!(increased.position.liquidity >= increased.liquidity);
+        // without the funding, the transfer of the tokens fails
+        let res = simulate_mint(
+            NPM_ADDRESS,
+            sender,
+            mint_params(sender),
+            &[],
+            &provider,
+            BLOCK_NUMBER,
+        )
+        .await;
+        assert!(matches!(res, Err(Error::Reverted(_))));
+    }
+}
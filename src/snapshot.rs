@@ -0,0 +1,521 @@
+//! ## Snapshot
+//!
+//! The snapshot module captures the complete state of a pool at a block, i.e. the static slots,
+//! every populated tick, the tick bitmap and the immutable parameters of the pool, so that
+//! backtests and simulations can be reproduced without an archive node. A snapshot is written to a
+//! compact, versioned binary format, or to JSON with the `serde` feature, and loads back into a
+//! [`PoolState`] and an [`Oracle`].
+
+use crate::{
+    bindings::{ephemeralpoolslots::PoolUtils::Slot, iuniswapv3pool::IUniswapV3Pool},
+    caller::CallOptions,
+    error::Error,
+    math::{MAX_TICK, MIN_TICK},
+    oracle::Oracle,
+    pool_lens::{get_static_slots, get_tick_bitmap_slots, get_ticks_slots},
+    pool_state::{word_positions, PoolState},
+    storage_slot::{get_tick_bitmap_slot, get_tick_slot},
+};
+use alloc::vec::Vec;
+use alloy::{
+    eips::BlockId,
+    network::Network,
+    primitives::{
+        aliases::{I24, U24},
+        Address, U256,
+    },
+    providers::Provider,
+    sol_types::SolValue,
+};
+use core::fmt;
+
+/// The serialization format version of [`PoolSnapshot`].
+const SNAPSHOT_VERSION: u8 = 1;
+
+/// The complete state of a pool at the end of a block.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "json::Versioned", try_from = "json::Versioned")
+)]
+pub struct PoolSnapshot {
+    /// The address of the pool
+    pub pool: Address,
+    /// The first of the two tokens of the pool, sorted by address
+    pub token0: Address,
+    /// The second of the two tokens of the pool, sorted by address
+    pub token1: Address,
+    /// The fee collected upon every swap in the pool, denominated in hundredths of a bip
+    pub fee: U24,
+    /// The minimum number of ticks between initialized ticks
+    pub tick_spacing: I24,
+    /// The block number of the snapshot
+    pub block_number: u64,
+    /// The slots returned by [`get_static_slots`]
+    pub static_slots: Vec<Slot>,
+    /// The slots returned by [`get_ticks_slots`] over the full tick range
+    pub ticks_slots: Vec<Slot>,
+    /// The slots returned by [`get_tick_bitmap_slots`]
+    pub tick_bitmap_slots: Vec<Slot>,
+}
+
+impl fmt::Debug for PoolSnapshot {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the sol! generated slots do not implement `Debug`
+        f.debug_struct("PoolSnapshot")
+            .field("pool", &self.pool)
+            .field("token0", &self.token0)
+            .field("token1", &self.token1)
+            .field("fee", &self.fee)
+            .field("tick_spacing", &self.tick_spacing)
+            .field("block_number", &self.block_number)
+            .field("static_slots", &self.static_slots.len())
+            .field("ticks_slots", &self.ticks_slots.len())
+            .field("tick_bitmap_slots", &self.tick_bitmap_slots.len())
+            .finish()
+    }
+}
+
+impl PoolSnapshot {
+    /// Fetch a snapshot of a pool at the end of a block.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The address of a V3 pool
+    /// * `block_number`: The block number to query
+    /// * `provider`: The alloy provider
+    /// * `options`: Optional [`CallOptions`] to query with, e.g. a gas limit for reading every
+    ///   tick, whose block is replaced by `block_number`
+    ///
+    /// ## Returns
+    ///
+    /// The pool snapshot
+    #[inline]
+    pub async fn fetch<N, P>(
+        pool: Address,
+        block_number: u64,
+        provider: P,
+        options: impl Into<CallOptions>,
+    ) -> Result<Self, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        let options = options.into().block(BlockId::number(block_number));
+        let contract = IUniswapV3Pool::new(pool, &provider);
+        Ok(Self {
+            pool,
+            token0: options.clone().apply(contract.token0()).call().await?,
+            token1: options.clone().apply(contract.token1()).call().await?,
+            fee: options.clone().apply(contract.fee()).call().await?,
+            tick_spacing: options.clone().apply(contract.tickSpacing()).call().await?,
+            block_number,
+            static_slots: get_static_slots(pool, &provider, options.clone()).await?,
+            ticks_slots: get_ticks_slots(pool, MIN_TICK, MAX_TICK, &provider, options.clone())
+                .await?,
+            tick_bitmap_slots: get_tick_bitmap_slots(pool, &provider, options).await?,
+        })
+    }
+
+    /// Decode the pool state at the block of the snapshot.
+    #[inline]
+    pub fn to_state(&self) -> Result<PoolState, Error> {
+        let mut state = PoolState::from_slots(
+            self.pool,
+            self.fee,
+            self.tick_spacing,
+            &self.static_slots,
+            &self.ticks_slots,
+            &self.tick_bitmap_slots,
+        )?;
+        state.block_number = self.block_number;
+        Ok(state)
+    }
+
+    /// Decode the oracle state at the block of the snapshot.
+    #[inline]
+    pub fn to_oracle(&self) -> Result<Oracle, Error> {
+        Oracle::from_static_slots(&self.static_slots)
+    }
+
+    /// Serialize the snapshot into a versioned, ABI encoded byte array.
+    ///
+    /// Only the non-empty bitmap words and the data of the tick slots are encoded, since the slot
+    /// numbers of the ticks and the bitmap are derived from the tick spacing.
+    ///
+    /// ## Returns
+    ///
+    /// The encoded snapshot, or [`Error::InvalidSlots`] if the bitmap words are not in ascending
+    /// order or the tick slots are not those of the populated ticks in the order of the bitmap
+    #[inline]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let tick_bitmap: Vec<(U256, U256)> = self
+            .tick_bitmap_slots
+            .iter()
+            .filter(|slot| !slot.data.is_zero())
+            .map(|slot| (slot.slot, slot.data))
+            .collect();
+        self.check_ticks_slots(&tick_bitmap)?;
+        let ticks: Vec<U256> = self.ticks_slots.iter().map(|slot| slot.data).collect();
+        let static_slots: Vec<(U256, U256)> = self
+            .static_slots
+            .iter()
+            .map(|slot| (slot.slot, slot.data))
+            .collect();
+        let mut bytes = Vec::from([SNAPSHOT_VERSION]);
+        bytes.extend(
+            (
+                self.pool,
+                self.token0,
+                self.token1,
+                self.fee,
+                self.tick_spacing,
+                self.block_number,
+                static_slots,
+                tick_bitmap,
+                ticks,
+            )
+                .abi_encode_params(),
+        );
+        Ok(bytes)
+    }
+
+    /// Check that the tick slots belong to the populated ticks of the non-empty bitmap words in
+    /// order, so that their slot numbers can be derived from the bitmap when decoding.
+    fn check_ticks_slots(&self, tick_bitmap: &[(U256, U256)]) -> Result<(), Error> {
+        let spacing = self.tick_spacing.as_i32();
+        if spacing <= 0 {
+            return Err(Error::InvalidTick);
+        }
+        let (word_pos_lower, word_pos_upper) = word_positions(spacing);
+        let mut words = tick_bitmap.iter().peekable();
+        let mut chunks = self.ticks_slots.chunks_exact(4);
+        for word_pos in word_pos_lower..=word_pos_upper {
+            let data = match words.peek() {
+                Some(&&(slot, data)) if slot == get_tick_bitmap_slot(word_pos as i16) => {
+                    words.next();
+                    data
+                }
+                _ => continue,
+            };
+            for bit_pos in (0..256).filter(|&bit_pos| data.bit(bit_pos)) {
+                let tick = I24::unchecked_from(((word_pos << 8) | bit_pos as i32) * spacing);
+                let slot = get_tick_slot(tick);
+                let slots = chunks.next().ok_or(Error::InvalidSlots)?;
+                if slots
+                    .iter()
+                    .enumerate()
+                    .any(|(i, s)| s.slot != slot + U256::from(i))
+                {
+                    return Err(Error::InvalidSlots);
+                }
+            }
+        }
+        if words.next().is_some() || chunks.next().is_some() || !chunks.remainder().is_empty() {
+            return Err(Error::InvalidSlots);
+        }
+        Ok(())
+    }
+
+    /// Deserialize a snapshot from the output of [`PoolSnapshot::to_bytes`].
+    #[inline]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let Some((&SNAPSHOT_VERSION, data)) = bytes.split_first() else {
+            return Err(Error::InvalidVersion);
+        };
+        type Encoded = (
+            Address,
+            Address,
+            Address,
+            U24,
+            I24,
+            u64,
+            Vec<(U256, U256)>,
+            Vec<(U256, U256)>,
+            Vec<U256>,
+        );
+        let (
+            pool,
+            token0,
+            token1,
+            fee,
+            tick_spacing,
+            block_number,
+            static_slots,
+            tick_bitmap,
+            ticks,
+        ) = Encoded::abi_decode_params(data)?;
+        let spacing = tick_spacing.as_i32();
+        if spacing <= 0 {
+            return Err(Error::InvalidTick);
+        }
+
+        // restore the empty bitmap words
+        let (word_pos_lower, word_pos_upper) = word_positions(spacing);
+        let mut words = tick_bitmap.into_iter().peekable();
+        let mut tick_bitmap_slots =
+            Vec::with_capacity((word_pos_upper - word_pos_lower + 1) as usize);
+        let mut populated = Vec::new();
+        for word_pos in word_pos_lower..=word_pos_upper {
+            let slot = get_tick_bitmap_slot(word_pos as i16);
+            let data = match words.peek() {
+                Some(&(s, data)) if s == slot => {
+                    words.next();
+                    data
+                }
+                _ => U256::ZERO,
+            };
+            for bit_pos in 0..256 {
+                if data.bit(bit_pos) {
+                    populated.push(((word_pos << 8) | bit_pos as i32) * spacing);
+                }
+            }
+            tick_bitmap_slots.push(Slot { slot, data });
+        }
+        if words.next().is_some() || ticks.len() != populated.len() << 2 {
+            return Err(Error::InvalidSlots);
+        }
+
+        // restore the slot numbers of the populated ticks
+        let ticks_slots = populated
+            .into_iter()
+            .flat_map(|tick| {
+                let slot = get_tick_slot(I24::unchecked_from(tick));
+                (0..4).map(move |i| slot + U256::from(i))
+            })
+            .zip(ticks)
+            .map(|(slot, data)| Slot { slot, data })
+            .collect();
+
+        Ok(Self {
+            pool,
+            token0,
+            token1,
+            fee,
+            tick_spacing,
+            block_number,
+            static_slots: static_slots
+                .into_iter()
+                .map(|(slot, data)| Slot { slot, data })
+                .collect(),
+            ticks_slots,
+            tick_bitmap_slots,
+        })
+    }
+}
+
+/// The JSON representation of a snapshot, tagged with the format version.
+#[cfg(feature = "serde")]
+mod json {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct Versioned {
+        version: u8,
+        pool: Address,
+        token0: Address,
+        token1: Address,
+        #[serde(with = "crate::serde_helpers::fee")]
+        fee: U24,
+        #[serde(with = "crate::serde_helpers::tick")]
+        tick_spacing: I24,
+        block_number: u64,
+        static_slots: Vec<Slot>,
+        ticks_slots: Vec<Slot>,
+        tick_bitmap_slots: Vec<Slot>,
+    }
+
+    impl From<PoolSnapshot> for Versioned {
+        #[inline]
+        fn from(snapshot: PoolSnapshot) -> Self {
+            Self {
+                version: SNAPSHOT_VERSION,
+                pool: snapshot.pool,
+                token0: snapshot.token0,
+                token1: snapshot.token1,
+                fee: snapshot.fee,
+                tick_spacing: snapshot.tick_spacing,
+                block_number: snapshot.block_number,
+                static_slots: snapshot.static_slots,
+                ticks_slots: snapshot.ticks_slots,
+                tick_bitmap_slots: snapshot.tick_bitmap_slots,
+            }
+        }
+    }
+
+    impl TryFrom<Versioned> for PoolSnapshot {
+        type Error = Error;
+
+        #[inline]
+        fn try_from(versioned: Versioned) -> Result<Self, Error> {
+            if versioned.version != SNAPSHOT_VERSION {
+                return Err(Error::InvalidVersion);
+            }
+            Ok(Self {
+                pool: versioned.pool,
+                token0: versioned.token0,
+                token1: versioned.token1,
+                fee: versioned.fee,
+                tick_spacing: versioned.tick_spacing,
+                block_number: versioned.block_number,
+                static_slots: versioned.static_slots,
+                ticks_slots: versioned.ticks_slots,
+                tick_bitmap_slots: versioned.tick_bitmap_slots,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use alloy::primitives::address;
+
+    const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+
+    /// A pool at tick 0 with a position of liquidity 1000 in [-120, 120).
+    fn mock_snapshot() -> PoolSnapshot {
+        let slot = |slot: U256, data: U256| Slot { slot, data };
+        let tick_slots = |tick: i32, liquidity_net: i128| {
+            let s = get_tick_slot(I24::unchecked_from(tick));
+            [
+                slot(
+                    s,
+                    U256::from(1000) | (U256::from(liquidity_net as u128) << 128_usize),
+                ),
+                slot(s + U256::from(1), U256::ZERO),
+                slot(s + U256::from(2), U256::ZERO),
+                slot(s + U256::from(3), U256::ZERO),
+            ]
+        };
+        let (lower, upper) = word_positions(60);
+        let tick_bitmap_slots = (lower..=upper)
+            .map(|word_pos| {
+                let data = match word_pos {
+                    -1 => U256::from(1) << 254_usize,
+                    0 => U256::from(1) << 2_usize,
+                    _ => U256::ZERO,
+                };
+                slot(get_tick_bitmap_slot(word_pos as i16), data)
+            })
+            .collect();
+        PoolSnapshot {
+            pool: POOL_ADDRESS,
+            token0: Address::repeat_byte(1),
+            token1: Address::repeat_byte(2),
+            fee: U24::from(3000),
+            tick_spacing: I24::try_from(60).unwrap(),
+            block_number: 1,
+            static_slots: vec![
+                slot(U256::ZERO, U256::from(1) << 96_usize),
+                slot(U256::from(1), U256::from(5)),
+                slot(U256::from(2), U256::ZERO),
+                slot(U256::from(3), U256::ZERO),
+                slot(U256::from(4), U256::from(1000)),
+            ],
+            ticks_slots: tick_slots(-120, 1000)
+                .into_iter()
+                .chain(tick_slots(120, -1000))
+                .collect(),
+            tick_bitmap_slots,
+        }
+    }
+
+    fn assert_slots_eq(a: &[Slot], b: &[Slot]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.slot, b.slot);
+            assert_eq!(a.data, b.data);
+        }
+    }
+
+    fn assert_snapshot_eq(a: &PoolSnapshot, b: &PoolSnapshot) {
+        assert_eq!(a.pool, b.pool);
+        assert_eq!(a.token0, b.token0);
+        assert_eq!(a.token1, b.token1);
+        assert_eq!(a.fee, b.fee);
+        assert_eq!(a.tick_spacing, b.tick_spacing);
+        assert_eq!(a.block_number, b.block_number);
+        assert_slots_eq(&a.static_slots, &b.static_slots);
+        assert_slots_eq(&a.ticks_slots, &b.ticks_slots);
+        assert_slots_eq(&a.tick_bitmap_slots, &b.tick_bitmap_slots);
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let snapshot = mock_snapshot();
+        let mut bytes = snapshot.to_bytes().unwrap();
+        let decoded = PoolSnapshot::from_bytes(&bytes).unwrap();
+        assert_snapshot_eq(&snapshot, &decoded);
+
+        let state = decoded.to_state().unwrap();
+        assert_eq!(state.block_number, 1);
+        assert_eq!(state.liquidity, 1000);
+        assert_eq!(state.ticks.len(), 2);
+        assert_eq!(state.tick_bitmap.len(), 2);
+        state.verify(&snapshot.to_state().unwrap()).unwrap();
+
+        bytes[0] = SNAPSHOT_VERSION + 1;
+        assert!(matches!(
+            PoolSnapshot::from_bytes(&bytes),
+            Err(Error::InvalidVersion)
+        ));
+        assert!(matches!(
+            PoolSnapshot::from_bytes(&[]),
+            Err(Error::InvalidVersion)
+        ));
+    }
+
+    #[test]
+    fn test_to_bytes_ticks_order() {
+        // the ticks are not in the order of the bitmap
+        let mut snapshot = mock_snapshot();
+        snapshot.ticks_slots.rotate_left(4);
+        assert!(matches!(snapshot.to_bytes(), Err(Error::InvalidSlots)));
+        // a populated tick is missing
+        let mut snapshot = mock_snapshot();
+        snapshot.ticks_slots.truncate(4);
+        assert!(matches!(snapshot.to_bytes(), Err(Error::InvalidSlots)));
+        // a tick is not populated in the bitmap
+        let mut snapshot = mock_snapshot();
+        snapshot.ticks_slots.extend(mock_snapshot().ticks_slots);
+        assert!(matches!(snapshot.to_bytes(), Err(Error::InvalidSlots)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_roundtrip() {
+        let snapshot = mock_snapshot();
+        let mut json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["version"], SNAPSHOT_VERSION);
+        assert_eq!(json["tickSpacing"], 60);
+        let decoded: PoolSnapshot = serde_json::from_value(json.clone()).unwrap();
+        assert_snapshot_eq(&snapshot, &decoded);
+
+        json["version"] = (SNAPSHOT_VERSION + 1).into();
+        assert!(serde_json::from_value::<PoolSnapshot>(json).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let snapshot = PoolSnapshot::fetch(
+            POOL_ADDRESS,
+            block_number,
+            &*PROVIDER,
+            CallOptions::default().gas(100_000_000),
+        )
+        .await
+        .unwrap();
+        let decoded = PoolSnapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap();
+        assert_snapshot_eq(&snapshot, &decoded);
+        let state = decoded.to_state().unwrap();
+        assert_eq!(state.block_number, block_number);
+        state.verify_with_lens(&*PROVIDER, None).await.unwrap();
+        assert!(snapshot.to_oracle().is_ok());
+    }
+}
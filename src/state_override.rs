@@ -0,0 +1,651 @@
+//! ## State Override
+//!
+//! The state override module builds the `eth_call` state overrides to run any lens under
+//! hypothetical state, e.g. a pool at a different price, a position with more liquidity or an
+//! account holding tokens. The values are written through `stateDiff` to the storage slots of the
+//! V3 layouts, leaving the rest of the storage untouched, and the overrides are passed to the lens
+//! functions with [`CallOptions::state`]. Values stored at token-specific slots, such as the
+//! decimals of a token, are written with [`WhatIf::storage`] at the slot planned by
+//! [`StorageLayout::plan`](crate::storage_layout::StorageLayout::plan) and packed with
+//! [`insert_field`].
+
+use crate::{
+    caller::CallOptions,
+    error::Error,
+    math::{add_delta, get_tick_at_sqrt_ratio},
+    pool_state::PoolState,
+    storage_lens::get_storage_at,
+    storage_slot::{
+        extract_field, get_npm_position_slot, get_tick_slot, insert_field, mapping_slot,
+        nested_mapping_slot,
+    },
+};
+use alloc::vec::Vec;
+use alloy::{
+    network::Network,
+    primitives::{
+        aliases::{I24, U160, U80, U96},
+        Address, Bytes, B256, U256,
+    },
+    providers::Provider,
+    rpc::types::state::StateOverride,
+};
+
+/// A position in the `_positions` mapping of `NonfungiblePositionManager`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NpmPosition {
+    /// The nonce for permits
+    pub nonce: U96,
+    /// The address approved to spend the position
+    pub operator: Address,
+    /// The ID of the pool in `_poolIdToPoolKey`
+    pub pool_id: U80,
+    /// The lower tick of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_lower: I24,
+    /// The upper tick of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::tick"))]
+    pub tick_upper: I24,
+    /// The liquidity of the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub liquidity: u128,
+    /// The fee growth of token0 inside the range as of the last update
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside0_last_x128: U256,
+    /// The fee growth of token1 inside the range as of the last update
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub fee_growth_inside1_last_x128: U256,
+    /// The uncollected amount of token0 owed to the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed0: u128,
+    /// The uncollected amount of token1 owed to the position
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub tokens_owed1: u128,
+}
+
+/// Encode a tick as the raw 24 bits stored in a packed slot.
+fn tick_bits(tick: I24) -> U256 {
+    U256::from(tick.as_i32() as u32 & 0xffffff)
+}
+
+impl NpmPosition {
+    /// Decode a position from its 5 storage slots.
+    ///
+    /// ## Arguments
+    ///
+    /// * `slots`: The values of the storage slots starting at [`get_npm_position_slot`]
+    ///
+    /// ## Returns
+    ///
+    /// The position
+    #[inline]
+    pub fn from_slots(slots: &[U256; 5]) -> Self {
+        Self {
+            nonce: extract_field(slots[0], 0, 12).to(),
+            operator: Address::from_word(extract_field(slots[0], 12, 20).into()),
+            pool_id: extract_field(slots[1], 0, 10).to(),
+            tick_lower: I24::from_raw(extract_field(slots[1], 10, 3).to()),
+            tick_upper: I24::from_raw(extract_field(slots[1], 13, 3).to()),
+            liquidity: extract_field(slots[1], 16, 16).to(),
+            fee_growth_inside0_last_x128: slots[2],
+            fee_growth_inside1_last_x128: slots[3],
+            tokens_owed0: extract_field(slots[4], 0, 16).to(),
+            tokens_owed1: extract_field(slots[4], 16, 16).to(),
+        }
+    }
+
+    /// Encode a position into its 5 storage slots.
+    ///
+    /// ## Returns
+    ///
+    /// The values of the storage slots starting at [`get_npm_position_slot`]
+    #[inline]
+    pub fn to_slots(&self) -> [U256; 5] {
+        let slot0 = insert_field(
+            U256::from(self.nonce),
+            12,
+            20,
+            self.operator.into_word().into(),
+        );
+        let mut slot1 = insert_field(U256::from(self.pool_id), 10, 3, tick_bits(self.tick_lower));
+        slot1 = insert_field(slot1, 13, 3, tick_bits(self.tick_upper));
+        slot1 = insert_field(slot1, 16, 16, U256::from(self.liquidity));
+        [
+            slot0,
+            slot1,
+            self.fee_growth_inside0_last_x128,
+            self.fee_growth_inside1_last_x128,
+            insert_field(
+                U256::from(self.tokens_owed0),
+                16,
+                16,
+                U256::from(self.tokens_owed1),
+            ),
+        ]
+    }
+}
+
+/// Get a position from the storage of `NonfungiblePositionManager`, including the `poolId` and
+/// `nonce` required to override it with [`WhatIf::npm_position`].
+///
+/// ## Arguments
+///
+/// * `npm`: The address of the non-fungible position manager
+/// * `token_id`: The token ID of the position
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The position
+#[inline]
+pub async fn get_npm_position<N, P>(
+    npm: Address,
+    token_id: U256,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<NpmPosition, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let slot = get_npm_position_slot(token_id);
+    let slots = (0..5_u8)
+        .map(|i| B256::from(slot + U256::from(i)))
+        .collect();
+    let values = get_storage_at(npm, slots, provider, options).await?;
+    let values: Vec<U256> = values
+        .into_iter()
+        .map(|value| U256::from_be_bytes(value.0))
+        .collect();
+    Ok(NpmPosition::from_slots(
+        &values.try_into().map_err(|_| Error::InvalidSlots)?,
+    ))
+}
+
+/// Pack the `slot0` of a pool.
+fn slot0_word(pool: &PoolState, sqrt_price_x96: U160, tick: I24) -> U256 {
+    let mut word = U256::from(sqrt_price_x96);
+    word = insert_field(word, 20, 3, tick_bits(tick));
+    word = insert_field(word, 23, 2, U256::from(pool.observation_index));
+    word = insert_field(word, 25, 2, U256::from(pool.observation_cardinality));
+    word = insert_field(word, 27, 2, U256::from(pool.observation_cardinality_next));
+    word = insert_field(word, 29, 1, U256::from(pool.fee_protocol));
+    insert_field(word, 30, 1, U256::from(pool.unlocked))
+}
+
+/// A builder of state overrides for hypothetical V3 state.
+///
+/// ## Example
+///
+/// Value every position of an owner if ETH drops 30% against USDC. The sqrt price is
+/// `sqrt(token1 / token0)`, so with USDC as token0 the price of USDC in WETH rises by `1 / 0.7`:
+///
+/// ```no_run
+/// use alloy::{
+///     primitives::{address, U256},
+///     providers::ProviderBuilder,
+/// };
+/// use uniswap_lens::{math::mul_div, prelude::*};
+///
+/// # async fn run() -> Result<(), Error> {
+/// let provider = ProviderBuilder::new().connect_http("http://localhost:8545".parse().unwrap());
+/// let npm = address!("C36442b4a4522E871399CD717aBDD847Ab11FE88");
+/// let owner = address!("1F98431c8aD98523631AE4a59f267346ea31F984");
+/// let block_number = 17000000;
+/// let pool = PoolState::fetch(
+///     address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+///     block_number,
+///     &provider,
+///     None,
+/// )
+/// .await?;
+/// // the sqrt price scales with the square root of the price, sqrt(1 / 0.7) ≈ 1.19523
+/// let sqrt_price_x96 = mul_div(
+///     U256::from(pool.sqrt_price_x96),
+///     U256::from(119523),
+///     U256::from(100000),
+/// )?
+/// .to();
+/// let state = WhatIf::new().sqrt_price(&pool, sqrt_price_x96)?.build();
+/// let options = CallOptions::from(alloy::eips::BlockId::number(block_number)).state(state);
+/// let positions = get_all_positions_by_owner(npm, owner, &provider, options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WhatIf {
+    state: StateOverride,
+}
+
+impl WhatIf {
+    /// Create an empty set of overrides.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override a storage slot of an account through `stateDiff`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `address`: The address of the account
+    /// * `slot`: The storage slot
+    /// * `value`: The value of the slot
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the slot overridden
+    #[inline]
+    #[must_use]
+    pub fn storage(mut self, address: Address, slot: U256, value: U256) -> Self {
+        self.state
+            .entry(address)
+            .or_default()
+            .state_diff
+            .get_or_insert_with(Default::default)
+            .insert(slot.into(), value.into());
+        self
+    }
+
+    /// Override the ether balance of an account.
+    #[inline]
+    #[must_use]
+    pub fn balance(mut self, address: Address, balance: U256) -> Self {
+        self.state.entry(address).or_default().balance = Some(balance);
+        self
+    }
+
+    /// Override the deployed bytecode of an account.
+    #[inline]
+    #[must_use]
+    pub fn code(mut self, address: Address, code: Bytes) -> Self {
+        self.state.entry(address).or_default().code = Some(code);
+        self
+    }
+
+    /// Override the static slots of a pool, i.e. `slot0`, the global fee growth, the protocol
+    /// fees and the active liquidity, with the values of a pool state.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The hypothetical state of the pool
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the static slots of the pool overridden
+    #[inline]
+    #[must_use]
+    pub fn pool(self, pool: &PoolState) -> Self {
+        let protocol_fees = insert_field(
+            U256::from(pool.protocol_fees_token0),
+            16,
+            16,
+            U256::from(pool.protocol_fees_token1),
+        );
+        self.storage(
+            pool.address,
+            U256::ZERO,
+            slot0_word(pool, pool.sqrt_price_x96, pool.tick),
+        )
+        .storage(pool.address, U256::from(1), pool.fee_growth_global0_x128)
+        .storage(pool.address, U256::from(2), pool.fee_growth_global1_x128)
+        .storage(pool.address, U256::from(3), protocol_fees)
+        .storage(pool.address, U256::from(4), U256::from(pool.liquidity))
+    }
+
+    /// Move the price of a pool as a swap would, without fees. The initialized ticks between the
+    /// current and the new tick are crossed, updating the active liquidity and flipping the fee
+    /// growth outside of each crossed tick, so that positions and fees are valued by the exact
+    /// on-chain logic.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The current state of the pool including its initialized ticks
+    /// * `sqrt_price_x96`: The new sqrt price
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the pool moved to the new price
+    #[inline]
+    pub fn sqrt_price(mut self, pool: &PoolState, sqrt_price_x96: U160) -> Result<Self, Error> {
+        let tick = get_tick_at_sqrt_ratio(sqrt_price_x96)?;
+        let mut liquidity = pool.liquidity;
+        let crossed = if tick > pool.tick {
+            pool.ticks.range(pool.tick + I24::ONE..=tick)
+        } else {
+            pool.ticks.range(tick + I24::ONE..=pool.tick)
+        };
+        for (&crossed_tick, info) in crossed {
+            // crossing up adds the net liquidity of the tick, crossing down subtracts it
+            let liquidity_net = if tick > pool.tick {
+                info.liquidity_net
+            } else {
+                info.liquidity_net.checked_neg().ok_or(Error::Overflow)?
+            };
+            liquidity = add_delta(liquidity, liquidity_net)?;
+            let slot = get_tick_slot(crossed_tick);
+            self = self
+                .storage(
+                    pool.address,
+                    slot + U256::from(1),
+                    pool.fee_growth_global0_x128
+                        .wrapping_sub(info.fee_growth_outside0_x128),
+                )
+                .storage(
+                    pool.address,
+                    slot + U256::from(2),
+                    pool.fee_growth_global1_x128
+                        .wrapping_sub(info.fee_growth_outside1_x128),
+                );
+        }
+        Ok(self
+            .storage(
+                pool.address,
+                U256::ZERO,
+                slot0_word(pool, sqrt_price_x96, tick),
+            )
+            .storage(pool.address, U256::from(4), U256::from(liquidity)))
+    }
+
+    /// Override a position of `NonfungiblePositionManager`, e.g. one returned by
+    /// [`get_npm_position`] with a different liquidity.
+    ///
+    /// ## Arguments
+    ///
+    /// * `npm`: The address of the non-fungible position manager
+    /// * `token_id`: The token ID of the position
+    /// * `position`: The hypothetical position
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the position overridden
+    #[inline]
+    #[must_use]
+    pub fn npm_position(mut self, npm: Address, token_id: U256, position: &NpmPosition) -> Self {
+        let slot = get_npm_position_slot(token_id);
+        for (i, value) in position.to_slots().into_iter().enumerate() {
+            self = self.storage(npm, slot + U256::from(i), value);
+        }
+        self
+    }
+
+    /// Override the ERC20 balance of an owner.
+    ///
+    /// ## Arguments
+    ///
+    /// * `token`: The address of the token
+    /// * `owner`: The owner of the tokens
+    /// * `balance`: The hypothetical balance
+    /// * `balances_slot`: The storage slot of the balance mapping of the token, e.g. 3 for WETH
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the balance overridden
+    #[inline]
+    #[must_use]
+    pub fn erc20_balance(
+        self,
+        token: Address,
+        owner: Address,
+        balance: U256,
+        balances_slot: U256,
+    ) -> Self {
+        self.storage(token, mapping_slot(&owner, balances_slot), balance)
+    }
+
+    /// Override the ERC20 allowance of an owner to a spender.
+    ///
+    /// ## Arguments
+    ///
+    /// * `token`: The address of the token
+    /// * `owner`: The owner of the tokens
+    /// * `spender`: The approved spender
+    /// * `amount`: The hypothetical allowance
+    /// * `allowances_slot`: The storage slot of the allowance mapping of the token, e.g. 4 for WETH
+    ///
+    /// ## Returns
+    ///
+    /// The builder with the allowance overridden
+    #[inline]
+    #[must_use]
+    pub fn erc20_allowance(
+        self,
+        token: Address,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+        allowances_slot: U256,
+    ) -> Self {
+        self.storage(
+            token,
+            nested_mapping_slot(&owner, &spender, allowances_slot),
+            amount,
+        )
+    }
+
+    /// Build the state overrides to pass to [`CallOptions::state`].
+    #[inline]
+    pub fn build(self) -> StateOverride {
+        self.state
+    }
+}
+
+impl From<StateOverride> for WhatIf {
+    #[inline]
+    fn from(state: StateOverride) -> Self {
+        Self { state }
+    }
+}
+
+impl From<WhatIf> for StateOverride {
+    #[inline]
+    fn from(what_if: WhatIf) -> Self {
+        what_if.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{get_amounts_for_liquidity, get_sqrt_ratio_at_tick, mul_div},
+        pool_lens::get_static_slots,
+        pool_state::TickInfo,
+        position_lens::get_position_details,
+        storage_slot::extract_signed_field,
+        tests::*,
+    };
+    use alloc::collections::BTreeMap;
+    use alloy::primitives::{address, aliases::U24, uint};
+
+    const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+    const NPM_ADDRESS: Address = address!("C36442b4a4522E871399CD717aBDD847Ab11FE88");
+
+    fn slot0_tick(word: U256) -> I24 {
+        I24::unchecked_from(extract_signed_field(word, 20, 3).as_i32())
+    }
+
+    fn diff(what_if: &WhatIf, address: Address, slot: U256) -> Option<U256> {
+        what_if.state[&address]
+            .state_diff
+            .as_ref()?
+            .get(&B256::from(slot))
+            .map(|value| (*value).into())
+    }
+
+    #[test]
+    fn test_npm_position_slots() {
+        let position = NpmPosition {
+            nonce: U96::from(7),
+            operator: Address::repeat_byte(1),
+            pool_id: U80::from(3),
+            tick_lower: I24::unchecked_from(-887220),
+            tick_upper: I24::unchecked_from(60),
+            liquidity: 1 << 100,
+            fee_growth_inside0_last_x128: U256::from(11),
+            fee_growth_inside1_last_x128: U256::from(12),
+            tokens_owed0: 13,
+            tokens_owed1: u128::MAX,
+        };
+        assert_eq!(NpmPosition::from_slots(&position.to_slots()), position);
+        let what_if = WhatIf::new().npm_position(NPM_ADDRESS, U256::from(4), &position);
+        let slot = get_npm_position_slot(U256::from(4));
+        assert_eq!(
+            diff(&what_if, NPM_ADDRESS, slot + U256::from(1)),
+            Some(position.to_slots()[1])
+        );
+    }
+
+    #[test]
+    fn test_sqrt_price() {
+        let tick_info = |liquidity_net: i128| TickInfo {
+            liquidity_gross: liquidity_net.unsigned_abs(),
+            liquidity_net,
+            fee_growth_outside0_x128: U256::from(10),
+            fee_growth_outside1_x128: U256::from(20),
+        };
+        let pool = PoolState {
+            address: POOL_ADDRESS,
+            fee: U24::from(3000),
+            tick_spacing: I24::unchecked_from(60),
+            sqrt_price_x96: get_sqrt_ratio_at_tick(I24::ZERO).unwrap(),
+            observation_cardinality: 1,
+            fee_protocol: 0x44,
+            unlocked: true,
+            fee_growth_global0_x128: U256::from(100),
+            fee_growth_global1_x128: U256::from(200),
+            liquidity: 300,
+            ticks: BTreeMap::from([
+                (I24::unchecked_from(-120), tick_info(100)),
+                (I24::unchecked_from(-60), tick_info(200)),
+                (I24::unchecked_from(60), tick_info(-200)),
+                (I24::unchecked_from(120), tick_info(-100)),
+            ]),
+            ..Default::default()
+        };
+        let what_if = WhatIf::new()
+            .pool(&pool)
+            .sqrt_price(
+                &pool,
+                get_sqrt_ratio_at_tick(I24::unchecked_from(-90)).unwrap(),
+            )
+            .unwrap();
+        let slot0 = diff(&what_if, POOL_ADDRESS, U256::ZERO).unwrap();
+        assert_eq!(slot0_tick(slot0), I24::unchecked_from(-90));
+        assert_eq!(extract_field(slot0, 29, 1), U256::from(0x44));
+        assert_eq!(extract_field(slot0, 30, 1), U256::from(1));
+        // only the tick at -60 is crossed
+        assert_eq!(
+            diff(&what_if, POOL_ADDRESS, U256::from(4)),
+            Some(U256::from(100))
+        );
+        let slot = get_tick_slot(I24::unchecked_from(-60));
+        assert_eq!(
+            diff(&what_if, POOL_ADDRESS, slot + U256::from(1)),
+            Some(U256::from(90))
+        );
+        assert_eq!(
+            diff(&what_if, POOL_ADDRESS, slot + U256::from(2)),
+            Some(U256::from(180))
+        );
+        let slot = get_tick_slot(I24::unchecked_from(-120));
+        assert_eq!(diff(&what_if, POOL_ADDRESS, slot + U256::from(1)), None);
+        let what_if = WhatIf::new()
+            .sqrt_price(
+                &pool,
+                get_sqrt_ratio_at_tick(I24::unchecked_from(150)).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            diff(&what_if, POOL_ADDRESS, U256::from(4)),
+            Some(U256::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_sqrt_price_direction() {
+        // 1 WETH = 2000 USDC with USDC as token0
+        let sqrt_price_x96 = uint!(1771595571142957166518320255467520_U160);
+        let pool = PoolState {
+            address: POOL_ADDRESS,
+            fee: U24::from(500),
+            tick_spacing: I24::unchecked_from(10),
+            sqrt_price_x96,
+            tick: get_tick_at_sqrt_ratio(sqrt_price_x96).unwrap(),
+            liquidity: 10_u128.pow(18),
+            ..Default::default()
+        };
+        // ETH drops 30%
+        let dropped = mul_div(
+            U256::from(sqrt_price_x96),
+            U256::from(119523),
+            U256::from(100000),
+        )
+        .unwrap();
+        let what_if = WhatIf::new().sqrt_price(&pool, dropped.to()).unwrap();
+        let slot0 = diff(&what_if, POOL_ADDRESS, U256::ZERO).unwrap();
+        let sqrt_price_after: U160 = extract_field(slot0, 0, 20).to();
+        let eth_price = |sqrt_price_x96: U160| {
+            let sqrt_price = f64::from(U256::from(sqrt_price_x96)) / 2_f64.powi(96);
+            1e12 / (sqrt_price * sqrt_price)
+        };
+        assert!((eth_price(sqrt_price_x96) - 2000.0).abs() < 1e-6);
+        assert!((eth_price(sqrt_price_after) - 1400.0).abs() < 0.1);
+        // a full range position holds more WETH and less USDC when ETH falls
+        let (lower, upper) = (
+            get_sqrt_ratio_at_tick(I24::unchecked_from(-887270)).unwrap(),
+            get_sqrt_ratio_at_tick(I24::unchecked_from(887270)).unwrap(),
+        );
+        let (amount0_before, amount1_before) =
+            get_amounts_for_liquidity(sqrt_price_x96, lower, upper, pool.liquidity).unwrap();
+        let (amount0_after, amount1_after) =
+            get_amounts_for_liquidity(sqrt_price_after, lower, upper, pool.liquidity).unwrap();
+        assert!(amount1_after > amount1_before);
+        assert!(amount0_after < amount0_before);
+    }
+
+    #[tokio::test]
+    async fn test_what_if() {
+        let provider = PROVIDER.clone();
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let pool = PoolState::fetch(POOL_ADDRESS, block_number, &provider, None)
+            .await
+            .unwrap();
+        let sqrt_price_x96 = get_sqrt_ratio_at_tick(pool.tick - I24::unchecked_from(3000)).unwrap();
+        let state = WhatIf::new().sqrt_price(&pool, sqrt_price_x96).unwrap();
+        let options = CallOptions::from(BLOCK_NUMBER).state(state.clone().build());
+        let slots = get_static_slots(POOL_ADDRESS, &provider, options)
+            .await
+            .unwrap();
+        assert_eq!(
+            slots[0].data,
+            diff(&state, POOL_ADDRESS, U256::ZERO).unwrap()
+        );
+        assert_eq!(
+            slots[4].data,
+            diff(&state, POOL_ADDRESS, U256::from(4)).unwrap()
+        );
+
+        let token_id = U256::from(4);
+        let mut position = get_npm_position(NPM_ADDRESS, token_id, &provider, BLOCK_NUMBER)
+            .await
+            .unwrap();
+        let details = get_position_details(NPM_ADDRESS, token_id, &provider, BLOCK_NUMBER)
+            .await
+            .unwrap();
+        assert_eq!(position.liquidity, details.position.liquidity);
+        assert_eq!(position.tick_lower, details.position.tickLower);
+        position.liquidity *= 2;
+        let options = CallOptions::from(BLOCK_NUMBER).state(
+            WhatIf::new()
+                .npm_position(NPM_ADDRESS, token_id, &position)
+                .build(),
+        );
+        let details = get_position_details(NPM_ADDRESS, token_id, &provider, options)
+            .await
+            .unwrap();
+        assert_eq!(details.position.liquidity, position.liquidity);
+    }
+}
@@ -0,0 +1,897 @@
+//! ## Storage Layout
+//!
+//! The storage layout module reads the state variables of any contract given the `storageLayout`
+//! emitted by solc, e.g. `solc --storage-layout` or `forge inspect <contract> storageLayout`. A
+//! variable is requested by a path such as `owner`, `balances[0x...]`, `allowance[0x...][0x...]`,
+//! `observations[3]` or `positions[0x...].liquidity`. The path is planned into a storage slot, the
+//! slots are fetched through [`get_storage_at`] and decoded into a [`DynSolValue`], which converts
+//! into a typed struct with [`decode_storage_value`].
+//!
+//! With the `serde` feature, [`StorageLayout`] deserializes from the JSON emitted by solc.
+
+use crate::{
+    caller::CallOptions,
+    error::Error,
+    storage_lens::get_storage_at,
+    storage_slot::{
+        array_element_slot, dynamic_array_element_slot, extract_field, extract_signed_field,
+        mapping_slot,
+    },
+};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use alloy::{
+    dyn_abi::{DynSolType, DynSolValue},
+    eips::BlockId,
+    network::Network,
+    primitives::{keccak256, Address, Bytes, B256, U256},
+    providers::Provider,
+    sol_types::{SolType, SolValue},
+};
+
+/// The maximum number of slots read to decode a variable, including those of nested `bytes`,
+/// `string` and dynamic arrays, so that lengths read from a mis-planned slot or a hostile contract
+/// cannot make the decoding read and allocate up to `2^64` slots.
+pub const MAX_DECODED_SLOTS: u64 = 1 << 16;
+
+/// A state variable or a struct member in a storage layout.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StorageEntry {
+    /// The name of the variable
+    pub label: String,
+    /// The offset in bytes within the storage slot
+    pub offset: usize,
+    /// The storage slot, relative to the start of the struct for a member
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub slot: U256,
+    /// The identifier of the type in [`StorageLayout::types`]
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub ty: String,
+}
+
+/// The encoding of a type in storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum StorageEncoding {
+    /// The value is laid out contiguously from its slot
+    #[default]
+    Inplace,
+    /// The value is a mapping whose values are at `keccak256(key . slot)`
+    Mapping,
+    /// The length is stored at the slot and the elements from `keccak256(slot)`
+    DynamicArray,
+    /// A `bytes` or `string` stored in the slot if shorter than 32 bytes, or from
+    /// `keccak256(slot)`
+    Bytes,
+}
+
+/// A type in a storage layout.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StorageType {
+    /// The encoding of the type
+    pub encoding: StorageEncoding,
+    /// The canonical name of the type, e.g. `uint256` or `struct Oracle.Observation`
+    pub label: String,
+    /// The number of bytes occupied by the type
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::decimal"))]
+    pub number_of_bytes: usize,
+    /// The type of the keys of a mapping
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub key: Option<String>,
+    /// The type of the values of a mapping
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub value: Option<String>,
+    /// The type of the elements of an array
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub base: Option<String>,
+    /// The members of a struct
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub members: Option<Vec<StorageEntry>>,
+}
+
+/// The storage layout of a contract as emitted by solc.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageLayout {
+    /// The state variables of the contract
+    pub storage: Vec<StorageEntry>,
+    /// The types of the state variables by identifier
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::serde_helpers::nullable")
+    )]
+    pub types: BTreeMap<String, StorageType>,
+}
+
+/// The location of a variable in storage, as planned by [`StorageLayout::plan`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageVariable {
+    /// The first storage slot of the variable
+    pub slot: U256,
+    /// The offset in bytes within the slot
+    pub offset: usize,
+    /// The identifier of the type in [`StorageLayout::types`]
+    pub ty: String,
+}
+
+/// Compute the storage slot of a mapping key, padding value types to 32 bytes.
+fn key_slot(key: &DynSolValue, slot: U256) -> Result<U256, Error> {
+    match key {
+        DynSolValue::String(key) => Ok(mapping_slot(key, slot)),
+        DynSolValue::Bytes(key) => Ok(mapping_slot(&Bytes::copy_from_slice(key), slot)),
+        key => key
+            .as_word()
+            .map(|word| mapping_slot(&word, slot))
+            .ok_or(Error::InvalidStoragePath),
+    }
+}
+
+/// Get the length of a statically-sized array from its label, e.g. `uint256[3]`.
+fn static_array_length(label: &str) -> Result<u64, Error> {
+    label
+        .strip_suffix(']')
+        .and_then(|label| label.rsplit_once('['))
+        .and_then(|(_, length)| length.parse().ok())
+        .ok_or(Error::InvalidStorageLayout)
+}
+
+/// Decode a value type packed into a storage word.
+fn decode_word(label: &str, word: U256, offset: usize, size: usize) -> DynSolValue {
+    let bits = size * 8;
+    if label == "bool" {
+        DynSolValue::Bool(!extract_field(word, offset, size).is_zero())
+    } else if label.starts_with("address") || label.starts_with("contract ") {
+        let value = extract_field(word, offset, size);
+        DynSolValue::Address(Address::from_word(value.into()))
+    } else if label.starts_with("int") {
+        DynSolValue::Int(extract_signed_field(word, offset, size), bits)
+    } else if label.starts_with("bytes") {
+        // fixed bytes are left aligned in the ABI
+        let value = extract_field(word, offset, size) << (256 - bits);
+        DynSolValue::FixedBytes(value.into(), size)
+    } else {
+        // unsigned integers, enums and user-defined value types
+        DynSolValue::Uint(extract_field(word, offset, size), bits)
+    }
+}
+
+impl StorageLayout {
+    /// Get a type by identifier.
+    #[inline]
+    pub fn get_type(&self, ty: &str) -> Result<&StorageType, Error> {
+        self.types.get(ty).ok_or(Error::InvalidStorageLayout)
+    }
+
+    /// Plan the location of a variable in storage.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path`: The path of the variable, e.g. `positions[0x...].liquidity`, where mapping keys
+    ///   are coerced to the key type and array indices are decimal
+    ///
+    /// ## Returns
+    ///
+    /// The location of the variable, or [`Error::InvalidStoragePath`]
+    #[inline]
+    pub fn plan(&self, path: &str) -> Result<StorageVariable, Error> {
+        let end = path.find(['.', '[']).unwrap_or(path.len());
+        let (label, mut rest) = path.split_at(end);
+        let entry = self
+            .storage
+            .iter()
+            .find(|entry| entry.label == label)
+            .ok_or(Error::InvalidStoragePath)?;
+        let mut variable = StorageVariable {
+            slot: entry.slot,
+            offset: entry.offset,
+            ty: entry.ty.clone(),
+        };
+        while !rest.is_empty() {
+            let ty = self.get_type(&variable.ty)?;
+            if let Some(member) = rest.strip_prefix('.') {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                let (label, tail) = member.split_at(end);
+                let member = ty
+                    .members
+                    .iter()
+                    .flatten()
+                    .find(|member| member.label == label)
+                    .ok_or(Error::InvalidStoragePath)?;
+                variable = StorageVariable {
+                    slot: variable.slot + member.slot,
+                    offset: member.offset,
+                    ty: member.ty.clone(),
+                };
+                rest = tail;
+            } else if let Some((key, tail)) =
+                rest.strip_prefix('[').and_then(|rest| rest.split_once(']'))
+            {
+                variable = match ty.encoding {
+                    StorageEncoding::Mapping => {
+                        let key_type = self.get_type(ty.key.as_deref().unwrap_or_default())?;
+                        let key_label = match key_type.label.as_str() {
+                            label if label.starts_with("contract ") => "address",
+                            "address payable" => "address",
+                            label if label.starts_with("enum ") => "uint8",
+                            label => label,
+                        };
+                        let key = DynSolType::parse(key_label)
+                            .and_then(|ty| ty.coerce_str(key.trim_matches('"')))
+                            .map_err(|_| Error::InvalidStoragePath)?;
+                        StorageVariable {
+                            slot: key_slot(&key, variable.slot)?,
+                            offset: 0,
+                            ty: ty.value.clone().ok_or(Error::InvalidStorageLayout)?,
+                        }
+                    }
+                    StorageEncoding::DynamicArray | StorageEncoding::Inplace => {
+                        let base = ty.base.as_deref().ok_or(Error::InvalidStoragePath)?;
+                        let index: u64 = key.parse().map_err(|_| Error::InvalidStoragePath)?;
+                        let element_bytes = self.get_type(base)?.number_of_bytes;
+                        let (slot, offset) = if ty.encoding == StorageEncoding::DynamicArray {
+                            dynamic_array_element_slot(variable.slot, index, element_bytes)
+                        } else {
+                            if index >= static_array_length(&ty.label)? {
+                                return Err(Error::InvalidStoragePath);
+                            }
+                            array_element_slot(variable.slot, index, element_bytes)
+                        }
+                        .ok_or(Error::InvalidStorageLayout)?;
+                        StorageVariable {
+                            slot,
+                            offset,
+                            ty: base.into(),
+                        }
+                    }
+                    StorageEncoding::Bytes => return Err(Error::InvalidStoragePath),
+                };
+                rest = tail;
+            } else {
+                return Err(Error::InvalidStoragePath);
+            }
+        }
+        Ok(variable)
+    }
+
+    /// Decode a variable from the known storage, collecting the slots that are yet to be read.
+    /// Every slot read, known or missing, is taken from `budget`.
+    fn decode_inner(
+        &self,
+        ty: &str,
+        slot: U256,
+        offset: usize,
+        storage: &BTreeMap<U256, U256>,
+        missing: &mut BTreeSet<U256>,
+        budget: &mut u64,
+    ) -> Result<Option<DynSolValue>, Error> {
+        let mut read = |slot: U256| {
+            *budget = budget.checked_sub(1).ok_or(Error::InvalidSlots)?;
+            let word = storage.get(&slot).copied();
+            if word.is_none() {
+                missing.insert(slot);
+            }
+            Ok::<_, Error>(word)
+        };
+        let ty = self.get_type(ty)?;
+        match ty.encoding {
+            StorageEncoding::Mapping => Err(Error::InvalidStoragePath),
+            StorageEncoding::Bytes => {
+                let Some(word) = read(slot)? else {
+                    return Ok(None);
+                };
+                let bytes = if word.bit(0) {
+                    // long bytes are stored from `keccak256(slot)` with `2 * length + 1` in the
+                    // slot
+                    let length: u64 = ((word - U256::from(1)) >> 1_usize)
+                        .try_into()
+                        .map_err(|_| Error::InvalidSlots)?;
+                    let data = U256::from_be_bytes(keccak256(slot.to_be_bytes::<32>()).0);
+                    // the budget stops the reads before a large length is allocated
+                    let words = (0..length.div_ceil(32))
+                        .map(|i| read(data + U256::from(i)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let Some(words) = words.into_iter().collect::<Option<Vec<_>>>() else {
+                        return Ok(None);
+                    };
+                    let mut bytes: Vec<u8> = words
+                        .iter()
+                        .flat_map(|word| word.to_be_bytes::<32>())
+                        .collect();
+                    bytes.truncate(length as usize);
+                    bytes
+                } else {
+                    // short bytes are stored in the higher-order bytes with `2 * length`
+                    let length = (word.byte(0) >> 1) as usize;
+                    word.to_be_bytes::<32>()[..length.min(31)].to_vec()
+                };
+                Ok(Some(if ty.label == "string" {
+                    DynSolValue::String(String::from_utf8_lossy(&bytes).into_owned())
+                } else {
+                    DynSolValue::Bytes(bytes)
+                }))
+            }
+            StorageEncoding::DynamicArray => {
+                let Some(length) = read(slot)? else {
+                    return Ok(None);
+                };
+                let length: u64 = length.try_into().map_err(|_| Error::InvalidSlots)?;
+                // every element reads at least one slot
+                if length > *budget {
+                    return Err(Error::InvalidSlots);
+                }
+                let base = ty.base.as_deref().ok_or(Error::InvalidStorageLayout)?;
+                let element_bytes = self.get_type(base)?.number_of_bytes;
+                let elements = (0..length)
+                    .map(|index| {
+                        let (slot, offset) = dynamic_array_element_slot(slot, index, element_bytes)
+                            .ok_or(Error::InvalidStorageLayout)?;
+                        self.decode_inner(base, slot, offset, storage, missing, budget)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // collect the missing slots of every element before giving up
+                Ok(elements
+                    .into_iter()
+                    .collect::<Option<Vec<_>>>()
+                    .map(DynSolValue::Array))
+            }
+            StorageEncoding::Inplace => {
+                if let Some(members) = &ty.members {
+                    let members = members
+                        .iter()
+                        .map(|member| {
+                            self.decode_inner(
+                                &member.ty,
+                                slot + member.slot,
+                                member.offset,
+                                storage,
+                                missing,
+                                budget,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(members
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(DynSolValue::Tuple))
+                } else if let Some(base) = &ty.base {
+                    let element_bytes = self.get_type(base)?.number_of_bytes;
+                    let elements = (0..static_array_length(&ty.label)?)
+                        .map(|index| {
+                            let (slot, offset) = array_element_slot(slot, index, element_bytes)
+                                .ok_or(Error::InvalidStorageLayout)?;
+                            self.decode_inner(base, slot, offset, storage, missing, budget)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(elements
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(DynSolValue::FixedArray))
+                } else {
+                    let size = ty.number_of_bytes;
+                    if size == 0 || size > 32 || offset + size > 32 {
+                        return Err(Error::InvalidStorageLayout);
+                    }
+                    Ok(read(slot)?.map(|word| decode_word(&ty.label, word, offset, size)))
+                }
+            }
+        }
+    }
+
+    /// Get the storage slots that must be read before a variable can be decoded. Since the
+    /// location of dynamic data depends on its length, more slots may be required once these are
+    /// read.
+    ///
+    /// ## Arguments
+    ///
+    /// * `variable`: The variable returned by [`StorageLayout::plan`]
+    /// * `storage`: The storage slots read so far
+    ///
+    /// ## Returns
+    ///
+    /// The missing storage slots, empty if the variable can be decoded, or [`Error::InvalidSlots`]
+    /// if decoding the variable reads more than [`MAX_DECODED_SLOTS`]
+    #[inline]
+    pub fn missing_slots(
+        &self,
+        variable: &StorageVariable,
+        storage: &BTreeMap<U256, U256>,
+    ) -> Result<Vec<U256>, Error> {
+        let mut missing = BTreeSet::new();
+        let mut budget = MAX_DECODED_SLOTS;
+        self.decode_inner(
+            &variable.ty,
+            variable.slot,
+            variable.offset,
+            storage,
+            &mut missing,
+            &mut budget,
+        )?;
+        Ok(missing.into_iter().collect())
+    }
+
+    /// Decode a variable from storage. Structs are decoded into tuples in the order of the
+    /// members.
+    ///
+    /// ## Arguments
+    ///
+    /// * `variable`: The variable returned by [`StorageLayout::plan`]
+    /// * `storage`: The storage slots including those returned by [`StorageLayout::missing_slots`]
+    ///
+    /// ## Returns
+    ///
+    /// The value of the variable, or [`Error::InvalidSlots`] if a slot is missing or decoding the
+    /// variable reads more than [`MAX_DECODED_SLOTS`]
+    #[inline]
+    pub fn decode(
+        &self,
+        variable: &StorageVariable,
+        storage: &BTreeMap<U256, U256>,
+    ) -> Result<DynSolValue, Error> {
+        let mut budget = MAX_DECODED_SLOTS;
+        self.decode_inner(
+            &variable.ty,
+            variable.slot,
+            variable.offset,
+            storage,
+            &mut BTreeSet::new(),
+            &mut budget,
+        )?
+        .ok_or(Error::InvalidSlots)
+    }
+}
+
+/// Convert a decoded storage value into a typed value, e.g. a `sol!` struct with the same members.
+#[inline]
+pub fn decode_storage_value<T: SolValue + From<<T::SolType as SolType>::RustType>>(
+    value: &DynSolValue,
+) -> Result<T, Error> {
+    Ok(T::abi_decode(&value.abi_encode())?)
+}
+
+/// Read variables of a contract given its storage layout. The slots are fetched through
+/// [`get_storage_at`] in as many round trips as the depth of dynamic data requires.
+///
+/// ## Arguments
+///
+/// * `address`: The contract address to fetch storage from
+/// * `layout`: The storage layout of the contract
+/// * `paths`: The paths of the variables, see [`StorageLayout::plan`]
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] to query with, pinned to the latest block
+///   number if no block is given
+///
+/// ## Returns
+///
+/// The values of the variables in the order of the paths
+#[inline]
+pub async fn get_storage_values<N, P>(
+    address: Address,
+    layout: &StorageLayout,
+    paths: &[&str],
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<DynSolValue>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let variables = paths
+        .iter()
+        .map(|path| layout.plan(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    // read every round at the same block
+    let mut options = options.into().resolve(&provider).await?;
+    if options.block_id.is_none() {
+        options = options.block(BlockId::number(provider.get_block_number().await?));
+    }
+    let mut storage = BTreeMap::new();
+    loop {
+        let mut missing = BTreeSet::new();
+        for variable in &variables {
+            missing.extend(layout.missing_slots(variable, &storage)?);
+        }
+        if missing.is_empty() {
+            return variables
+                .iter()
+                .map(|variable| layout.decode(variable, &storage))
+                .collect();
+        }
+        let slots: Vec<B256> = missing.into_iter().map(B256::from).collect();
+        let values = get_storage_at(address, slots.clone(), &provider, options.clone()).await?;
+        storage.extend(
+            slots
+                .into_iter()
+                .zip(values)
+                .map(|(slot, value)| (U256::from_be_bytes(slot.0), U256::from_be_bytes(value.0))),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        primitives::{address, I256},
+        sol,
+    };
+
+    sol! {
+        struct Observation {
+            uint32 blockTimestamp;
+            int56 tickCumulative;
+            uint160 secondsPerLiquidityCumulativeX128;
+            bool initialized;
+        }
+    }
+
+    fn entry(label: &str, slot: u64, offset: usize, ty: &str) -> StorageEntry {
+        StorageEntry {
+            label: label.into(),
+            offset,
+            slot: U256::from(slot),
+            ty: ty.into(),
+        }
+    }
+
+    fn value_type(label: &str, number_of_bytes: usize) -> StorageType {
+        StorageType {
+            label: label.into(),
+            number_of_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// The layout of
+    /// ```solidity
+    /// address owner; uint24 fee; int24 tick; bool unlocked;
+    /// mapping(address => uint256) balances;
+    /// Observation[2] observations;
+    /// uint128[] values;
+    /// string name;
+    /// mapping(string => Observation) named;
+    /// uint256[][] nested;
+    /// ```
+    fn layout() -> StorageLayout {
+        let observation = "t_struct(Observation)1_storage";
+        StorageLayout {
+            storage: vec![
+                entry("owner", 0, 0, "t_address"),
+                entry("fee", 0, 20, "t_uint24"),
+                entry("tick", 0, 23, "t_int24"),
+                entry("unlocked", 0, 26, "t_bool"),
+                entry("balances", 1, 0, "t_mapping(t_address,t_uint256)"),
+                entry(
+                    "observations",
+                    2,
+                    0,
+                    "t_array(t_struct(Observation)1_storage)2_storage",
+                ),
+                entry("values", 4, 0, "t_array(t_uint128)dyn_storage"),
+                entry("name", 5, 0, "t_string_storage"),
+                entry(
+                    "named",
+                    6,
+                    0,
+                    "t_mapping(t_string_memory_ptr,t_struct(Observation)1_storage)",
+                ),
+                entry(
+                    "nested",
+                    7,
+                    0,
+                    "t_array(t_array(t_uint256)dyn_storage)dyn_storage",
+                ),
+            ],
+            types: BTreeMap::from([
+                ("t_address".into(), value_type("address", 20)),
+                ("t_bool".into(), value_type("bool", 1)),
+                ("t_int24".into(), value_type("int24", 3)),
+                ("t_int56".into(), value_type("int56", 7)),
+                ("t_uint24".into(), value_type("uint24", 3)),
+                ("t_uint32".into(), value_type("uint32", 4)),
+                ("t_uint128".into(), value_type("uint128", 16)),
+                ("t_uint160".into(), value_type("uint160", 20)),
+                ("t_uint256".into(), value_type("uint256", 32)),
+                (
+                    "t_string_memory_ptr".into(),
+                    StorageType {
+                        encoding: StorageEncoding::Bytes,
+                        ..value_type("string", 32)
+                    },
+                ),
+                (
+                    "t_string_storage".into(),
+                    StorageType {
+                        encoding: StorageEncoding::Bytes,
+                        ..value_type("string", 32)
+                    },
+                ),
+                (
+                    "t_mapping(t_address,t_uint256)".into(),
+                    StorageType {
+                        encoding: StorageEncoding::Mapping,
+                        key: Some("t_address".into()),
+                        value: Some("t_uint256".into()),
+                        ..value_type("mapping(address => uint256)", 32)
+                    },
+                ),
+                (
+                    "t_mapping(t_string_memory_ptr,t_struct(Observation)1_storage)".into(),
+                    StorageType {
+                        encoding: StorageEncoding::Mapping,
+                        key: Some("t_string_memory_ptr".into()),
+                        value: Some(observation.into()),
+                        ..value_type("mapping(string => struct Observation)", 32)
+                    },
+                ),
+                (
+                    "t_array(t_struct(Observation)1_storage)2_storage".into(),
+                    StorageType {
+                        base: Some(observation.into()),
+                        ..value_type("struct Observation[2]", 64)
+                    },
+                ),
+                (
+                    "t_array(t_uint128)dyn_storage".into(),
+                    StorageType {
+                        encoding: StorageEncoding::DynamicArray,
+                        base: Some("t_uint128".into()),
+                        ..value_type("uint128[]", 32)
+                    },
+                ),
+                (
+                    "t_array(t_uint256)dyn_storage".into(),
+                    StorageType {
+                        encoding: StorageEncoding::DynamicArray,
+                        base: Some("t_uint256".into()),
+                        ..value_type("uint256[]", 32)
+                    },
+                ),
+                (
+                    "t_array(t_array(t_uint256)dyn_storage)dyn_storage".into(),
+                    StorageType {
+                        encoding: StorageEncoding::DynamicArray,
+                        base: Some("t_array(t_uint256)dyn_storage".into()),
+                        ..value_type("uint256[][]", 32)
+                    },
+                ),
+                (
+                    observation.into(),
+                    StorageType {
+                        members: Some(vec![
+                            entry("blockTimestamp", 0, 0, "t_uint32"),
+                            entry("tickCumulative", 0, 4, "t_int56"),
+                            entry("secondsPerLiquidityCumulativeX128", 0, 11, "t_uint160"),
+                            entry("initialized", 0, 31, "t_bool"),
+                        ]),
+                        ..value_type("struct Observation", 32)
+                    },
+                ),
+            ]),
+        }
+    }
+
+    /// Read and decode a variable, fetching the missing slots from `state` until none is left.
+    fn read(
+        layout: &StorageLayout,
+        path: &str,
+        state: &BTreeMap<U256, U256>,
+    ) -> Result<DynSolValue, Error> {
+        let variable = layout.plan(path)?;
+        let mut storage = BTreeMap::new();
+        loop {
+            let missing = layout.missing_slots(&variable, &storage)?;
+            if missing.is_empty() {
+                return layout.decode(&variable, &storage);
+            }
+            for slot in missing {
+                storage.insert(slot, state.get(&slot).copied().unwrap_or_default());
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let layout = layout();
+        let owner = address!("1F98431c8aD98523631AE4a59f267346ea31F984");
+        let observation = |timestamp: u64| {
+            U256::from(timestamp)
+                | (U256::from_be_bytes(I256::unchecked_from(-5).to_be_bytes::<32>())
+                    & U256::from(0xffffffffffffff_u64))
+                    << 32
+                | U256::from(7) << 88
+                | U256::from(1) << 248
+        };
+        let name = "a string longer than thirty-one bytes";
+        let name_slot = U256::from_be_bytes(keccak256(U256::from(5).to_be_bytes::<32>()).0);
+        let mut name_words = name.as_bytes().to_vec();
+        name_words.resize(64, 0);
+        let values_slot = U256::from_be_bytes(keccak256(U256::from(4).to_be_bytes::<32>()).0);
+        let state = BTreeMap::from([
+            (
+                U256::ZERO,
+                U256::from_be_bytes(owner.into_word().0)
+                    | U256::from(3000) << 160
+                    | U256::from(0xffffc4) << 184
+                    | U256::from(1) << 208,
+            ),
+            (mapping_slot(&owner, U256::from(1)), U256::from(42)),
+            (U256::from(2), observation(1)),
+            (U256::from(3), observation(2)),
+            (U256::from(4), U256::from(3)),
+            (values_slot, U256::from(2) << 128 | U256::from(1)),
+            (values_slot + U256::from(1), U256::from(3)),
+            (U256::from(5), U256::from(name.len() * 2 + 1)),
+            (name_slot, U256::from_be_slice(&name_words[..32])),
+            (
+                name_slot + U256::from(1),
+                U256::from_be_slice(&name_words[32..]),
+            ),
+            (
+                mapping_slot(&"pool".to_owned(), U256::from(6)),
+                observation(3),
+            ),
+        ]);
+
+        assert_eq!(
+            read(&layout, "owner", &state).unwrap(),
+            DynSolValue::Address(owner)
+        );
+        assert_eq!(
+            read(&layout, "fee", &state).unwrap(),
+            DynSolValue::Uint(U256::from(3000), 24)
+        );
+        assert_eq!(
+            read(&layout, "tick", &state).unwrap(),
+            DynSolValue::Int(I256::unchecked_from(-60), 24)
+        );
+        assert_eq!(
+            read(&layout, "unlocked", &state).unwrap(),
+            DynSolValue::Bool(true)
+        );
+        assert_eq!(
+            read(&layout, &format!("balances[{owner}]"), &state).unwrap(),
+            DynSolValue::Uint(U256::from(42), 256)
+        );
+        assert_eq!(
+            read(&layout, "observations[1].blockTimestamp", &state).unwrap(),
+            DynSolValue::Uint(U256::from(2), 32)
+        );
+        let observations = read(&layout, "observations", &state).unwrap();
+        let DynSolValue::FixedArray(observations) = observations else {
+            panic!("expected a fixed array");
+        };
+        let observation: Observation = decode_storage_value(&observations[0]).unwrap();
+        assert_eq!(observation.blockTimestamp, 1);
+        assert_eq!(observation.tickCumulative.as_i64(), -5);
+        assert_eq!(observation.secondsPerLiquidityCumulativeX128.to::<u64>(), 7);
+        assert!(observation.initialized);
+        assert_eq!(
+            read(&layout, "values", &state).unwrap(),
+            DynSolValue::Array(vec![
+                DynSolValue::Uint(U256::from(1), 128),
+                DynSolValue::Uint(U256::from(2), 128),
+                DynSolValue::Uint(U256::from(3), 128),
+            ])
+        );
+        assert_eq!(
+            read(&layout, "values[2]", &state).unwrap(),
+            DynSolValue::Uint(U256::from(3), 128)
+        );
+        assert_eq!(
+            read(&layout, "name", &state).unwrap(),
+            DynSolValue::String(name.into())
+        );
+        assert_eq!(
+            read(&layout, "named[pool].blockTimestamp", &state).unwrap(),
+            DynSolValue::Uint(U256::from(3), 32)
+        );
+
+        assert!(matches!(
+            layout.plan("missing"),
+            Err(Error::InvalidStoragePath)
+        ));
+        assert!(matches!(
+            layout.plan("observations[2]"),
+            Err(Error::InvalidStoragePath)
+        ));
+        assert!(matches!(
+            layout.plan("balances[0x12]"),
+            Err(Error::InvalidStoragePath)
+        ));
+        assert!(matches!(
+            read(&layout, "balances", &state),
+            Err(Error::InvalidStoragePath)
+        ));
+        assert!(matches!(
+            layout.decode(&layout.plan("name").unwrap(), &BTreeMap::new()),
+            Err(Error::InvalidSlots)
+        ));
+
+        // lengths that need more slots than the cap are rejected before reading all the data
+        let mut state = state;
+        state.insert(U256::from(4), U256::from(MAX_DECODED_SLOTS));
+        state.insert(U256::from(5), U256::from(MAX_DECODED_SLOTS * 32 * 2 + 1));
+        for path in ["values", "name"] {
+            let variable = layout.plan(path).unwrap();
+            assert!(matches!(
+                layout.missing_slots(&variable, &state),
+                Err(Error::InvalidSlots)
+            ));
+        }
+        state.insert(U256::from(4), U256::MAX);
+        assert!(matches!(
+            read(&layout, "values", &state),
+            Err(Error::InvalidSlots)
+        ));
+        // the cap applies to the nested arrays in total, not to each length
+        let nested_slot = U256::from_be_bytes(keccak256(U256::from(7).to_be_bytes::<32>()).0);
+        state.insert(U256::from(7), U256::from(300));
+        for i in 0..300 {
+            state.insert(nested_slot + U256::from(i), U256::from(300));
+        }
+        assert!(matches!(
+            read(&layout, "nested", &state),
+            Err(Error::InvalidSlots)
+        ));
+        state.insert(U256::from(7), U256::from(2));
+        assert_eq!(
+            read(&layout, "nested", &state).unwrap(),
+            DynSolValue::Array(vec![
+                DynSolValue::Array(vec![
+                    DynSolValue::Uint(
+                        U256::ZERO,
+                        256
+                    );
+                    300
+                ]);
+                2
+            ])
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let json = r#"{
+            "storage": [
+                {"astId": 3, "contract": "Test.sol:Test", "label": "balances", "offset": 0, "slot": "1", "type": "t_mapping(t_address,t_uint256)"}
+            ],
+            "types": {
+                "t_address": {"encoding": "inplace", "label": "address", "numberOfBytes": "20"},
+                "t_mapping(t_address,t_uint256)": {"encoding": "mapping", "key": "t_address", "label": "mapping(address => uint256)", "numberOfBytes": "32", "value": "t_uint256"},
+                "t_uint256": {"encoding": "inplace", "label": "uint256", "numberOfBytes": "32"}
+            }
+        }"#;
+        let layout: StorageLayout = serde_json::from_str(json).unwrap();
+        assert_eq!(layout.storage[0], self::layout().storage[4]);
+        assert_eq!(
+            layout.get_type("t_mapping(t_address,t_uint256)").unwrap(),
+            self::layout()
+                .get_type("t_mapping(t_address,t_uint256)")
+                .unwrap()
+        );
+        let empty: StorageLayout =
+            serde_json::from_str(r#"{"storage": [], "types": null}"#).unwrap();
+        assert!(empty.types.is_empty());
+    }
+}
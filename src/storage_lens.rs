@@ -2,15 +2,28 @@
 //!
 //! The storage lens module provides a function to batch `eth_getStorageAt` RPC calls in a single
 //! `eth_call` by overriding the target contract's deployed bytecode with `EphemeralStorageLens`.
+//! The storage of multiple contracts is read in a single JSON-RPC batch, and the values can be
+//! verified against the state root of the block with the Merkle proofs of `eth_getProof`.
 
-use crate::{bindings::ephemeralstoragelens::EphemeralStorageLens, error::Error};
+use crate::{
+    bindings::ephemeralstoragelens::{EphemeralStorageLens, EphemeralStorageLens::extsloadCall},
+    caller::CallOptions,
+    error::Error,
+};
 use alloc::vec::Vec;
 use alloy::{
+    consensus::BlockHeader,
     eips::BlockId,
-    network::Network,
-    primitives::{Address, B256},
+    network::{primitives::HeaderResponse, BlockResponse, Network, TransactionBuilder},
+    primitives::{keccak256, Address, Bytes, B256},
     providers::Provider,
-    rpc::types::state::{AccountOverride, StateOverride},
+    rlp,
+    rpc::{
+        client::BatchRequest,
+        types::{state::StateOverride, EIP1186AccountProofResponse},
+    },
+    sol_types::SolCall,
+    trie::{proof::verify_proof, Nibbles, TrieAccount},
 };
 
 /// Batch `eth_getStorageAt` RPC calls in a single `eth_call` by overriding the target contract's
@@ -21,7 +34,8 @@ use alloy::{
 /// * `address`: The contract address to fetch storage from
 /// * `slots`: The storage slots to query
 /// * `provider`: The alloy provider
-/// * `block_id`: Optional block id to query
+/// * `options`: Optional block id or [`CallOptions`] to query with, whose state overrides of
+///   `address` are kept except for the code
 ///
 /// ## Returns
 ///
@@ -31,38 +45,284 @@ pub async fn get_storage_at<N, P>(
     address: Address,
     slots: Vec<B256>,
     provider: P,
-    block_id: Option<BlockId>,
+    options: impl Into<CallOptions>,
 ) -> Result<Vec<B256>, Error>
 where
     N: Network,
     P: Provider<N>,
 {
-    // override the deployed bytecode at `address`
-    let state = StateOverride::from_iter([(
-        address,
-        AccountOverride {
-            code: Some(EphemeralStorageLens::DEPLOYED_BYTECODE.clone()),
-            ..Default::default()
-        },
-    )]);
+    let mut options = options.into().resolve(&provider).await?;
+    let state = lens_override(options.state.take().unwrap_or_default(), address);
     let lens = EphemeralStorageLens::new(address, provider);
-    let call_builder = lens.extsload(slots).state(state);
-    let call_builder = match block_id {
-        Some(block_id) => call_builder.block(block_id),
-        None => call_builder,
-    };
-    Ok(call_builder.call().await?)
+    Ok(options
+        .state(state)
+        .apply(lens.extsload(slots))
+        .call()
+        .await?)
+}
+
+/// Override the deployed bytecode at `address` with `EphemeralStorageLens`, keeping the other
+/// overrides of `address`.
+fn lens_override(mut state: StateOverride, address: Address) -> StateOverride {
+    state.entry(address).or_default().code = Some(EphemeralStorageLens::DEPLOYED_BYTECODE.clone());
+    state
+}
+
+/// Read the storage of multiple contracts in a single JSON-RPC batch of `eth_call`s, each
+/// overriding the deployed bytecode of a contract with `EphemeralStorageLens`.
+///
+/// ## Arguments
+///
+/// * `requests`: The contract addresses and the storage slots to query from each
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] applied to each `eth_call`, whose state
+///   overrides of each contract are kept except for the code
+///
+/// ## Returns
+///
+/// The storage values at the given slots of each contract, in the order of the requests
+#[inline]
+pub async fn get_storage_at_batch<N, P>(
+    requests: Vec<(Address, Vec<B256>)>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<Vec<B256>>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let mut options = options.into().resolve(&provider).await?;
+    let block_id = options.block_id.unwrap_or_default();
+    let state = options.state.take().unwrap_or_default();
+    let mut batch = BatchRequest::new(provider.client());
+    let waiters = requests
+        .into_iter()
+        .map(|(address, slots)| {
+            let mut tx = N::TransactionRequest::default()
+                .with_to(address)
+                .with_input(extsloadCall { slots }.abi_encode());
+            if let Some(gas) = options.gas {
+                tx.set_gas_limit(gas);
+            }
+            if let Some(from) = options.from {
+                tx.set_from(from);
+            }
+            if let Some(value) = options.value {
+                tx.set_value(value);
+            }
+            let state = lens_override(state.clone(), address);
+            batch.add_call::<_, Bytes>("eth_call", &(tx, block_id, state))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    batch.send().await?;
+    let mut results = Vec::with_capacity(waiters.len());
+    for waiter in waiters {
+        let data: Bytes = waiter.await?;
+        results.push(extsloadCall::abi_decode_returns(&data)?);
+    }
+    Ok(results)
+}
+
+/// Verify the account and storage proofs of an `eth_getProof` response against a state root.
+///
+/// ## Arguments
+///
+/// * `state_root`: The state root of the block
+/// * `proof`: The `eth_getProof` response
+///
+/// ## Returns
+///
+/// The verified storage values in the order of the storage proofs, or [`Error::InvalidProof`]
+#[inline]
+pub fn verify_storage_proof(
+    state_root: B256,
+    proof: &EIP1186AccountProofResponse,
+) -> Result<Vec<B256>, Error> {
+    // a non-existent account is proven by exclusion
+    let account = (!proof.is_empty()).then(|| {
+        rlp::encode(TrieAccount {
+            nonce: proof.nonce,
+            balance: proof.balance,
+            storage_root: proof.storage_hash,
+            code_hash: proof.code_hash,
+        })
+    });
+    verify_proof(
+        state_root,
+        Nibbles::unpack(keccak256(proof.address)),
+        account,
+        &proof.account_proof,
+    )
+    .map_err(|_| Error::InvalidProof)?;
+    proof
+        .storage_proof
+        .iter()
+        .map(|storage_proof| {
+            // an empty slot is proven by exclusion
+            let value = (!storage_proof.value.is_zero()).then(|| rlp::encode(storage_proof.value));
+            verify_proof(
+                proof.storage_hash,
+                Nibbles::unpack(keccak256(storage_proof.key.as_b256())),
+                value,
+                &storage_proof.proof,
+            )
+            .map_err(|_| Error::InvalidProof)?;
+            Ok(B256::from(storage_proof.value))
+        })
+        .collect()
+}
+
+/// Batch `eth_getStorageAt` RPC calls like [`get_storage_at`] and verify the values with the
+/// Merkle proofs of `eth_getProof` against the state root of the block.
+///
+/// ## Arguments
+///
+/// * `address`: The contract address to fetch storage from
+/// * `slots`: The storage slots to query
+/// * `provider`: The alloy provider
+/// * `options`: Optional block id or [`CallOptions`] to query with, whose state overrides must not
+///   change the storage of `address` as the proofs are of the actual state
+///
+/// ## Returns
+///
+/// The verified storage values at the given slots, [`Error::InvalidProof`] if the proofs do not
+/// verify, or [`Error::StorageMismatch`] if the lens returned a different value
+#[inline]
+pub async fn get_storage_at_verified<N, P>(
+    address: Address,
+    slots: Vec<B256>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<B256>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let options = options.into().resolve(&provider).await?;
+    // pin the block by hash so that the lens and the proofs read the same state
+    let block = provider
+        .get_block(options.block_id.unwrap_or_default())
+        .await?
+        .ok_or(Error::BlockNotFound)?;
+    let header = block.header();
+    let block_id = BlockId::hash(header.hash());
+    let values = get_storage_at(address, slots.clone(), &provider, options.block(block_id)).await?;
+    let proof = provider
+        .get_proof(address, slots.clone())
+        .block_id(block_id)
+        .await?;
+    if proof.address != address
+        || proof.storage_proof.len() != slots.len()
+        || proof
+            .storage_proof
+            .iter()
+            .zip(&slots)
+            .any(|(storage_proof, slot)| storage_proof.key.as_b256() != *slot)
+    {
+        return Err(Error::InvalidProof);
+    }
+    let verified = verify_storage_proof(header.state_root(), &proof)?;
+    for ((slot, value), verified) in slots.iter().zip(values).zip(&verified) {
+        if value != *verified {
+            return Err(Error::StorageMismatch(*slot));
+        }
+    }
+    Ok(verified)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::*;
-    use alloy::primitives::{address, U256};
+    use crate::{state_override::WhatIf, tests::*};
+    use alloy::{
+        primitives::{address, Bytes, U256},
+        providers::ProviderBuilder,
+        rpc::types::{Block, EIP1186StorageProof, Header},
+        sol_types::SolValue,
+        transports::mock::Asserter,
+        trie::{proof::ProofRetainer, HashBuilder},
+    };
     use futures::future::join_all;
 
     const POOL_ADDRESS: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
 
+    /// Build an in-memory trie from hashed keys and return the root and the proof of each target.
+    fn build_trie(leaves: &[(B256, Vec<u8>)], targets: &[B256]) -> (B256, Vec<Vec<Bytes>>) {
+        let mut leaves: Vec<(Nibbles, &[u8])> = leaves
+            .iter()
+            .map(|(key, value)| (Nibbles::unpack(key), value.as_slice()))
+            .collect();
+        leaves.sort_unstable_by_key(|(key, _)| *key);
+        let targets: Vec<Nibbles> = targets.iter().map(Nibbles::unpack).collect();
+        let mut hash_builder =
+            HashBuilder::default().with_proof_retainer(ProofRetainer::new(targets.clone()));
+        for (key, value) in leaves {
+            hash_builder.add_leaf(key, value);
+        }
+        let root = hash_builder.root();
+        let nodes = hash_builder.take_proof_nodes();
+        let proofs = targets
+            .iter()
+            .map(|target| {
+                nodes
+                    .matching_nodes_sorted(target)
+                    .into_iter()
+                    .map(|(_, node)| node)
+                    .collect()
+            })
+            .collect();
+        (root, proofs)
+    }
+
+    /// A proof of three slots of the pool, the last of which is empty, and the state root.
+    fn mock_proof() -> (B256, EIP1186AccountProofResponse) {
+        let slots: Vec<B256> = (0..3_u8).map(B256::with_last_byte).collect();
+        let values = [U256::from(1), U256::from(0xdead), U256::ZERO];
+        let leaves: Vec<(B256, Vec<u8>)> = slots
+            .iter()
+            .zip(values)
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(slot, value)| (keccak256(slot), rlp::encode(value)))
+            .collect();
+        let keys: Vec<B256> = slots.iter().map(keccak256).collect();
+        let (storage_root, storage_proofs) = build_trie(&leaves, &keys);
+        let account = TrieAccount {
+            nonce: 1,
+            balance: U256::from(100),
+            storage_root,
+            code_hash: keccak256("code"),
+        };
+        let (state_root, account_proofs) = build_trie(
+            &[
+                (keccak256(POOL_ADDRESS), rlp::encode(account)),
+                (
+                    keccak256(Address::repeat_byte(1)),
+                    rlp::encode(TrieAccount::default()),
+                ),
+            ],
+            &[keccak256(POOL_ADDRESS)],
+        );
+        let proof = EIP1186AccountProofResponse {
+            address: POOL_ADDRESS,
+            balance: account.balance,
+            code_hash: account.code_hash,
+            nonce: account.nonce,
+            storage_hash: storage_root,
+            account_proof: account_proofs[0].clone(),
+            storage_proof: slots
+                .into_iter()
+                .zip(values)
+                .zip(storage_proofs)
+                .map(|((slot, value), proof)| EIP1186StorageProof {
+                    key: slot.into(),
+                    value,
+                    proof,
+                })
+                .collect(),
+        };
+        (state_root, proof)
+    }
+
     #[tokio::test]
     async fn test_get_storage_at() {
         let provider = PROVIDER.clone();
@@ -88,4 +348,164 @@ mod tests {
         });
         join_all(futures).await;
     }
+
+    #[test]
+    fn test_verify_storage_proof() {
+        let (state_root, mut proof) = mock_proof();
+        let values: Vec<B256> = proof
+            .storage_proof
+            .iter()
+            .map(|storage_proof| B256::from(storage_proof.value))
+            .collect();
+        assert_eq!(verify_storage_proof(state_root, &proof).unwrap(), values);
+        assert!(matches!(
+            verify_storage_proof(B256::ZERO, &proof),
+            Err(Error::InvalidProof)
+        ));
+
+        // a forged value
+        proof.storage_proof[1].value = U256::from(0xbeef);
+        assert!(matches!(
+            verify_storage_proof(state_root, &proof),
+            Err(Error::InvalidProof)
+        ));
+        // a value for an empty slot
+        proof.storage_proof[1].value = U256::from(0xdead);
+        proof.storage_proof[2].value = U256::from(1);
+        assert!(matches!(
+            verify_storage_proof(state_root, &proof),
+            Err(Error::InvalidProof)
+        ));
+        // a forged account
+        proof.storage_proof[2].value = U256::ZERO;
+        proof.balance = U256::ZERO;
+        assert!(matches!(
+            verify_storage_proof(state_root, &proof),
+            Err(Error::InvalidProof)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_storage_at_verified_mismatch() {
+        let (state_root, proof) = mock_proof();
+        let slots: Vec<B256> = proof
+            .storage_proof
+            .iter()
+            .map(|storage_proof| storage_proof.key.as_b256())
+            .collect();
+        let block = Block::<()> {
+            header: Header::new(alloy::consensus::Header {
+                state_root,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mock = |values: [u64; 3]| {
+            let asserter = Asserter::new();
+            asserter.push_success(&block);
+            asserter.push_success(&Bytes::from(
+                values
+                    .map(|value| B256::from(U256::from(value)))
+                    .to_vec()
+                    .abi_encode(),
+            ));
+            asserter.push_success(&proof);
+            ProviderBuilder::new()
+                .disable_recommended_fillers()
+                .connect_mocked_client(asserter)
+        };
+
+        let values =
+            get_storage_at_verified(POOL_ADDRESS, slots.clone(), mock([1, 0xdead, 0]), None)
+                .await
+                .unwrap();
+        assert_eq!(values[1], B256::from(U256::from(0xdead)));
+        let err = get_storage_at_verified(POOL_ADDRESS, slots.clone(), mock([1, 0xbeef, 0]), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::StorageMismatch(slot) if slot == slots[1]));
+    }
+
+    #[tokio::test]
+    async fn test_get_storage_at_batch_mock() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let values = |values: &[u64]| -> Vec<B256> {
+            values
+                .iter()
+                .map(|&value| B256::from(U256::from(value)))
+                .collect()
+        };
+        asserter.push_success(&Bytes::from(values(&[1, 2]).abi_encode()));
+        asserter.push_success(&Bytes::from(values(&[3]).abi_encode()));
+        let results = get_storage_at_batch(
+            vec![
+                (POOL_ADDRESS, values(&[0, 1])),
+                (Address::repeat_byte(1), values(&[0])),
+            ],
+            provider,
+            CallOptions::default().gas(30_000_000).state(
+                WhatIf::new()
+                    .storage(POOL_ADDRESS, U256::ZERO, U256::from(1))
+                    .build(),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results, [values(&[1, 2]), values(&[3])]);
+    }
+
+    #[test]
+    fn test_lens_override() {
+        let state = WhatIf::new()
+            .storage(POOL_ADDRESS, U256::ZERO, U256::from(1))
+            .balance(Address::repeat_byte(1), U256::from(2))
+            .build();
+        let state = lens_override(state, POOL_ADDRESS);
+        let account = &state[&POOL_ADDRESS];
+        assert_eq!(
+            account.code,
+            Some(EphemeralStorageLens::DEPLOYED_BYTECODE.clone())
+        );
+        // the other overrides are kept
+        assert_eq!(account.state_diff.as_ref().unwrap().len(), 1);
+        assert_eq!(state[&Address::repeat_byte(1)].balance, Some(U256::from(2)));
+    }
+
+    #[tokio::test]
+    async fn test_get_storage_at_batch() {
+        let pools = [
+            POOL_ADDRESS,
+            address!("8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8"),
+        ];
+        let slots: Vec<B256> = (0..5_u8).map(B256::with_last_byte).collect();
+        let results = get_storage_at_batch(
+            pools.iter().map(|&pool| (pool, slots.clone())).collect(),
+            &*PROVIDER,
+            Some(BLOCK_NUMBER),
+        )
+        .await
+        .unwrap();
+        for (pool, values) in pools.into_iter().zip(results) {
+            let expected = get_storage_at(pool, slots.clone(), &*PROVIDER, Some(BLOCK_NUMBER))
+                .await
+                .unwrap();
+            assert_eq!(values, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_storage_at_verified() {
+        let slots: Vec<B256> = (0..10_u8).map(B256::with_last_byte).collect();
+        let verified =
+            get_storage_at_verified(POOL_ADDRESS, slots.clone(), &*PROVIDER, Some(BLOCK_NUMBER))
+                .await
+                .unwrap();
+        let values = get_storage_at(POOL_ADDRESS, slots, &*PROVIDER, Some(BLOCK_NUMBER))
+            .await
+            .unwrap();
+        assert_eq!(verified, values);
+    }
 }
@@ -0,0 +1,355 @@
+//! ## Storage Slot
+//!
+//! The storage slot module computes the storage slots of Solidity state variables following the
+//! [layout of state variables in storage](https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html),
+//! so that any variable can be read with [`get_storage_at`](crate::storage_lens::get_storage_at).
+//! It also provides the slots of the `ticks`, `tickBitmap` and `observations` of `UniswapV3Pool`
+//! and of the `_positions` of `NonfungiblePositionManager`, while the slots of the `positions` of a
+//! pool are computed by [`get_position_slot`](crate::pool_lens::get_position_slot).
+
+use alloy::{
+    primitives::{aliases::I24, keccak256, I256, U256},
+    sol_types::{SolType, SolValue},
+};
+
+/// The storage slot of the `ticks` mapping in `UniswapV3Pool`.
+pub(crate) const TICKS_SLOT: u64 = 5;
+
+/// The storage slot of the `tickBitmap` mapping in `UniswapV3Pool`.
+pub(crate) const TICK_BITMAP_SLOT: u64 = 6;
+
+/// The storage slot of the `positions` mapping in `UniswapV3Pool`.
+pub(crate) const POSITIONS_SLOT: u64 = 7;
+
+/// The storage slot of the `observations` array in `UniswapV3Pool`.
+pub(crate) const OBSERVATIONS_SLOT: u64 = 8;
+
+/// The storage slot of the `_positions` mapping in `NonfungiblePositionManager`.
+pub(crate) const NPM_POSITIONS_SLOT: u64 = 12;
+
+/// The storage slot of `_nextId` and `_nextPoolId` in `NonfungiblePositionManager`.
+pub(crate) const NPM_NEXT_ID_SLOT: u64 = 13;
+
+/// Compute the storage slot of a key in a mapping, i.e. `keccak256(h(key) . slot)` where `h` pads
+/// value types to 32 bytes and leaves `string` and `bytes` keys unpadded.
+///
+/// ## Arguments
+///
+/// * `key`: The mapping key
+/// * `slot`: The storage slot of the mapping
+///
+/// ## Returns
+///
+/// The storage slot of the value
+#[inline]
+pub fn mapping_slot<K: SolValue>(key: &K, slot: U256) -> U256 {
+    let mut preimage = if <K::SolType as SolType>::DYNAMIC {
+        key.abi_encode_packed()
+    } else {
+        key.abi_encode()
+    };
+    preimage.extend_from_slice(&slot.to_be_bytes::<32>());
+    keccak256(preimage).into()
+}
+
+/// Compute the storage slot of a value in a nested mapping, e.g. `allowance[owner][spender]`.
+///
+/// ## Arguments
+///
+/// * `key0`: The key of the outer mapping
+/// * `key1`: The key of the inner mapping
+/// * `slot`: The storage slot of the outer mapping
+///
+/// ## Returns
+///
+/// The storage slot of the value
+#[inline]
+pub fn nested_mapping_slot<K0: SolValue, K1: SolValue>(key0: &K0, key1: &K1, slot: U256) -> U256 {
+    mapping_slot(key1, mapping_slot(key0, slot))
+}
+
+/// Compute the location of an element in a statically-sized array. Elements of less than 32 bytes
+/// are packed into the same slot, while larger elements such as structs start a new slot.
+///
+/// ## Arguments
+///
+/// * `slot`: The first storage slot of the array
+/// * `index`: The index of the element
+/// * `element_bytes`: The size of an element in bytes, a multiple of 32 for structs
+///
+/// ## Returns
+///
+/// The storage slot of the element and its byte offset within the slot, or `None` if
+/// `element_bytes` is zero
+#[inline]
+pub fn array_element_slot(slot: U256, index: u64, element_bytes: usize) -> Option<(U256, usize)> {
+    match element_bytes {
+        0 => None,
+        1..32 => {
+            let per_slot = 32 / element_bytes as u64;
+            Some((
+                slot + U256::from(index / per_slot),
+                (index % per_slot) as usize * element_bytes,
+            ))
+        }
+        _ => {
+            let element_slots = element_bytes.div_ceil(32) as u64;
+            Some((slot + U256::from(index) * U256::from(element_slots), 0))
+        }
+    }
+}
+
+/// Compute the location of an element in a dynamically-sized array, whose elements start at
+/// `keccak256(slot)` while the length is stored at `slot`.
+///
+/// ## Arguments
+///
+/// * `slot`: The storage slot of the array
+/// * `index`: The index of the element
+/// * `element_bytes`: The size of an element in bytes, a multiple of 32 for structs
+///
+/// ## Returns
+///
+/// The storage slot of the element and its byte offset within the slot, or `None` if
+/// `element_bytes` is zero
+#[inline]
+pub fn dynamic_array_element_slot(
+    slot: U256,
+    index: u64,
+    element_bytes: usize,
+) -> Option<(U256, usize)> {
+    array_element_slot(
+        keccak256(slot.to_be_bytes::<32>()).into(),
+        index,
+        element_bytes,
+    )
+}
+
+/// Compute the storage slot of a struct member.
+///
+/// ## Arguments
+///
+/// * `slot`: The first storage slot of the struct
+/// * `member_slot`: The slot of the member relative to the start of the struct
+///
+/// ## Returns
+///
+/// The storage slot of the member
+#[inline]
+pub fn struct_member_slot(slot: U256, member_slot: u64) -> U256 {
+    slot + U256::from(member_slot)
+}
+
+/// Extract an unsigned field packed into a storage word.
+///
+/// ## Arguments
+///
+/// * `word`: The storage word
+/// * `offset`: The offset of the field in bytes, counted from the lowest-order byte
+/// * `width`: The size of the field in bytes
+///
+/// ## Returns
+///
+/// The value of the field
+#[inline]
+pub fn extract_field(word: U256, offset: usize, width: usize) -> U256 {
+    let value = word.wrapping_shr(offset * 8);
+    if width >= 32 {
+        value
+    } else {
+        value & ((U256::from(1) << (width * 8)) - U256::from(1))
+    }
+}
+
+/// Extract a signed field packed into a storage word.
+///
+/// ## Arguments
+///
+/// * `word`: The storage word
+/// * `offset`: The offset of the field in bytes, counted from the lowest-order byte
+/// * `width`: The size of the field in bytes
+///
+/// ## Returns
+///
+/// The sign-extended value of the field
+#[inline]
+pub fn extract_signed_field(word: U256, offset: usize, width: usize) -> I256 {
+    let value = extract_field(word, offset, width);
+    let unused_bits = 256 - width.min(32) * 8;
+    I256::from_raw(value << unused_bits).asr(unused_bits)
+}
+
+/// Insert an unsigned field into a storage word, leaving the other fields untouched.
+///
+/// ## Arguments
+///
+/// * `word`: The storage word
+/// * `offset`: The offset of the field in bytes, counted from the lowest-order byte
+/// * `width`: The size of the field in bytes
+/// * `value`: The value of the field, truncated to `width` bytes
+///
+/// ## Returns
+///
+/// The storage word with the field replaced
+#[inline]
+pub fn insert_field(word: U256, offset: usize, width: usize, value: U256) -> U256 {
+    let mask = if width >= 32 {
+        U256::MAX
+    } else {
+        (U256::from(1) << (width * 8)) - U256::from(1)
+    };
+    (word & !(mask << (offset * 8))) | ((value & mask) << (offset * 8))
+}
+
+/// Compute the storage slot of a tick in the `ticks` mapping of `UniswapV3Pool`.
+///
+/// ## Arguments
+///
+/// * `tick`: The tick
+///
+/// ## Returns
+///
+/// The first of the 4 storage slots occupied by the tick
+#[inline]
+pub fn get_tick_slot(tick: I24) -> U256 {
+    mapping_slot(&tick, U256::from(TICKS_SLOT))
+}
+
+/// Compute the storage slot of a word in the `tickBitmap` mapping of `UniswapV3Pool`.
+///
+/// ## Arguments
+///
+/// * `word_pos`: The position of the word, i.e. the compressed tick shifted right by 8 bits
+///
+/// ## Returns
+///
+/// The storage slot of the word
+#[inline]
+pub fn get_tick_bitmap_slot(word_pos: i16) -> U256 {
+    mapping_slot(&word_pos, U256::from(TICK_BITMAP_SLOT))
+}
+
+/// Compute the storage slot of an observation in the `observations` array of `UniswapV3Pool`.
+///
+/// ## Arguments
+///
+/// * `index`: The index of the observation
+///
+/// ## Returns
+///
+/// The storage slot of the observation
+#[inline]
+pub fn get_observation_slot(index: u16) -> U256 {
+    U256::from(OBSERVATIONS_SLOT + index as u64)
+}
+
+/// Compute the storage slot of a position in the `_positions` mapping of
+/// `NonfungiblePositionManager`.
+///
+/// ## Arguments
+///
+/// * `token_id`: The token ID of the position
+///
+/// ## Returns
+///
+/// The first of the 5 storage slots occupied by the position
+#[inline]
+pub fn get_npm_position_slot(token_id: U256) -> U256 {
+    mapping_slot(&token_id, U256::from(NPM_POSITIONS_SLOT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, b256, uint, Address};
+
+    #[test]
+    fn test_mapping_slot() {
+        // `balanceOf` of WETH is at slot 3
+        let owner = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+        let mut preimage = [0_u8; 64];
+        preimage[12..32].copy_from_slice(owner.as_slice());
+        preimage[63] = 3;
+        assert_eq!(
+            mapping_slot(&owner, U256::from(3)),
+            U256::from_be_bytes(keccak256(preimage).0)
+        );
+        // negative keys are sign extended
+        let mut preimage = [0xff_u8; 64];
+        preimage[32..63].fill(0);
+        preimage[63] = 5;
+        assert_eq!(
+            get_tick_slot(I24::unchecked_from(-1)),
+            U256::from_be_bytes(keccak256(preimage).0)
+        );
+        assert_eq!(
+            get_tick_bitmap_slot(-1),
+            mapping_slot(&-1_i32, U256::from(6))
+        );
+        // string keys are not padded
+        let mut preimage = b"key".to_vec();
+        preimage.extend_from_slice(&[0_u8; 32]);
+        assert_eq!(
+            mapping_slot(&"key".to_owned(), U256::ZERO),
+            U256::from_be_bytes(keccak256(preimage).0)
+        );
+        let spender = Address::repeat_byte(1);
+        assert_eq!(
+            nested_mapping_slot(&owner, &spender, U256::from(4)),
+            mapping_slot(&spender, mapping_slot(&owner, U256::from(4)))
+        );
+    }
+
+    #[test]
+    fn test_array_element_slot() {
+        let slot = U256::from(2);
+        assert_eq!(array_element_slot(slot, 3, 32), Some((U256::from(5), 0)));
+        assert_eq!(array_element_slot(slot, 3, 64), Some((U256::from(8), 0)));
+        // two `uint128` per slot
+        assert_eq!(array_element_slot(slot, 3, 16), Some((U256::from(3), 16)));
+        // ten `uint24` per slot
+        assert_eq!(array_element_slot(slot, 21, 3), Some((U256::from(4), 3)));
+        // an element cannot be empty
+        assert_eq!(array_element_slot(slot, 3, 0), None);
+        let data = uint!(0x405787fa12a823e0f2b7631cc41b3ba8828b3321ca811111fa75cd3aa3bb5ace_U256);
+        assert_eq!(
+            dynamic_array_element_slot(slot, 1, 32),
+            Some((data + U256::from(1), 0))
+        );
+        assert_eq!(dynamic_array_element_slot(slot, 1, 0), None);
+        assert_eq!(get_observation_slot(2), U256::from(10));
+        assert_eq!(struct_member_slot(data, 3), data + U256::from(3));
+    }
+
+    #[test]
+    fn test_extract_field() {
+        // slot0 of a pool with tick -1 and `feeProtocol` 0x44
+        let sqrt_price_x96 = uint!(79228162514264337593543950336_U256);
+        let word = sqrt_price_x96
+            | (U256::from(0xffffff) << 160)
+            | (U256::from(0x44) << 232)
+            | (U256::from(1) << 240);
+        assert_eq!(extract_field(word, 0, 20), sqrt_price_x96);
+        assert_eq!(extract_signed_field(word, 20, 3), I256::MINUS_ONE);
+        assert_eq!(extract_field(word, 20, 3), U256::from(0xffffff));
+        assert_eq!(extract_field(word, 29, 1), U256::from(0x44));
+        assert_eq!(extract_field(word, 30, 1), U256::from(1));
+        assert_eq!(extract_field(word, 0, 32), word);
+        let word = insert_field(word, 20, 3, U256::from(0x3c));
+        assert_eq!(
+            extract_signed_field(word, 20, 3),
+            I256::unchecked_from(0x3c)
+        );
+        assert_eq!(extract_field(word, 0, 20), sqrt_price_x96);
+        assert_eq!(extract_field(word, 29, 1), U256::from(0x44));
+        assert_eq!(insert_field(word, 0, 32, U256::from(1)), U256::from(1));
+        assert_eq!(
+            extract_signed_field(
+                b256!("00000000000000000000000000000000ffffffffffffffffffffffffffffff85").into(),
+                0,
+                16
+            ),
+            I256::unchecked_from(-123)
+        );
+    }
+}
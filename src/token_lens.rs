@@ -0,0 +1,245 @@
+//! ## Token Lens
+//!
+//! The token lens module provides functions to fetch ERC20 token details using ephemeral
+//! contracts, tolerating tokens that do not conform to the standard.
+
+use crate::{
+    bindings::{
+        ephemeralgetallowances::{
+            EphemeralGetAllowances, EphemeralGetAllowances::getAllowancesCall,
+        },
+        ephemeralgetbalances::{EphemeralGetBalances, EphemeralGetBalances::getBalancesCall},
+        ephemeralgettokenmetadata::{
+            EphemeralGetTokenMetadata, EphemeralGetTokenMetadata::getTokenMetadataCall,
+            TokenUtils::TokenMetadata,
+        },
+    },
+    caller::{call_ephemeral, CallOptions},
+    error::Error,
+};
+use alloc::vec::Vec;
+use alloy::{
+    network::Network,
+    primitives::{Address, U256},
+    providers::Provider,
+};
+
+/// Get the name, symbol, decimals and total supply of multiple tokens.
+///
+/// `name` and `symbol` returned as `bytes32` are decoded as strings. Fields that the token does not
+/// return fall back to an empty string, 18 decimals and zero total supply respectively, and the
+/// corresponding `has*` flag is `false`.
+///
+/// ## Arguments
+///
+/// * `tokens`: The addresses of the tokens
+/// * `provider`: The alloy provider
+/// * `options`: Optional block number or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// The metadata of each token
+#[inline]
+pub async fn get_token_metadata<N, P>(
+    tokens: Vec<Address>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<TokenMetadata>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let deploy_builder = EphemeralGetTokenMetadata::deploy_builder(provider, tokens);
+    call_ephemeral::<getTokenMetadataCall, _, _>(deploy_builder, options).await
+}
+
+/// The address representing the native token in [`get_balances`].
+pub const NATIVE_TOKEN: Address = Address::ZERO;
+
+/// Get the balances of multiple owners in multiple tokens.
+///
+/// ## Arguments
+///
+/// * `owners`: The addresses of the owners
+/// * `tokens`: The addresses of the tokens, where [`NATIVE_TOKEN`] represents the native token and
+///   an address without code, that reverts or that does not return a word has zero balances
+/// * `provider`: The alloy provider
+/// * `options`: Optional block number or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// A matrix where the balance of `owners[i]` in `tokens[j]` is at `[i][j]`
+#[inline]
+pub async fn get_balances<N, P>(
+    owners: Vec<Address>,
+    tokens: Vec<Address>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<Vec<U256>>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let deploy_builder = EphemeralGetBalances::deploy_builder(provider, owners, tokens);
+    call_ephemeral::<getBalancesCall, _, _>(deploy_builder, options).await
+}
+
+/// Get the allowances granted by an owner to multiple spenders in multiple tokens.
+///
+/// ## Arguments
+///
+/// * `owner`: The address of the owner
+/// * `tokens`: The addresses of the tokens, where an address without code, that reverts or that
+///   does not return a word has zero allowances
+/// * `spenders`: The addresses of the spenders
+/// * `provider`: The alloy provider
+/// * `options`: Optional block number or [`CallOptions`] to query with
+///
+/// ## Returns
+///
+/// A matrix where the allowance of `spenders[j]` in `tokens[i]` is at `[i][j]`
+#[inline]
+pub async fn get_allowances<N, P>(
+    owner: Address,
+    tokens: Vec<Address>,
+    spenders: Vec<Address>,
+    provider: P,
+    options: impl Into<CallOptions>,
+) -> Result<Vec<Vec<U256>>, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let deploy_builder = EphemeralGetAllowances::deploy_builder(provider, owner, tokens, spenders);
+    call_ephemeral::<getAllowancesCall, _, _>(deploy_builder, options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bindings::{ierc20::IERC20, ierc20metadata::IERC20Metadata},
+        tests::*,
+    };
+    use alloy::primitives::address;
+
+    const USDC: Address = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const MKR: Address = address!("9f8F72aA9304c8B593d555F12eF6589cC3A579A2");
+    const POOL: Address = address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+    const NPM: Address = address!("C36442b4a4522E871399CD717aBDD847Ab11FE88");
+    const ROUTER: Address = address!("E592427A0AEce92De3Edee1F18E0157C05861564");
+    const EOA: Address = address!("000000000000000000000000000000000000dEaD");
+
+    #[tokio::test]
+    async fn test_get_token_metadata() {
+        let provider = PROVIDER.clone();
+        let metadata = get_token_metadata(
+            vec![USDC, WETH, MKR, Address::ZERO],
+            provider.clone(),
+            Some(BLOCK_NUMBER),
+        )
+        .await
+        .unwrap();
+        assert_eq!(metadata.len(), 4);
+        for token in &metadata[..2] {
+            let contract = IERC20Metadata::new(token.token, provider.clone());
+            assert_eq!(
+                token.symbol,
+                contract.symbol().block(BLOCK_NUMBER).call().await.unwrap()
+            );
+            assert_eq!(
+                token.decimals,
+                contract
+                    .decimals()
+                    .block(BLOCK_NUMBER)
+                    .call()
+                    .await
+                    .unwrap()
+            );
+            assert_eq!(
+                token.totalSupply,
+                contract
+                    .totalSupply()
+                    .block(BLOCK_NUMBER)
+                    .call()
+                    .await
+                    .unwrap()
+            );
+            assert!(token.hasName && token.hasSymbol && token.hasDecimals && token.hasTotalSupply);
+        }
+        // MKR returns bytes32 for `name` and `symbol`
+        assert_eq!(metadata[2].symbol, "MKR");
+        assert!(metadata[2].hasSymbol);
+        // the zero address has no code
+        assert_eq!(metadata[3].decimals, 18);
+        assert!(!metadata[3].hasName && !metadata[3].hasDecimals);
+    }
+
+    #[tokio::test]
+    async fn test_get_balances() {
+        let provider = PROVIDER.clone();
+        let owners = vec![POOL, WETH];
+        let balances = get_balances(
+            owners.clone(),
+            vec![USDC, WETH, NATIVE_TOKEN, EOA, ROUTER],
+            provider.clone(),
+            Some(BLOCK_NUMBER),
+        )
+        .await
+        .unwrap();
+        assert_eq!(balances.len(), 2);
+        for (owner, balances) in owners.into_iter().zip(balances) {
+            for (i, token) in [USDC, WETH].into_iter().enumerate() {
+                let balance = IERC20::new(token, provider.clone())
+                    .balanceOf(owner)
+                    .block(BLOCK_NUMBER)
+                    .call()
+                    .await
+                    .unwrap();
+                assert_eq!(balances[i], balance);
+            }
+            let balance = provider
+                .get_balance(owner)
+                .block_id(BLOCK_NUMBER)
+                .await
+                .unwrap();
+            assert_eq!(balances[2], balance);
+            // an address without code and a contract that is not a token have no balances
+            assert_eq!(balances[3], U256::ZERO);
+            assert_eq!(balances[4], U256::ZERO);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_allowances() {
+        let provider = PROVIDER.clone();
+        let tokens = vec![USDC, WETH];
+        let spenders = vec![NPM, ROUTER];
+        let mut allowances = get_allowances(
+            POOL,
+            [tokens.clone(), vec![EOA, ROUTER]].concat(),
+            spenders.clone(),
+            provider.clone(),
+            Some(BLOCK_NUMBER),
+        )
+        .await
+        .unwrap();
+        assert_eq!(allowances.len(), 4);
+        // an address without code and a contract that is not a token have no allowances
+        assert_eq!(allowances.pop().unwrap(), vec![U256::ZERO; 2]);
+        assert_eq!(allowances.pop().unwrap(), vec![U256::ZERO; 2]);
+        for (token, allowances) in tokens.into_iter().zip(allowances) {
+            let token = IERC20::new(token, provider.clone());
+            for (spender, allowance) in spenders.iter().zip(allowances) {
+                let expected = token
+                    .allowance(POOL, *spender)
+                    .block(BLOCK_NUMBER)
+                    .call()
+                    .await
+                    .unwrap();
+                assert_eq!(allowance, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,522 @@
+//! ## Tracker
+//!
+//! The tracker module keeps a state maintained from a lens snapshot plus logs consistent with the
+//! canonical chain. It records the block hash of every applied block together with a checkpoint of
+//! the state, detects a reorg when the parent hash of a new block does not match the tracked head,
+//! rolls back to the last common ancestor and re-applies the canonical blocks from there. When the
+//! reorg is deeper than the tracked history, a fresh snapshot has to be taken instead.
+
+use crate::{error::Error, pool_state::PoolState};
+use alloc::collections::VecDeque;
+use alloy::{
+    consensus::BlockHeader,
+    eips::BlockNumberOrTag,
+    network::{primitives::HeaderResponse, BlockResponse, Network},
+    primitives::{Address, B256},
+    providers::Provider,
+    rpc::types::{Filter, Log},
+};
+
+/// A state that is updated by applying logs block by block.
+pub trait Tracked: Clone {
+    /// The filter of the logs that update the state, without a block range.
+    fn filter(&self) -> Filter;
+
+    /// Apply the logs of a block to the state, returning the number of logs applied.
+    fn apply_block(&mut self, block: &BlockRef, logs: &[Log]) -> Result<usize, Error>;
+}
+
+impl Tracked for PoolState {
+    #[inline]
+    fn filter(&self) -> Filter {
+        Filter::new().address(self.address)
+    }
+
+    #[inline]
+    fn apply_block(&mut self, block: &BlockRef, logs: &[Log]) -> Result<usize, Error> {
+        let applied = self.apply_logs(logs)?;
+        self.block_number = block.number;
+        self.log_index = None;
+        Ok(applied)
+    }
+}
+
+/// The identity of a block and its parent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct BlockRef {
+    /// The block number
+    pub number: u64,
+    /// The block hash
+    pub hash: B256,
+    /// The hash of the parent block
+    pub parent_hash: B256,
+}
+
+/// Get the identity of a block on the canonical chain.
+///
+/// ## Arguments
+///
+/// * `block_number`: The block number
+/// * `provider`: The alloy provider
+///
+/// ## Returns
+///
+/// The block number, hash and parent hash, or [`Error::BlockNotFound`]
+#[inline]
+pub async fn get_block_ref<N, P>(block_number: u64, provider: P) -> Result<BlockRef, Error>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let block = provider
+        .get_block_by_number(BlockNumberOrTag::Number(block_number))
+        .await?
+        .ok_or(Error::BlockNotFound)?;
+    let header = block.header();
+    Ok(BlockRef {
+        number: header.number(),
+        hash: header.hash(),
+        parent_hash: header.parent_hash(),
+    })
+}
+
+/// Tracks a state block by block and rolls it back on reorgs.
+///
+/// A checkpoint of the state is kept for each of the last `max_depth` blocks, so the memory use
+/// grows with the size of the state times `max_depth`.
+#[derive(Clone, Debug)]
+pub struct ReorgTracker<S> {
+    /// The applied blocks and the state after each of them, the last being the head
+    checkpoints: VecDeque<(BlockRef, S)>,
+    /// The maximum number of blocks that can be rolled back
+    max_depth: usize,
+}
+
+impl<S: Tracked> ReorgTracker<S> {
+    /// Create a tracker from a snapshot of the state at the end of a block.
+    ///
+    /// ## Arguments
+    ///
+    /// * `state`: The state at the end of `block`
+    /// * `block`: The block of the snapshot
+    /// * `max_depth`: The maximum number of blocks that can be rolled back
+    #[inline]
+    pub fn new(state: S, block: BlockRef, max_depth: usize) -> Self {
+        let mut checkpoints = VecDeque::with_capacity(max_depth + 1);
+        checkpoints.push_back((block, state));
+        Self {
+            checkpoints,
+            max_depth,
+        }
+    }
+
+    /// The state at the head.
+    #[inline]
+    pub fn state(&self) -> &S {
+        &self
+            .checkpoints
+            .back()
+            .expect("checkpoints are never empty")
+            .1
+    }
+
+    /// The last applied block.
+    #[inline]
+    pub fn head(&self) -> BlockRef {
+        self.checkpoints
+            .back()
+            .expect("checkpoints are never empty")
+            .0
+    }
+
+    /// The oldest block that can be rolled back to.
+    #[inline]
+    pub fn oldest(&self) -> BlockRef {
+        self.checkpoints
+            .front()
+            .expect("checkpoints are never empty")
+            .0
+    }
+
+    /// The number of blocks that can currently be rolled back.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.checkpoints.len() - 1
+    }
+
+    /// Apply the logs of the block following the head.
+    ///
+    /// ## Arguments
+    ///
+    /// * `block`: The block following the head
+    /// * `logs`: The logs of `block` matching [`Tracked::filter`]
+    ///
+    /// ## Returns
+    ///
+    /// The number of logs applied, or [`Error::ParentHashMismatch`] if the block does not extend
+    /// the head
+    #[inline]
+    pub fn apply_block(&mut self, block: BlockRef, logs: &[Log]) -> Result<usize, Error> {
+        let head = self.head();
+        if block.number != head.number + 1 || block.parent_hash != head.hash {
+            return Err(Error::ParentHashMismatch);
+        }
+        if logs
+            .iter()
+            .any(|log| log.block_hash.is_some_and(|hash| hash != block.hash))
+        {
+            return Err(Error::ParentHashMismatch);
+        }
+        let mut state = self.state().clone();
+        let applied = state.apply_block(&block, logs)?;
+        self.checkpoints.push_back((block, state));
+        while self.checkpoints.len() > self.max_depth + 1 {
+            self.checkpoints.pop_front();
+        }
+        Ok(applied)
+    }
+
+    /// Roll back the state to the end of a previously applied block.
+    ///
+    /// ## Arguments
+    ///
+    /// * `block_number`: The block to roll back to
+    ///
+    /// ## Returns
+    ///
+    /// The number of blocks rolled back, or [`Error::ReorgTooDeep`] if the block is not tracked
+    #[inline]
+    pub fn rollback(&mut self, block_number: u64) -> Result<usize, Error> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|(block, _)| block.number == block_number)
+            .ok_or(Error::ReorgTooDeep)?;
+        let rolled_back = self.checkpoints.len() - 1 - index;
+        self.checkpoints.truncate(index + 1);
+        Ok(rolled_back)
+    }
+
+    /// Find the most recent tracked block that is still on the canonical chain.
+    ///
+    /// ## Arguments
+    ///
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The last common ancestor, or `None` if none of the tracked blocks are canonical
+    #[inline]
+    pub async fn find_common_ancestor<N, P>(&self, provider: P) -> Result<Option<BlockRef>, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        for (block, _) in self.checkpoints.iter().rev() {
+            if get_block_ref(block.number, &provider).await?.hash == block.hash {
+                return Ok(Some(*block));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Apply the canonical blocks after the head up to `to_block`, rolling back to the last common
+    /// ancestor and re-applying the canonical blocks whenever a reorg is detected. The head is
+    /// checked to still be canonical before returning, so that a block replaced at the same height
+    /// is detected even if `to_block` is not past the head.
+    ///
+    /// ## Arguments
+    ///
+    /// * `to_block`: The last block to apply, inclusive
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The total number of blocks rolled back, [`Error::ReorgTooDeep`] if the reorg is deeper than
+    /// the tracked history, or [`Error::ParentHashMismatch`] if the next block does not extend the
+    /// head although the head is still canonical, e.g. on a lagging or load-balanced RPC
+    #[inline]
+    pub async fn sync<N, P>(&mut self, to_block: u64, provider: P) -> Result<usize, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        let mut rolled_back = 0;
+        loop {
+            let head = self.head();
+            if head.number < to_block {
+                let block = get_block_ref(head.number + 1, &provider).await?;
+                if block.parent_hash == head.hash {
+                    // query by block hash so that the logs belong to exactly this block
+                    let filter = self.state().filter().at_block_hash(block.hash);
+                    let logs = provider.get_logs(&filter).await?;
+                    self.apply_block(block, &logs)?;
+                    continue;
+                }
+            } else if get_block_ref(head.number, &provider).await?.hash == head.hash {
+                // the head may also have been replaced at the same height
+                break;
+            }
+            let ancestor = self
+                .find_common_ancestor(&provider)
+                .await?
+                .ok_or(Error::ReorgTooDeep)?;
+            // the provider disagrees with itself, retrying would refetch the same block
+            if ancestor == head {
+                return Err(Error::ParentHashMismatch);
+            }
+            rolled_back += self.rollback(ancestor.number)?;
+        }
+        Ok(rolled_back)
+    }
+}
+
+impl ReorgTracker<PoolState> {
+    /// Create a tracker from a fresh lens snapshot of a pool.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The address of a V3 pool
+    /// * `block_number`: The block number of the snapshot
+    /// * `max_depth`: The maximum number of blocks that can be rolled back
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// The tracker with the snapshot as its head
+    #[inline]
+    pub async fn snapshot<N, P>(
+        pool: Address,
+        block_number: u64,
+        max_depth: usize,
+        provider: P,
+    ) -> Result<Self, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        let block = get_block_ref(block_number, &provider).await?;
+        // pin the snapshot to the block hash in case the block is reorged meanwhile
+        let state = PoolState::fetch(pool, block_number, &provider, None).await?;
+        if get_block_ref(block_number, &provider).await?.hash != block.hash {
+            return Err(Error::ParentHashMismatch);
+        }
+        Ok(Self::new(state, block, max_depth))
+    }
+
+    /// Sync the pool state up to `to_block`, taking a fresh lens snapshot at `to_block` if the
+    /// reorg is deeper than the tracked history.
+    ///
+    /// ## Arguments
+    ///
+    /// * `to_block`: The last block to apply, inclusive
+    /// * `provider`: The alloy provider
+    ///
+    /// ## Returns
+    ///
+    /// Whether a fresh snapshot was taken
+    #[inline]
+    pub async fn sync_or_snapshot<N, P>(
+        &mut self,
+        to_block: u64,
+        provider: P,
+    ) -> Result<bool, Error>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        match self.sync(to_block, &provider).await {
+            Ok(_) => Ok(false),
+            Err(Error::ReorgTooDeep) => {
+                let address = self.state().address;
+                *self = Self::snapshot(address, to_block, self.max_depth, &provider).await?;
+                Ok(true)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use alloc::vec::Vec;
+    use alloy::{
+        primitives::address,
+        providers::ProviderBuilder,
+        rpc::types::{Block, Header},
+        transports::mock::Asserter,
+    };
+
+    /// A state that records the blocks of the logs applied to it.
+    #[derive(Clone, Debug, Default)]
+    struct MockState(Vec<u64>);
+
+    impl Tracked for MockState {
+        fn filter(&self) -> Filter {
+            Filter::new()
+        }
+
+        fn apply_block(&mut self, _: &BlockRef, logs: &[Log]) -> Result<usize, Error> {
+            self.0
+                .extend(logs.iter().filter_map(|log| log.block_number));
+            Ok(logs.len())
+        }
+    }
+
+    fn block(number: u64, fork: u8) -> BlockRef {
+        let hash = |number: u64, fork: u8| {
+            let mut hash = B256::ZERO;
+            hash[..8].copy_from_slice(&number.to_be_bytes());
+            // the common history before block 10 is not forked
+            hash[8] = if number < 10 { 0 } else { fork };
+            hash
+        };
+        BlockRef {
+            number,
+            hash: hash(number, fork),
+            parent_hash: hash(number - 1, fork),
+        }
+    }
+
+    fn log(block: BlockRef) -> Log {
+        Log {
+            block_number: Some(block.number),
+            block_hash: Some(block.hash),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_block_and_rollback() {
+        let mut tracker = ReorgTracker::new(MockState::default(), block(8, 0), 3);
+        for number in 9..=12 {
+            let block = block(number, 0);
+            assert_eq!(tracker.apply_block(block, &[log(block)]).unwrap(), 1);
+        }
+        assert_eq!(tracker.head(), block(12, 0));
+        assert_eq!(tracker.state().0, vec![9, 10, 11, 12]);
+        // only the last `max_depth` blocks can be rolled back
+        assert_eq!(tracker.depth(), 3);
+        assert_eq!(tracker.oldest(), block(9, 0));
+
+        // a block on another fork does not extend the head
+        assert!(matches!(
+            tracker.apply_block(block(13, 1), &[]),
+            Err(Error::ParentHashMismatch)
+        ));
+        // a log from another block
+        assert!(matches!(
+            tracker.apply_block(block(13, 0), &[log(block(13, 1))]),
+            Err(Error::ParentHashMismatch)
+        ));
+        // a block that is not next
+        assert!(matches!(
+            tracker.apply_block(block(14, 0), &[]),
+            Err(Error::ParentHashMismatch)
+        ));
+
+        // roll back to the fork point and re-apply the other fork
+        assert_eq!(tracker.rollback(9).unwrap(), 3);
+        assert_eq!(tracker.head(), block(9, 0));
+        assert_eq!(tracker.state().0, vec![9]);
+        for number in 10..=13 {
+            let block = block(number, 1);
+            tracker.apply_block(block, &[log(block)]).unwrap();
+        }
+        assert_eq!(tracker.head(), block(13, 1));
+        assert_eq!(tracker.state().0, vec![9, 10, 11, 12, 13]);
+        assert!(matches!(tracker.rollback(8), Err(Error::ReorgTooDeep)));
+    }
+
+    fn rpc_block(block: BlockRef) -> Block<()> {
+        Block {
+            header: Header {
+                hash: block.hash,
+                inner: alloy::consensus::Header {
+                    number: block.number,
+                    parent_hash: block.parent_hash,
+                    ..Default::default()
+                },
+                total_difficulty: None,
+                size: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_inconsistent_provider() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let mut tracker = ReorgTracker::new(MockState::default(), block(12, 0), 3);
+        // the next block is on another fork while the head is still reported as canonical
+        asserter.push_success(&rpc_block(block(13, 1)));
+        asserter.push_success(&rpc_block(block(12, 0)));
+        assert!(matches!(
+            tracker.sync(13, &provider).await,
+            Err(Error::ParentHashMismatch)
+        ));
+        assert_eq!(tracker.head(), block(12, 0));
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_replaced_head() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        let mut tracker = ReorgTracker::new(MockState::default(), block(8, 0), 5);
+        for number in 9..=12 {
+            tracker.apply_block(block(number, 0), &[]).unwrap();
+        }
+        // the head is replaced at the same height
+        asserter.push_success(&rpc_block(block(12, 1)));
+        // the common ancestor search from the head
+        for number in (9..=12).rev() {
+            asserter.push_success(&rpc_block(block(number, 1)));
+        }
+        // the blocks of the new fork and their logs
+        for number in 10..=12 {
+            asserter.push_success(&rpc_block(block(number, 1)));
+            asserter.push_success(&[log(block(number, 1))]);
+        }
+        // the new head is canonical
+        asserter.push_success(&rpc_block(block(12, 1)));
+        assert_eq!(tracker.sync(12, &provider).await.unwrap(), 3);
+        assert_eq!(tracker.head(), block(12, 1));
+        assert_eq!(tracker.state().0, vec![10, 11, 12]);
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let provider = PROVIDER.clone();
+        let block_number = BLOCK_NUMBER.as_u64().unwrap();
+        let mut tracker = ReorgTracker::snapshot(
+            address!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+            block_number - 10,
+            64,
+            provider.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            tracker.sync(block_number, provider.clone()).await.unwrap(),
+            0
+        );
+        assert_eq!(tracker.head().number, block_number);
+        assert_eq!(tracker.depth(), 10);
+        tracker
+            .state()
+            .verify_with_lens(provider, None)
+            .await
+            .unwrap();
+    }
+}
@@ -2,3 +2,4 @@ export * from "./caller";
 export * from "./poolLens";
 export * from "./positionLens";
 export * from "./storageLens";
+export * from "./tokenLens";
@@ -2,9 +2,12 @@ import { Address, PublicClient } from "viem";
 import { callEphemeralContract } from "./caller";
 import {
   EphemeralAllPositionsByOwner__factory,
+  EphemeralGetFeesOwed__factory,
   EphemeralGetPosition__factory,
   EphemeralGetPositions__factory,
+  EphemeralGetTotalAmounts__factory,
 } from "../../typechain";
+import { PositionKey } from "./poolLens";
 
 /**
  * Get the position details in a single call by deploying an ephemeral contract via `eth_call`
@@ -82,3 +85,54 @@ export async function getAllPositionsByOwner(
     blockNumber,
   );
 }
+
+/**
+ * Get the fees owed to positions minted directly on a pool by deploying an ephemeral contract via `eth_call`.
+ * @param pool The address of the pool.
+ * @param keys The position keys.
+ * @param publicClient Viem public client.
+ * @param blockNumber Optional block number to query.
+ * @returns The fees owed to each position.
+ */
+export async function getFeesOwed(
+  pool: Address,
+  keys: PositionKey[],
+  publicClient: PublicClient,
+  blockNumber?: bigint,
+) {
+  return await callEphemeralContract(
+    {
+      abi: EphemeralGetFeesOwed__factory.abi,
+      bytecode: EphemeralGetFeesOwed__factory.bytecode,
+      args: [pool, keys],
+    },
+    publicClient,
+    blockNumber,
+  );
+}
+
+/**
+ * Get the total amounts including fees held in positions minted directly on a pool by deploying an ephemeral contract
+ * via `eth_call`.
+ * @param pool The address of the pool.
+ * @param keys The position keys.
+ * @param publicClient Viem public client.
+ * @param blockNumber Optional block number to query.
+ * @returns The total amounts of token0 and token1 held in each position.
+ */
+export async function getTotalAmounts(
+  pool: Address,
+  keys: PositionKey[],
+  publicClient: PublicClient,
+  blockNumber?: bigint,
+) {
+  return await callEphemeralContract(
+    {
+      abi: EphemeralGetTotalAmounts__factory.abi,
+      bytecode: EphemeralGetTotalAmounts__factory.bytecode,
+      args: [pool, keys],
+    },
+    publicClient,
+    blockNumber,
+  );
+}
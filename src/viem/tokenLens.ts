@@ -0,0 +1,79 @@
+import { Address, PublicClient } from "viem";
+import {
+  EphemeralGetAllowances__factory,
+  EphemeralGetBalances__factory,
+  EphemeralGetTokenMetadata__factory,
+} from "../../typechain";
+import { callEphemeralContract } from "./caller";
+
+/**
+ * Get the name, symbol, decimals and total supply of tokens by deploying an ephemeral contract via `eth_call`.
+ * `name` and `symbol` returned as `bytes32` are decoded as strings, and fields the token does not return are flagged.
+ * @param tokens The addresses of the tokens.
+ * @param publicClient Viem public client.
+ * @param blockNumber Optional block number to query.
+ * @returns The metadata of each token.
+ */
+export async function getTokenMetadata(tokens: Address[], publicClient: PublicClient, blockNumber?: bigint) {
+  return await callEphemeralContract(
+    {
+      abi: EphemeralGetTokenMetadata__factory.abi,
+      bytecode: EphemeralGetTokenMetadata__factory.bytecode,
+      args: [tokens],
+    },
+    publicClient,
+    blockNumber,
+  );
+}
+
+/**
+ * Get the balances of owners in tokens by deploying an ephemeral contract via `eth_call`.
+ * @param owners The addresses of the owners.
+ * @param tokens The addresses of the tokens, where the zero address represents the native token.
+ * @param publicClient Viem public client.
+ * @param blockNumber Optional block number to query.
+ * @returns A matrix where the balance of `owners[i]` in `tokens[j]` is at `[i][j]`.
+ */
+export async function getBalances(
+  owners: Address[],
+  tokens: Address[],
+  publicClient: PublicClient,
+  blockNumber?: bigint,
+) {
+  return await callEphemeralContract(
+    {
+      abi: EphemeralGetBalances__factory.abi,
+      bytecode: EphemeralGetBalances__factory.bytecode,
+      args: [owners, tokens],
+    },
+    publicClient,
+    blockNumber,
+  );
+}
+
+/**
+ * Get the allowances granted by an owner to spenders in tokens by deploying an ephemeral contract via `eth_call`.
+ * @param owner The address of the owner.
+ * @param tokens The addresses of the tokens.
+ * @param spenders The addresses of the spenders.
+ * @param publicClient Viem public client.
+ * @param blockNumber Optional block number to query.
+ * @returns A matrix where the allowance of `spenders[j]` in `tokens[i]` is at `[i][j]`.
+ */
+export async function getAllowances(
+  owner: Address,
+  tokens: Address[],
+  spenders: Address[],
+  publicClient: PublicClient,
+  blockNumber?: bigint,
+) {
+  return await callEphemeralContract(
+    {
+      abi: EphemeralGetAllowances__factory.abi,
+      bytecode: EphemeralGetAllowances__factory.bytecode,
+      args: [owner, tokens, spenders],
+    },
+    publicClient,
+    blockNumber,
+  );
+}
@@ -2,8 +2,10 @@
 pragma solidity ^0.8.0;
 
 import "contracts/EphemeralAllPositionsByOwner.sol";
+import "contracts/EphemeralGetFeesOwed.sol";
 import "contracts/EphemeralGetPosition.sol";
 import "contracts/EphemeralGetPositions.sol";
+import "contracts/EphemeralGetTotalAmounts.sol";
 import "contracts/PositionLens.sol";
 import "./Base.t.sol";
 
@@ -84,6 +86,54 @@ contract PositionLensTest is BaseTest {
         assertEq(collect1, amount1);
     }
 
+    function positionKeys() internal view returns (PoolUtils.PositionKey[] memory keys) {
+        keys = new PoolUtils.PositionKey[](2);
+        keys[0] = PoolUtils.PositionKey(address(this), _tickLower, _tickUpper);
+        // a nonexistent position
+        keys[1] = PoolUtils.PositionKey(address(this), _tickLower - tickSpacing, _tickUpper + tickSpacing);
+    }
+
+    function test_GetFeesOwedBatch() public {
+        generateFees();
+        PoolUtils.PositionKey[] memory keys = positionKeys();
+        try new EphemeralGetFeesOwed(V3PoolCallee.wrap(pool), keys) {} catch (bytes memory returnData) {
+            PoolUtils.PositionFees[] memory fees = abi.decode(returnData, (PoolUtils.PositionFees[]));
+            assertEq(fees.length, keys.length);
+            for (uint256 i; i < keys.length; ++i) {
+                (uint128 fees0, uint128 fees1) = positionLens.getFeesOwed(
+                    V3PoolCallee.wrap(pool),
+                    keys[i].owner,
+                    keys[i].tickLower,
+                    keys[i].tickUpper
+                );
+                assertEq(fees[i].tokensOwed0, fees0, "tokensOwed0");
+                assertEq(fees[i].tokensOwed1, fees1, "tokensOwed1");
+            }
+            assertGt(uint256(fees[0].tokensOwed0) + fees[0].tokensOwed1, 0);
+        }
+    }
+
+    function test_GetTotalAmountsBatch() public {
+        generateFees();
+        PoolUtils.PositionKey[] memory keys = positionKeys();
+        try new EphemeralGetTotalAmounts(V3PoolCallee.wrap(pool), keys) {} catch (bytes memory returnData) {
+            PoolUtils.PositionAmounts[] memory amounts = abi.decode(returnData, (PoolUtils.PositionAmounts[]));
+            assertEq(amounts.length, keys.length);
+            for (uint256 i; i < keys.length; ++i) {
+                (uint256 amount0, uint256 amount1) = positionLens.getTotalAmounts(
+                    V3PoolCallee.wrap(pool),
+                    keys[i].owner,
+                    keys[i].tickLower,
+                    keys[i].tickUpper
+                );
+                assertEq(amounts[i].amount0, amount0, "amount0");
+                assertEq(amounts[i].amount1, amount1, "amount1");
+            }
+            assertEq(amounts[1].amount0, 0);
+            assertEq(amounts[1].amount1, 0);
+        }
+    }
+
     function verifyPosition(PositionState memory pos) internal view {
         {
             assertEq(pos.owner, npm.ownerOf(pos.tokenId), "owner");
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "contracts/EphemeralGetAllowances.sol";
+import "contracts/EphemeralGetBalances.sol";
+import "contracts/EphemeralGetTokenMetadata.sol";
+import "./Base.t.sol";
+
+contract TokenLensTest is BaseTest {
+    address internal constant MKR = 0x9f8F72aA9304c8B593d555F12eF6589cC3A579A2;
+
+    function test_GetTokenMetadata() public {
+        address[] memory tokens = new address[](4);
+        tokens[0] = USDC;
+        tokens[1] = WETH;
+        tokens[2] = MKR;
+        // an address without code
+        tokens[3] = address(0xdead);
+        try new EphemeralGetTokenMetadata(tokens) {} catch (bytes memory returnData) {
+            TokenUtils.TokenMetadata[] memory metadata = abi.decode(returnData, (TokenUtils.TokenMetadata[]));
+            assertEq(metadata.length, tokens.length);
+            for (uint256 i; i < 2; ++i) {
+                IERC20Metadata token = IERC20Metadata(tokens[i]);
+                assertEq(metadata[i].token, tokens[i], "token");
+                assertEq(metadata[i].name, token.name(), "name");
+                assertEq(metadata[i].symbol, token.symbol(), "symbol");
+                assertEq(metadata[i].decimals, token.decimals(), "decimals");
+                assertEq(metadata[i].totalSupply, token.totalSupply(), "totalSupply");
+                assertTrue(metadata[i].hasName && metadata[i].hasSymbol);
+                assertTrue(metadata[i].hasDecimals && metadata[i].hasTotalSupply);
+            }
+            // MKR returns bytes32 for `name` and `symbol`
+            assertEq(metadata[2].name, "Maker");
+            assertEq(metadata[2].symbol, "MKR");
+            assertEq(metadata[2].decimals, 18);
+            assertTrue(metadata[2].hasName && metadata[2].hasSymbol);
+            // every field falls back to the default
+            assertEq(metadata[3].name, "");
+            assertEq(metadata[3].decimals, 18);
+            assertEq(metadata[3].totalSupply, 0);
+            assertFalse(metadata[3].hasName || metadata[3].hasSymbol);
+            assertFalse(metadata[3].hasDecimals || metadata[3].hasTotalSupply);
+        }
+    }
+
+    function test_GetBalances() public {
+        address[] memory owners = new address[](2);
+        owners[0] = pool;
+        owners[1] = WETH;
+        address[] memory tokens = new address[](5);
+        tokens[0] = USDC;
+        tokens[1] = WETH;
+        // the native token
+        tokens[2] = address(0);
+        // an address without code
+        tokens[3] = address(0xdead);
+        // a contract that reverts on `balanceOf`
+        tokens[4] = pool;
+        try new EphemeralGetBalances(owners, tokens) {} catch (bytes memory returnData) {
+            uint256[][] memory balances = abi.decode(returnData, (uint256[][]));
+            assertEq(balances.length, owners.length);
+            for (uint256 i; i < owners.length; ++i) {
+                assertEq(balances[i].length, tokens.length);
+                assertEq(balances[i][0], IERC20(USDC).balanceOf(owners[i]), "USDC");
+                assertEq(balances[i][1], IERC20(WETH).balanceOf(owners[i]), "WETH");
+                assertEq(balances[i][2], owners[i].balance, "ETH");
+                assertEq(balances[i][3], 0, "EOA");
+                assertEq(balances[i][4], 0, "pool");
+            }
+        }
+    }
+
+    function test_GetAllowances() public {
+        address[] memory tokens = new address[](4);
+        tokens[0] = USDC;
+        tokens[1] = WETH;
+        // an address without code
+        tokens[2] = address(0xdead);
+        // a contract that reverts on `allowance`
+        tokens[3] = pool;
+        address[] memory spenders = new address[](2);
+        spenders[0] = address(npm);
+        spenders[1] = pool;
+        IERC20(USDC).approve(address(npm), 1);
+        IERC20(WETH).approve(pool, type(uint256).max);
+        try new EphemeralGetAllowances(address(this), tokens, spenders) {} catch (bytes memory returnData) {
+            uint256[][] memory allowances = abi.decode(returnData, (uint256[][]));
+            assertEq(allowances.length, tokens.length);
+            for (uint256 i; i < 2; ++i) {
+                for (uint256 j; j < spenders.length; ++j) {
+                    assertEq(allowances[i][j], IERC20(tokens[i]).allowance(address(this), spenders[j]));
+                }
+            }
+            for (uint256 i = 2; i < tokens.length; ++i) {
+                assertEq(allowances[i].length, spenders.length);
+                assertEq(allowances[i][0], 0);
+                assertEq(allowances[i][1], 0);
+            }
+            assertEq(allowances[0][0], 1);
+            assertEq(allowances[1][1], type(uint256).max);
+        }
+    }
+}